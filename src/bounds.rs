@@ -0,0 +1,230 @@
+use std::fmt::Debug;
+
+use crate::{instance::RcpspInstance, state::{State, ConsumptionProfile}, model::Rcpsp};
+
+/// A lower bound on the makespan that can be reached from a given state,
+/// expressed in the time frame of that state
+pub trait BoundProvider: Debug {
+    fn lower_bound(&self, state: &State, inst: &RcpspInstance) -> isize;
+}
+
+/// This bound relaxes the resource constraints and only considers the longest
+/// path from each unscheduled job to the sink. Each job must also be followed by
+/// all its descendants, whose energy must fit in the capacity of each resource.
+/// The optional jobs are ignored since they may be dropped.
+#[derive(Debug, Clone, Default)]
+pub struct CriticalPathBound {
+    /// Minimum time from the start of each job to the start of the sink
+    pub tail: Vec<isize>,
+}
+
+impl CriticalPathBound {
+    pub fn new(pb: &Rcpsp) -> Self {
+        let inst = &pb.instance;
+        let mut tail = vec![0; inst.n_jobs];
+        for i in pb.topo_order.iter().copied().rev() {
+            for (j, lag) in pb.lags[i].iter().copied() {
+                if !inst.optional.contains(j) {
+                    tail[i] = tail[i].max(lag + tail[j]);
+                }
+            }
+
+            if !inst.time_lags.is_empty() { // descendants may overlap with the job
+                continue;
+            }
+            if !inst.optional.is_clear() { // dropping a job may remove some descendants
+                continue;
+            }
+
+            for r in 0..inst.n_resources {
+                let energy = pb.descendants[i].ones().map(|j| pb.energy[j][r]).sum::<isize>();
+                if energy > 0 && inst.capacity[r] > 0 {
+                    tail[i] = tail[i].max(inst.duration[i] + (energy + inst.capacity[r] - 1) / inst.capacity[r]);
+                }
+            }
+        }
+        Self { tail }
+    }
+}
+
+impl BoundProvider for CriticalPathBound {
+    fn lower_bound(&self, state: &State, inst: &RcpspInstance) -> isize {
+        let mut bound = 0;
+        for i in 0..inst.n_jobs {
+            if !state.done.contains(i) && !inst.optional.contains(i) {
+                bound = bound.max(state.earliest[i] + self.tail[i]);
+            }
+        }
+        bound
+    }
+}
+
+/// This bound allows the jobs to be preempted and only requires the energy
+/// (duration times consumption) of the unscheduled jobs to fit in the remaining
+/// capacity of each resource, starting from the earliest unscheduled job.
+/// It is combined with the critical path bound by taking the max of both.
+#[derive(Debug, Clone)]
+pub struct EnergyBound {
+    pub critical_path: CriticalPathBound,
+    /// Energy of each job on each resource
+    pub energy: Vec<Vec<isize>>,
+}
+
+impl EnergyBound {
+    pub fn new(pb: &Rcpsp) -> Self {
+        Self { critical_path: CriticalPathBound::new(pb), energy: pb.energy.clone() }
+    }
+
+    fn energy_completion(profile: &ConsumptionProfile, from: isize, energy: isize) -> isize {
+        let mut remaining = energy;
+        if remaining <= 0 {
+            return from;
+        }
+
+        for step in profile.steps.iter() {
+            if step.end <= from || step.rem_capacity <= 0 {
+                continue;
+            }
+
+            let start = step.start.max(from);
+            let available = (step.end.saturating_sub(start)).saturating_mul(step.rem_capacity);
+            if available >= remaining {
+                return start + (remaining + step.rem_capacity - 1) / step.rem_capacity;
+            }
+            remaining -= available;
+        }
+
+        from
+    }
+}
+
+impl BoundProvider for EnergyBound {
+    fn lower_bound(&self, state: &State, inst: &RcpspInstance) -> isize {
+        let mut bound = self.critical_path.lower_bound(state, inst);
+
+        let mut from = isize::MAX;
+        let mut energy = vec![0; inst.n_resources];
+        for i in 0..inst.n_jobs {
+            if !state.done.contains(i) && !inst.optional.contains(i) {
+                from = from.min(state.earliest[i]);
+                for r in 0..inst.n_resources {
+                    energy[r] += self.energy[i][r];
+                }
+            }
+        }
+
+        if from == isize::MAX {
+            return bound;
+        }
+
+        for r in 0..inst.n_resources {
+            bound = bound.max(Self::energy_completion(&state.profile[r], from, energy[r]));
+        }
+
+        bound
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use std::{io::BufReader, sync::{Arc, atomic::{AtomicUsize, Ordering}}};
+
+    use crate::model::{Rcpsp, tests::solve};
+
+    use super::*;
+
+    /// Trivial bound counting the number of times it is computed
+    #[derive(Debug, Default)]
+    struct ZeroBound {
+        calls: AtomicUsize,
+    }
+
+    impl BoundProvider for ZeroBound {
+        fn lower_bound(&self, _state: &State, _inst: &RcpspInstance) -> isize {
+            self.calls.fetch_add(1, Ordering::Relaxed);
+            0
+        }
+    }
+
+    fn parse(content: &str) -> RcpspInstance {
+        RcpspInstance::try_from(BufReader::new(content.as_bytes())).unwrap()
+    }
+
+    /// Small instances along with their optimal makespan
+    fn instances() -> Vec<(RcpspInstance, isize)> {
+        vec![
+            // three independent jobs on a resource of capacity 2
+            (parse("5 1\n2\n0 0 3 2 3 4\n3 1 1 5\n2 1 1 5\n2 1 1 5\n0 0 0\n"), 4),
+            // the successors of the two chains conflict on both resources
+            (parse("6 2\n2 1\n0 0 0 2 2 3\n4 1 1 1 4\n2 1 0 1 5\n3 2 0 1 6\n3 1 1 1 6\n0 0 0 0\n"), 10),
+            // no precedence but a single unit of resource, so that the energy bound is tight
+            (parse("5 1\n1\n0 0 3 2 3 4\n2 1 1 5\n3 1 1 5\n1 1 1 5\n0 0 0\n"), 6),
+        ]
+    }
+
+    #[test]
+    fn root_bounds_do_not_exceed_the_optimum() {
+        for (k, (inst, optimum)) in instances().into_iter().enumerate() {
+            let pb = Rcpsp::new(inst);
+            assert_eq!(solve(&pb), optimum, "instance {}", k);
+
+            let critical_path = CriticalPathBound::new(&pb).lower_bound(&pb.initial, &pb.instance);
+            let energy = EnergyBound::new(&pb).lower_bound(&pb.initial, &pb.instance);
+            assert!(critical_path <= optimum, "instance {}: critical path bound {} above {}", k, critical_path, optimum);
+            assert!(energy <= optimum, "instance {}: energy bound {} above {}", k, energy, optimum);
+            assert!(energy >= critical_path, "instance {}: energy bound {} below the critical path {}", k, energy, critical_path);
+        }
+    }
+
+    #[test]
+    fn energy_bound_is_tight_on_a_single_unit_of_resource() {
+        let (inst, optimum) = instances().remove(2);
+        let pb = Rcpsp::new(inst);
+        assert_eq!(EnergyBound::new(&pb).lower_bound(&pb.initial, &pb.instance), optimum);
+    }
+
+    #[test]
+    fn problem_lower_bound_lies_between_the_trivial_bound_and_the_optimum() {
+        for (k, (inst, optimum)) in instances().into_iter().enumerate() {
+            let pb = Rcpsp::new(inst);
+            let bound = pb.lower_bound();
+            assert!(bound <= optimum, "instance {}: bound {} above {}", k, bound, optimum);
+
+            // trivially, the longest job must fit, and the jobs that need the whole capacity
+            // of a resource must run one after the other
+            let inst = &pb.instance;
+            let longest = inst.duration.iter().copied().max().unwrap_or(0);
+            let sequential = (0..inst.n_resources)
+                .map(|r| (0..inst.n_jobs).filter(|i| inst.consumption[*i][r] == inst.capacity[r]).map(|i| inst.duration[i]).sum::<isize>())
+                .max().unwrap_or(0);
+            assert!(bound >= longest.max(sequential), "instance {}: bound {} below the trivial {}", k, bound, longest.max(sequential));
+        }
+    }
+
+    #[test]
+    fn total_energy_bound_divides_the_energy_by_the_capacity() {
+        let instances = instances();
+        // 7 units of energy on a capacity of 2, then 15 units on a capacity of 2 (which beats
+        // 7 units on a capacity of 1), and 6 units on a capacity of 1
+        let expected = [4, 8, 6];
+        for (k, (inst, optimum)) in instances.into_iter().enumerate() {
+            let pb = Rcpsp::new(inst);
+            let bound = pb.total_energy_bound();
+            assert_eq!(bound, expected[k], "instance {}", k);
+            assert!(bound <= optimum, "instance {}: total energy bound {} above {}", k, bound, optimum);
+            assert!(pb.lower_bound() >= bound, "instance {}", k);
+        }
+    }
+
+    #[test]
+    fn solver_calls_the_bound_and_remains_correct_with_a_trivial_one() {
+        // jobs 2 and 3 can overlap but job 4 needs the whole resource
+        let mut problem = Rcpsp::new(parse("5 1\n2\n0 0 3 2 3 4\n3 1 1 5\n2 1 1 5\n2 2 1 5\n0 0 0\n"));
+        assert_eq!(solve(&problem), 5);
+
+        let bound = Arc::new(ZeroBound::default());
+        problem.bound = bound.clone();
+        assert_eq!(solve(&problem), 5);
+        assert!(bound.calls.load(Ordering::Relaxed) > 0);
+    }
+}
@@ -0,0 +1,104 @@
+/// Unit of the time of the instances when rendered as dates
+#[derive(Debug, Clone, Copy, PartialEq, Eq, clap::ValueEnum)]
+pub enum TimeUnit {
+    Day,
+    Hour,
+}
+
+/// Renders the times of the schedules as dates counted from a start date
+#[derive(Debug, Clone, Copy)]
+pub struct Calendar {
+    /// Number of days from 1970-01-01 to the start date
+    pub start: i64,
+    pub unit: TimeUnit,
+}
+
+impl Calendar {
+    pub fn new(start: i64, unit: TimeUnit) -> Self {
+        Self { start, unit }
+    }
+
+    /// Date at the given time, with the hour when the unit is the hour
+    pub fn render(&self, time: isize) -> String {
+        let time = time as i64;
+        match self.unit {
+            TimeUnit::Day => format_date(self.start + time),
+            TimeUnit::Hour => format!("{} {:02}:00", format_date(self.start + time.div_euclid(24)), time.rem_euclid(24)),
+        }
+    }
+}
+
+/// Parses a date given as YYYY-MM-DD into its number of days from 1970-01-01
+pub fn parse_date(s: &str) -> Result<i64, String> {
+    let parts = s.split('-').map(|p| p.parse::<i64>()).collect::<Result<Vec<i64>, _>>()
+        .map_err(|_| format!("expected YYYY-MM-DD, found {}", s))?;
+    if parts.len() != 3 {
+        return Err(format!("expected YYYY-MM-DD, found {}", s));
+    }
+    let (year, month, day) = (parts[0], parts[1], parts[2]);
+    if !(1..=12).contains(&month) || day < 1 || day > days_in_month(year, month) {
+        return Err(format!("{} is not a valid date", s));
+    }
+    Ok(days_from_civil(year, month, day))
+}
+
+fn is_leap(year: i64) -> bool {
+    (year % 4 == 0 && year % 100 != 0) || year % 400 == 0
+}
+
+fn days_in_month(year: i64, month: i64) -> i64 {
+    match month {
+        2 if is_leap(year) => 29,
+        2 => 28,
+        4 | 6 | 9 | 11 => 30,
+        _ => 31,
+    }
+}
+
+/// Number of days from 1970-01-01 to the given date of the proleptic Gregorian calendar,
+/// counting the years from March so that the leap day comes last
+fn days_from_civil(year: i64, month: i64, day: i64) -> i64 {
+    let year = if month <= 2 { year - 1 } else { year };
+    let era = year.div_euclid(400);
+    let year_of_era = year - era * 400;
+    let day_of_year = (153 * (month + if month > 2 { -3 } else { 9 }) + 2) / 5 + day - 1;
+    let day_of_era = year_of_era * 365 + year_of_era / 4 - year_of_era / 100 + day_of_year;
+    era * 146097 + day_of_era - 719468
+}
+
+/// Inverse of days_from_civil, formatted as YYYY-MM-DD
+fn format_date(days: i64) -> String {
+    let days = days + 719468;
+    let era = days.div_euclid(146097);
+    let day_of_era = days - era * 146097;
+    let year_of_era = (day_of_era - day_of_era / 1460 + day_of_era / 36524 - day_of_era / 146096) / 365;
+    let day_of_year = day_of_era - (365 * year_of_era + year_of_era / 4 - year_of_era / 100);
+    let mp = (5 * day_of_year + 2) / 153;
+    let day = day_of_year - (153 * mp + 2) / 5 + 1;
+    let month = if mp < 10 { mp + 3 } else { mp - 9 };
+    let year = year_of_era + era * 400 + if month <= 2 { 1 } else { 0 };
+    format!("{:04}-{:02}-{:02}", year, month, day)
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn times_are_rendered_from_the_start_date() {
+        let start = parse_date("2024-02-27").unwrap();
+        let days = Calendar::new(start, TimeUnit::Day);
+        assert_eq!(days.render(0), "2024-02-27");
+        assert_eq!(days.render(5), "2024-03-03");
+
+        let hours = Calendar::new(start, TimeUnit::Hour);
+        assert_eq!(hours.render(0), "2024-02-27 00:00");
+        assert_eq!(hours.render(5), "2024-02-27 05:00");
+        assert_eq!(hours.render(53), "2024-02-29 05:00");
+
+        assert_eq!(parse_date("1970-01-01"), Ok(0));
+        assert!(parse_date("2023-02-29").is_err());
+        assert!(parse_date("2024-13-01").is_err());
+        assert!(parse_date("27/02/2024").is_err());
+    }
+}
@@ -0,0 +1,232 @@
+use std::{collections::HashMap, fs::File, io::{BufRead, BufReader, BufWriter, Write}, sync::Arc, thread::ThreadId};
+
+use ddo::{Fringe, SubProblem, Decision, Variable};
+
+use crate::state::State;
+
+/// This fringe keeps the nodes that the solver discards when the resolution is aborted,
+/// so that they can be written to a checkpoint, and replaces the root of the resolution
+/// by the nodes read from a checkpoint when resuming. The nodes being explored when the
+/// resolution is aborted are no longer in the fringe, so the last node popped by each
+/// thread is kept as well. Some of them may have been fully explored, which only costs
+/// their exploration again when resuming.
+pub struct CheckpointFringe<'a> {
+    inner: Box<dyn Fringe<State = State> + Send + Sync + 'a>,
+    /// Nodes pushed instead of the root, if resuming
+    resumed: Option<Vec<SubProblem<State>>>,
+    /// Last node popped by each thread, which may still be explored
+    popped: HashMap<ThreadId, SubProblem<State>>,
+    /// Nodes that were in the fringe or being explored when it was cleared
+    saved: Vec<SubProblem<State>>,
+}
+
+impl <'a> CheckpointFringe<'a> {
+    pub fn new(inner: Box<dyn Fringe<State = State> + Send + Sync + 'a>) -> Self {
+        Self { inner, resumed: None, popped: HashMap::new(), saved: vec![] }
+    }
+
+    /// Replaces the first node pushed, which is the root, by the given nodes
+    pub fn resume(&mut self, nodes: Vec<SubProblem<State>>) {
+        self.resumed = Some(nodes);
+    }
+
+    /// Removes and returns the nodes that are still open, either left in the fringe
+    /// or discarded by the solver
+    pub fn drain_open(&mut self) -> Vec<SubProblem<State>> {
+        let mut nodes = std::mem::take(&mut self.saved);
+        while let Some(node) = self.inner.pop() {
+            nodes.push(node);
+        }
+        nodes
+    }
+}
+
+impl Fringe for CheckpointFringe<'_> {
+    type State = State;
+
+    fn push(&mut self, node: SubProblem<State>) {
+        match self.resumed.take() {
+            Some(nodes) => nodes.into_iter().for_each(|n| self.inner.push(n)),
+            None => self.inner.push(node),
+        }
+    }
+
+    fn pop(&mut self) -> Option<SubProblem<State>> {
+        let node = self.inner.pop()?;
+        self.popped.insert(std::thread::current().id(), node.clone());
+        Some(node)
+    }
+
+    fn clear(&mut self) {
+        self.saved.extend(self.popped.drain().map(|(_, node)| node));
+        while let Some(node) = self.inner.pop() {
+            self.saved.push(node);
+        }
+    }
+
+    fn len(&self) -> usize {
+        self.inner.len()
+    }
+}
+
+/// The best solution and the open nodes of an interrupted resolution. The values are
+/// those of the problem solved, so a checkpoint can only be resumed with the same
+/// instance and options.
+pub struct Checkpoint {
+    pub n_jobs: usize,
+    pub best: Option<(isize, Vec<Decision>)>,
+    pub nodes: Vec<SubProblem<State>>,
+}
+
+fn write_decisions(tokens: &mut Vec<String>, decisions: &[Decision]) {
+    tokens.push(decisions.len().to_string());
+    for d in decisions.iter() {
+        tokens.push(d.variable.id().to_string());
+        tokens.push(d.value.to_string());
+    }
+}
+
+fn read_decisions<'a>(tokens: &mut impl Iterator<Item = &'a str>) -> Option<Vec<Decision>> {
+    let n = tokens.next()?.parse::<usize>().ok()?;
+    (0..n).map(|_| {
+        let variable = Variable(tokens.next()?.parse::<usize>().ok()?);
+        let value = tokens.next()?.parse::<isize>().ok()?;
+        Some(Decision { variable, value })
+    }).collect()
+}
+
+impl Checkpoint {
+    /// Writes the checkpoint with one line for the number of jobs, one for the best solution
+    /// and one for each node
+    pub fn write(&self, path: &str) -> Result<(), String> {
+        let file = File::create(path).map_err(|e| e.to_string())?;
+        let mut writer = BufWriter::new(file);
+        let mut lines = vec![format!("jobs {}", self.n_jobs)];
+        lines.push(match &self.best {
+            Some((value, decisions)) => {
+                let mut tokens = vec!["best".to_string(), value.to_string()];
+                write_decisions(&mut tokens, decisions);
+                tokens.join(" ")
+            },
+            None => "best none".to_string(),
+        });
+        for node in self.nodes.iter() {
+            let mut tokens = vec!["node".to_string(), node.value.to_string(), node.ub.to_string(), node.depth.to_string()];
+            write_decisions(&mut tokens, &node.path);
+            tokens.push(node.state.serialize());
+            lines.push(tokens.join(" "));
+        }
+        for line in lines.iter() {
+            writeln!(writer, "{}", line).map_err(|e| e.to_string())?;
+        }
+        writer.flush().map_err(|e| e.to_string())
+    }
+
+    pub fn read(path: &str) -> Result<Self, String> {
+        let file = File::open(path).map_err(|e| e.to_string())?;
+        let mut n_jobs = None;
+        let mut best = None;
+        let mut nodes = vec![];
+        for (i, line) in BufReader::new(file).lines().enumerate() {
+            let line = line.map_err(|e| e.to_string())?;
+            let malformed = || format!("line {} is malformed", i + 1);
+            let mut tokens = line.split_whitespace();
+            match tokens.next() {
+                Some("jobs") => n_jobs = Some(tokens.next().and_then(|t| t.parse::<usize>().ok()).ok_or_else(malformed)?),
+                Some("best") => {
+                    let mut tokens = tokens.peekable();
+                    if tokens.peek() != Some(&"none") {
+                        let value = tokens.next().and_then(|t| t.parse::<isize>().ok()).ok_or_else(malformed)?;
+                        best = Some((value, read_decisions(&mut tokens).ok_or_else(malformed)?));
+                    }
+                },
+                Some("node") => {
+                    let mut next = || tokens.next().and_then(|t| t.parse::<isize>().ok());
+                    let (value, ub, depth) = (next(), next(), next());
+                    let path = read_decisions(&mut tokens);
+                    let state = State::deserialize(&mut tokens);
+                    match (value, ub, depth, path, state) {
+                        (Some(value), Some(ub), Some(depth), Some(path), Some(state)) if depth >= 0 =>
+                            nodes.push(SubProblem { state: Arc::new(state), value, path, ub, depth: depth as usize }),
+                        _ => return Err(malformed()),
+                    }
+                },
+                None => {},
+                Some(_) => return Err(malformed()),
+            }
+        }
+
+        let n_jobs = n_jobs.ok_or("missing number of jobs")?;
+        if nodes.iter().any(|n| n.state.earliest.len() != n_jobs) {
+            return Err("the states do not match the number of jobs".to_string());
+        }
+        Ok(Self { n_jobs, best, nodes })
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use std::{io::BufReader, sync::atomic::{AtomicUsize, Ordering}};
+
+    use ddo::{Cutoff, NoCutoff, NbUnassignedWitdh, NoDupFringe, MaxUB, ParBarrierSolverFc, Problem, Solver};
+
+    use crate::{instance::RcpspInstance, model::{Rcpsp, tests::solve}, relax::RcpspRelax, heuristics::RcpspRanking};
+
+    use super::*;
+
+    /// Stops the search at its n-th check
+    struct StopAt {
+        checks: AtomicUsize,
+        n: usize,
+    }
+
+    impl Cutoff for StopAt {
+        fn must_stop(&self) -> bool {
+            self.checks.fetch_add(1, Ordering::Relaxed) + 1 >= self.n
+        }
+    }
+
+    #[test]
+    fn resuming_a_checkpoint_ends_with_the_optimum() {
+        let content = "6 1\n2\n0 0 2 2 3\n3 1 1 4\n2 1 1 5\n2 1 1 6\n1 1 1 6\n0 0 0\n";
+        let problem = Rcpsp::new(RcpspInstance::try_from(BufReader::new(content.as_bytes())).unwrap());
+        let relaxation = RcpspRelax::new(&problem);
+        let ranking = RcpspRanking::default();
+        let width = NbUnassignedWitdh(problem.nb_variables());
+        let optimum = solve(&problem);
+        let path = std::env::temp_dir().join(format!("rcpsp_checkpoint_{}.txt", std::process::id()));
+        let path = path.to_str().unwrap();
+
+        let mut interrupted = 0;
+        for n in 1.. {
+            let cutoff = StopAt { checks: AtomicUsize::new(0), n };
+            let mut fringe = CheckpointFringe::new(Box::new(NoDupFringe::new(MaxUB::new(&ranking))));
+            let mut solver = ParBarrierSolverFc::new(&problem, &relaxation, &ranking, &width, &cutoff, &mut fringe);
+            let completion = solver.maximize();
+            if completion.is_exact {
+                break;
+            }
+            interrupted += 1;
+            let best = completion.best_value.zip(solver.best_solution());
+            drop(solver);
+            Checkpoint { n_jobs: problem.instance.n_jobs, best, nodes: fringe.drain_open() }.write(path).unwrap();
+
+            let checkpoint = Checkpoint::read(path).unwrap();
+            assert_eq!(checkpoint.n_jobs, problem.instance.n_jobs);
+            let mut fringe = CheckpointFringe::new(Box::new(NoDupFringe::new(MaxUB::new(&ranking))));
+            fringe.resume(checkpoint.nodes);
+            let mut solver = ParBarrierSolverFc::new(&problem, &relaxation, &ranking, &width, &NoCutoff, &mut fringe);
+            if let Some((value, decisions)) = checkpoint.best {
+                solver.set_primal(value, decisions);
+            }
+            let completion = solver.maximize();
+            assert!(completion.is_exact);
+            assert_eq!(completion.best_value, Some(-optimum));
+            let starts = problem.schedule(&solver.best_solution().unwrap());
+            assert!(problem.is_feasible(&starts));
+            assert_eq!(starts[problem.sink], optimum);
+        }
+        assert!(interrupted > 0);
+        let _ = std::fs::remove_file(path);
+    }
+}
@@ -0,0 +1,152 @@
+use std::time::Duration;
+
+use ddo::{Problem, WidthHeuristic, Cutoff, Fringe, FixedWidth, NbUnassignedWitdh, NoCutoff, TimeBudget, NoDupFringe, MaxUB, ParBarrierSolverFc, Solver, Completion};
+
+use crate::{instance::RcpspInstance, model::Rcpsp, state::State, relax::RcpspRelax, fringe::MemBoundedFringe, heuristics::{RcpspRanking, RankingMode, ScaledUnassignedWidth, AdaptiveWidth}};
+
+/// How the max width of the layers is chosen
+#[derive(Debug, Clone, Copy, PartialEq)]
+pub enum WidthChoice {
+    /// As many nodes as there are unassigned variables
+    NbUnassigned,
+    /// The same number of nodes in every layer
+    Fixed(usize),
+    /// A multiple of the number of unassigned variables
+    Scaled(f64),
+    /// A base width halved each time a tenth of the variables are assigned
+    Adaptive(usize),
+    /// No limit, so that the diagrams are exact
+    Unbounded,
+}
+
+/// The settings of the solver that do not depend on the problem solved
+#[derive(Debug, Clone)]
+pub struct SolverConfig {
+    pub width: WidthChoice,
+    /// Timeout for the resolution, in seconds
+    pub duration: Option<u64>,
+    /// Number of threads (0 or none to use all available cores)
+    pub threads: Option<usize>,
+    /// Memory budget (in MB) for the states of the fringe above which the nodes are
+    /// no longer deduplicated
+    pub max_mem_mb: Option<usize>,
+    pub ranking: RankingMode,
+    pub merge_looseness: f64,
+}
+
+impl Default for SolverConfig {
+    fn default() -> Self {
+        Self {
+            width: WidthChoice::NbUnassigned,
+            duration: None,
+            threads: None,
+            max_mem_mb: None,
+            ranking: RankingMode::Depth,
+            merge_looseness: 1.0,
+        }
+    }
+}
+
+impl SolverConfig {
+    pub fn width_heuristic(&self, nb_vars: usize) -> Box<dyn WidthHeuristic<State> + Send + Sync> {
+        match self.width {
+            WidthChoice::NbUnassigned => Box::new(NbUnassignedWitdh(nb_vars)),
+            WidthChoice::Fixed(width) => Box::new(FixedWidth(width)),
+            WidthChoice::Scaled(factor) => Box::new(ScaledUnassignedWidth { nb_vars, factor }),
+            WidthChoice::Adaptive(base) => Box::new(AdaptiveWidth { nb_vars, base }),
+            WidthChoice::Unbounded => Box::new(FixedWidth(usize::MAX)),
+        }
+    }
+
+    /// Stops the resolution once the timeout, if any, is exhausted
+    pub fn time_budget(&self) -> Box<dyn Cutoff + Send + Sync> {
+        match self.duration {
+            Some(d) => Box::new(TimeBudget::new(Duration::from_secs(d))),
+            None => Box::new(NoCutoff),
+        }
+    }
+
+    pub fn ranking(&self, pb: &Rcpsp) -> RcpspRanking {
+        RcpspRanking { merge_looseness: self.merge_looseness, mode: self.ranking, sink: pb.sink }
+    }
+
+    pub fn fringe<'a>(&self, ranking: &'a RcpspRanking) -> Box<dyn Fringe<State = State> + Send + Sync + 'a> {
+        match self.max_mem_mb {
+            Some(mb) => Box::new(MemBoundedFringe::new(ranking, mb * 1024 * 1024)),
+            None => Box::new(NoDupFringe::new(MaxUB::new(ranking))),
+        }
+    }
+
+    /// Number of threads to use, resolving 0 or none to all available cores
+    pub fn nb_threads(&self) -> usize {
+        match self.threads {
+            Some(threads) if threads > 0 => threads,
+            _ => std::thread::available_parallelism().map_or(1, |n| n.get()),
+        }
+    }
+}
+
+/// Solves the given problem with the given configuration, stopping when the given cutoff
+/// fires instead of the timeout of the configuration, and returns the completion of the
+/// solver along with the start time of each job in the best schedule, empty if none was found
+pub fn solve_with_cutoff(pb: &Rcpsp, config: &SolverConfig, cutoff: &(dyn Cutoff + Send + Sync)) -> (Completion, Vec<isize>) {
+    let relaxation = RcpspRelax::new(pb);
+    let ranking = config.ranking(pb);
+    let width = config.width_heuristic(pb.nb_variables());
+    let mut fringe = config.fringe(&ranking);
+    let mut solver = ParBarrierSolverFc::new(
+        pb,
+        &relaxation,
+        &ranking,
+        width.as_ref(),
+        cutoff,
+        fringe.as_mut())
+        .with_nb_threads(config.nb_threads());
+
+    let completion = solver.maximize();
+    let starts = solver.best_solution().map_or(vec![], |solution| pb.schedule(&solution));
+    (completion, starts)
+}
+
+/// Solves the given problem with the given configuration and returns the completion of the
+/// solver along with the start time of each job in the best schedule, empty if none was found
+pub fn solve_problem(pb: &Rcpsp, config: &SolverConfig) -> (Completion, Vec<isize>) {
+    solve_with_cutoff(pb, config, config.time_budget().as_ref())
+}
+
+/// Solves the given instance with the given configuration, failing if the instance is invalid
+pub fn solve(instance: RcpspInstance, config: &SolverConfig) -> Result<(Completion, Vec<isize>), String> {
+    let pb = Rcpsp::try_new(instance)?;
+    Ok(solve_problem(&pb, config))
+}
+
+#[cfg(test)]
+mod tests {
+    use std::io::BufReader;
+
+    use crate::objective::Objective;
+
+    use super::*;
+
+    #[test]
+    fn zero_threads_use_all_available_cores() {
+        let available = std::thread::available_parallelism().map_or(1, |n| n.get());
+        let config = |threads| SolverConfig { threads, ..SolverConfig::default() };
+        assert_eq!(config(Some(0)).nb_threads(), available);
+        assert_eq!(config(None).nb_threads(), available);
+        assert_eq!(config(Some(3)).nb_threads(), 3);
+        assert!(config(Some(0)).nb_threads() > 0);
+    }
+
+    #[test]
+    fn default_config_solves_a_tiny_instance() {
+        let content = "5 1\n2\n0 0 3 2 3 4\n3 1 1 5\n2 1 1 5\n2 2 1 5\n0 0 0\n";
+        let instance = RcpspInstance::try_from(BufReader::new(content.as_bytes())).unwrap();
+
+        let (completion, starts) = solve(instance.clone(), &SolverConfig::default()).unwrap();
+        assert!(completion.is_exact);
+        assert_eq!(completion.best_value.map(|v| Objective::Makespan.value(v)), Some(5));
+        assert_eq!(starts.len(), instance.n_jobs);
+        assert_eq!(starts[4], 5);
+    }
+}
@@ -0,0 +1,231 @@
+use std::{sync::{Arc, Mutex, atomic::{AtomicBool, AtomicIsize, Ordering}}, time::{Duration, Instant}};
+
+use ddo::{Cutoff, TimeBudget};
+
+/// Keeps track of the best objective value found so far by any thread, of the
+/// corresponding start times when they are known and of when it was found. The value
+/// can be read without locking, while the schedule is only updated along with it under
+/// the lock. The values are those of the objective minimized, not the negated costs.
+#[derive(Debug)]
+pub struct Incumbent {
+    value: AtomicIsize,
+    best: Mutex<BestSchedule>,
+}
+
+#[derive(Debug)]
+struct BestSchedule {
+    value: isize,
+    starts: Vec<isize>,
+    found: Option<Instant>,
+}
+
+impl Default for Incumbent {
+    fn default() -> Self {
+        Self {
+            value: AtomicIsize::new(isize::MAX),
+            best: Mutex::new(BestSchedule { value: isize::MAX, starts: vec![], found: None }),
+        }
+    }
+}
+
+impl Incumbent {
+    /// Registers a complete schedule with the given objective value but unknown start times
+    pub fn report(&self, value: isize) {
+        self.report_schedule(value, &[]);
+    }
+
+    /// Registers a complete schedule with the given objective value and start times
+    pub fn report_schedule(&self, value: isize, starts: &[isize]) {
+        if value >= self.value.load(Ordering::Acquire) {
+            return;
+        }
+
+        let mut best = self.best.lock().unwrap();
+        if value < best.value {
+            *best = BestSchedule { value, starts: starts.to_vec(), found: Some(Instant::now()) };
+            self.value.store(value, Ordering::Release);
+        }
+    }
+
+    /// Best objective value reported so far (isize::MAX if none)
+    pub fn best(&self) -> isize {
+        self.value.load(Ordering::Acquire)
+    }
+
+    /// Best objective value reported so far along with its start times (empty if unknown)
+    pub fn best_schedule(&self) -> (isize, Vec<isize>) {
+        let best = self.best.lock().unwrap();
+        (best.value, best.starts.clone())
+    }
+
+    /// Time elapsed since the last improvement, or None if no schedule was reported yet
+    pub fn since_improvement(&self) -> Option<Duration> {
+        self.best.lock().unwrap().found.map(|found| found.elapsed())
+    }
+}
+
+/// This cutoff stops the resolution when the incumbent has not improved for
+/// a given duration, or when the optional time budget is exhausted. The clock
+/// only starts once a first schedule is found, so that the time needed to find
+/// it is not counted as stagnation.
+pub struct StagnationCutoff {
+    incumbent: Arc<Incumbent>,
+    stagnation: Duration,
+    budget: Option<TimeBudget>,
+}
+
+impl StagnationCutoff {
+    pub fn new(incumbent: Arc<Incumbent>, stagnation: Duration, budget: Option<Duration>) -> Self {
+        Self { incumbent, stagnation, budget: budget.map(TimeBudget::new) }
+    }
+}
+
+impl Cutoff for StagnationCutoff {
+    fn must_stop(&self) -> bool {
+        self.budget.as_ref().map_or(false, |b| b.must_stop())
+            || self.incumbent.since_improvement().map_or(false, |d| d > self.stagnation)
+    }
+}
+
+/// This cutoff stops the resolution as soon as a schedule whose makespan does not
+/// exceed the target has been found, or when the wrapped cutoff fires
+pub struct TargetCutoff {
+    incumbent: Arc<Incumbent>,
+    target: isize,
+    inner: Box<dyn Cutoff + Send + Sync>,
+}
+
+impl TargetCutoff {
+    pub fn new(incumbent: Arc<Incumbent>, target: isize, inner: Box<dyn Cutoff + Send + Sync>) -> Self {
+        Self { incumbent, target, inner }
+    }
+}
+
+impl Cutoff for TargetCutoff {
+    fn must_stop(&self) -> bool {
+        self.incumbent.best() <= self.target || self.inner.must_stop()
+    }
+}
+
+/// This cutoff stops the resolution once the given flag is raised, e.g. by an interrupt
+/// signal, so that the best solution found so far can still be reported, or when the
+/// wrapped cutoff fires
+pub struct InterruptCutoff {
+    interrupted: Arc<AtomicBool>,
+    inner: Box<dyn Cutoff + Send + Sync>,
+}
+
+impl InterruptCutoff {
+    pub fn new(interrupted: Arc<AtomicBool>, inner: Box<dyn Cutoff + Send + Sync>) -> Self {
+        Self { interrupted, inner }
+    }
+}
+
+impl Cutoff for InterruptCutoff {
+    fn must_stop(&self) -> bool {
+        self.interrupted.load(Ordering::Relaxed) || self.inner.must_stop()
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use std::{io::BufReader, sync::atomic::AtomicUsize};
+
+    use ddo::{NoCutoff, NbUnassignedWitdh, NoDupFringe, MaxUB, ParBarrierSolverFc, Problem, Solver};
+
+    use crate::{instance::RcpspInstance, model::{Rcpsp, tests::solve}, relax::RcpspRelax, heuristics::RcpspRanking};
+
+    use super::*;
+
+    #[test]
+    fn concurrent_reports_keep_the_schedule_of_the_best_value() {
+        let incumbent = Arc::new(Incumbent::default());
+        let handles = (0..8).map(|t| {
+            let incumbent = incumbent.clone();
+            std::thread::spawn(move || {
+                for k in 0..1000 {
+                    let value = 10_000 - (k * 8 + t) as isize;
+                    incumbent.report_schedule(value, &[value, value + 1]);
+                    let (best, starts) = incumbent.best_schedule();
+                    assert_eq!(starts, vec![best, best + 1]);
+                }
+            })
+        }).collect::<Vec<_>>();
+        handles.into_iter().for_each(|h| h.join().unwrap());
+
+        let (best, starts) = incumbent.best_schedule();
+        assert_eq!(best, 10_000 - 7999);
+        assert_eq!(incumbent.best(), best);
+        assert_eq!(starts, vec![best, best + 1]);
+    }
+
+    #[test]
+    fn stagnation_clock_starts_at_the_first_schedule() {
+        let incumbent = Arc::new(Incumbent::default());
+        let cutoff = StagnationCutoff::new(incumbent.clone(), Duration::ZERO, None);
+        assert!(!cutoff.must_stop());
+
+        incumbent.report(5);
+        std::thread::sleep(Duration::from_millis(1));
+        assert!(cutoff.must_stop());
+    }
+
+    #[test]
+    fn target_fires_once_the_incumbent_reaches_it() {
+        let incumbent = Arc::new(Incumbent::default());
+        let cutoff = TargetCutoff::new(incumbent.clone(), 10, Box::new(NoCutoff));
+        assert!(!cutoff.must_stop());
+
+        incumbent.report(12);
+        assert!(!cutoff.must_stop());
+        incumbent.report(10);
+        assert!(cutoff.must_stop());
+    }
+
+    /// Raises the flag at its n-th check, as an interrupt signal would in the middle of a search
+    struct InterruptAt {
+        flag: Arc<AtomicBool>,
+        checks: Arc<AtomicUsize>,
+        n: usize,
+    }
+
+    impl Cutoff for InterruptAt {
+        fn must_stop(&self) -> bool {
+            if self.checks.fetch_add(1, Ordering::Relaxed) + 1 == self.n {
+                self.flag.store(true, Ordering::Relaxed);
+            }
+            false
+        }
+    }
+
+    #[test]
+    fn interrupt_stops_the_search_with_the_best_solution_so_far() {
+        let content = "6 1\n2\n0 0 2 2 3\n3 1 1 4\n2 1 1 5\n2 1 1 6\n1 1 1 6\n0 0 0\n";
+        let problem = Rcpsp::new(RcpspInstance::try_from(BufReader::new(content.as_bytes())).unwrap());
+        let relaxation = RcpspRelax::new(&problem);
+        let ranking = RcpspRanking::default();
+        let width = NbUnassignedWitdh(problem.nb_variables());
+        let optimum = solve(&problem);
+
+        let mut found = false;
+        for n in 1..=problem.transitions.load(Ordering::Relaxed) {
+            let flag = Arc::new(AtomicBool::new(false));
+            let checks = Arc::new(AtomicUsize::new(0));
+            let cutoff = InterruptCutoff::new(flag.clone(), Box::new(InterruptAt { flag: flag.clone(), checks: checks.clone(), n }));
+            let mut fringe = NoDupFringe::new(MaxUB::new(&ranking));
+            let mut solver = ParBarrierSolverFc::new(&problem, &relaxation, &ranking, &width, &cutoff, &mut fringe);
+            let completion = solver.maximize();
+            if !flag.load(Ordering::Relaxed) {
+                break;
+            }
+            // the search stops at the check that follows the interrupt, without proving anything
+            assert_eq!(checks.load(Ordering::Relaxed), n);
+            assert!(!completion.is_exact);
+            if let Some(value) = completion.best_value {
+                assert!(-value >= optimum);
+                found = true;
+            }
+        }
+        assert!(found);
+    }
+}
@@ -0,0 +1,413 @@
+use std::{fs::{File, read_to_string}, fmt::Write as _, io::Write, collections::HashSet};
+
+use crate::instance::RcpspInstance;
+
+/// The instance formats that can be read and written
+#[derive(Debug, Clone, Copy, PartialEq, Eq, clap::ValueEnum)]
+pub enum Format {
+    /// Patterson-like format of the instances shipped in the resources folder
+    Custom,
+    /// Single-mode PSPLIB format (.sm files)
+    Psplib,
+}
+
+pub fn read_instance(format: Format, path: &str) -> Result<RcpspInstance, String> {
+    match format {
+        Format::Custom => {
+            let file = File::open(path).map_err(|e| format!("cannot open {}: {}", path, e))?;
+            RcpspInstance::try_from(file).map_err(|e| format!("cannot parse {}: {}", path, e))
+        },
+        Format::Psplib => {
+            let content = read_to_string(path).map_err(|e| format!("cannot read {}: {}", path, e))?;
+            parse_psplib(&content)
+        },
+    }
+}
+
+pub fn write_instance(format: Format, instance: &RcpspInstance, path: &str) -> Result<(), String> {
+    let content = match format {
+        Format::Custom => to_custom(instance),
+        Format::Psplib => {
+            if let Some((i, j)) = instance.time_lags.keys().next() {
+                return Err(format!("precedence {} -> {} has a time lag, which cannot be represented in the PSPLIB format", i + 1, j + 1));
+            }
+            if let Some(i) = instance.segments.iter().position(|s| s.is_some()) {
+                return Err(format!("job {} has a varying consumption, which cannot be represented in the PSPLIB format", i + 1));
+            }
+            if let Some(i) = instance.flexible.iter().position(|f| f.is_some()) {
+                return Err(format!("job {} has a flexible requirement, which cannot be represented in the PSPLIB format", i + 1));
+            }
+            if let Some(r) = instance.resource_cost.iter().position(|c| *c != 0) {
+                return Err(format!("resource {} has a cost, which cannot be represented in the PSPLIB format", r + 1));
+            }
+            if let Some(i) = instance.due.iter().position(|d| d.is_some()) {
+                return Err(format!("job {} has a due date, which cannot be represented in the PSPLIB format", i + 1));
+            }
+            if let Some(i) = instance.optional.ones().next() {
+                return Err(format!("job {} is optional, which cannot be represented in the PSPLIB format", i + 1));
+            }
+            if let Some(r) = instance.available_from.iter().position(|t| *t > 0) {
+                return Err(format!("resource {} is not available from time 0, which cannot be represented in the PSPLIB format", r + 1));
+            }
+            if let Some(r) = instance.shifts.iter().position(|s| s.is_some()) {
+                return Err(format!("resource {} has a periodic capacity, which cannot be represented in the PSPLIB format", r + 1));
+            }
+            to_psplib(instance)
+        },
+    };
+    File::create(path)
+        .and_then(|mut file| file.write_all(content.as_bytes()))
+        .map_err(|e| format!("cannot write {}: {}", path, e))
+}
+
+fn sorted_successors(instance: &RcpspInstance, i: usize) -> Vec<usize> {
+    let mut successors = instance.successors_set[i].iter().copied().collect::<Vec<usize>>();
+    successors.sort_unstable();
+    successors
+}
+
+pub fn to_custom(instance: &RcpspInstance) -> String {
+    let mut out = String::new();
+    match instance.horizon {
+        Some(horizon) => writeln!(out, "{} {} {}", instance.n_jobs, instance.n_resources, horizon).unwrap(),
+        None => writeln!(out, "{} {}", instance.n_jobs, instance.n_resources).unwrap(),
+    }
+    let capacity = instance.capacity.iter().map(|c| c.to_string()).collect::<Vec<String>>();
+    writeln!(out, "{}", capacity.join(" ")).unwrap();
+
+    for i in 0..instance.n_jobs {
+        let successors = sorted_successors(instance, i);
+        let mut line = vec![instance.duration[i].to_string()];
+        line.extend(instance.consumption[i].iter().map(|c| c.to_string()));
+        line.push(successors.len().to_string());
+        line.extend(successors.iter().map(|j| (j + 1).to_string()));
+        writeln!(out, "{}", line.join(" ")).unwrap();
+    }
+
+    let mut time_lags = instance.time_lags.iter().collect::<Vec<(&(usize, usize), &isize)>>();
+    time_lags.sort_unstable();
+    for ((i, j), lag) in time_lags {
+        writeln!(out, "lag {} {} {}", i + 1, j + 1, lag).unwrap();
+    }
+
+    for (i, segments) in instance.segments.iter().enumerate() {
+        if let Some(segments) = segments {
+            let mut line = vec!["ramp".to_string(), (i + 1).to_string(), segments.len().to_string()];
+            for segment in segments.iter() {
+                line.push(segment.duration.to_string());
+                line.extend(segment.consumption.iter().map(|c| c.to_string()));
+            }
+            writeln!(out, "{}", line.join(" ")).unwrap();
+        }
+    }
+
+    for (r, shift) in instance.shifts.iter().enumerate() {
+        if let Some(shift) = shift {
+            let mut line = vec!["shift".to_string(), (r + 1).to_string(), shift.period.to_string(), shift.capacity.len().to_string()];
+            line.extend(shift.capacity.iter().map(|c| c.to_string()));
+            writeln!(out, "{}", line.join(" ")).unwrap();
+        }
+    }
+
+    for (i, flexible) in instance.flexible.iter().enumerate() {
+        if let Some(flexible) = flexible {
+            let mut line = vec!["flex".to_string(), (i + 1).to_string(), flexible.amount.to_string(), flexible.resources.len().to_string()];
+            line.extend(flexible.resources.iter().map(|r| (r + 1).to_string()));
+            writeln!(out, "{}", line.join(" ")).unwrap();
+        }
+    }
+
+    for (i, weight) in instance.job_weight.iter().enumerate() {
+        if *weight != 1 {
+            writeln!(out, "weight {} {}", i + 1, weight).unwrap();
+        }
+    }
+    for i in instance.optional.ones() {
+        writeln!(out, "optional {} {}", i + 1, instance.drop_penalty[i]).unwrap();
+    }
+
+    for (r, time) in instance.available_from.iter().enumerate() {
+        if *time > 0 {
+            writeln!(out, "ready {} {}", r + 1, time).unwrap();
+        }
+    }
+
+    for (r, cost) in instance.resource_cost.iter().enumerate() {
+        if *cost != 0 {
+            writeln!(out, "cost {} {}", r + 1, cost).unwrap();
+        }
+    }
+
+    // the due dates come last since their section extends to the end of the file
+    if instance.due.iter().any(|d| d.is_some()) {
+        writeln!(out, "DUE DATES").unwrap();
+        for (i, due) in instance.due.iter().enumerate() {
+            if let Some(due) = due {
+                writeln!(out, "{} {}", i + 1, due).unwrap();
+            }
+        }
+    }
+
+    out
+}
+
+pub fn to_psplib(instance: &RcpspInstance) -> String {
+    let separator = "*".repeat(72);
+    let horizon = instance.duration.iter().sum::<isize>();
+
+    let mut out = String::new();
+    writeln!(out, "{}", separator).unwrap();
+    writeln!(out, "projects                      :  1").unwrap();
+    writeln!(out, "jobs (incl. supersource/sink ):  {}", instance.n_jobs).unwrap();
+    writeln!(out, "horizon                       :  {}", horizon).unwrap();
+    writeln!(out, "RESOURCES").unwrap();
+    writeln!(out, "  - renewable                 :  {}   R", instance.n_resources).unwrap();
+    writeln!(out, "  - nonrenewable              :  0   N").unwrap();
+    writeln!(out, "  - doubly constrained        :  0   D").unwrap();
+    writeln!(out, "{}", separator).unwrap();
+    writeln!(out, "PRECEDENCE RELATIONS:").unwrap();
+    writeln!(out, "jobnr.    #modes  #successors   successors").unwrap();
+    for i in 0..instance.n_jobs {
+        let successors = sorted_successors(instance, i);
+        let successors = successors.iter().map(|j| format!("{:>4}", j + 1)).collect::<String>();
+        writeln!(out, "{:>4}        1{:>11}   {}", i + 1, instance.successors_set[i].len(), successors).unwrap();
+    }
+    writeln!(out, "{}", separator).unwrap();
+    writeln!(out, "REQUESTS/DURATIONS:").unwrap();
+    let resources = (1..=instance.n_resources).map(|r| format!("  R{:>2}", r)).collect::<String>();
+    writeln!(out, "jobnr. mode duration{}", resources).unwrap();
+    writeln!(out, "{}", "-".repeat(72)).unwrap();
+    for i in 0..instance.n_jobs {
+        let consumption = instance.consumption[i].iter().map(|c| format!("{:>5}", c)).collect::<String>();
+        writeln!(out, "{:>4}{:>6}{:>6}{}", i + 1, 1, instance.duration[i], consumption).unwrap();
+    }
+    writeln!(out, "{}", separator).unwrap();
+    writeln!(out, "RESOURCEAVAILABILITIES:").unwrap();
+    writeln!(out, "{}", resources).unwrap();
+    let capacity = instance.capacity.iter().map(|c| format!("{:>5}", c)).collect::<String>();
+    writeln!(out, "{}", capacity).unwrap();
+    writeln!(out, "{}", separator).unwrap();
+
+    out
+}
+
+#[derive(PartialEq, Eq)]
+enum Section {
+    Header,
+    Precedence,
+    Requests,
+    Availabilities,
+    DueDates,
+}
+
+fn parse_number<T: std::str::FromStr>(token: Option<&str>, what: &str) -> Result<T, String> {
+    token.and_then(|t| t.parse::<T>().ok())
+        .ok_or_else(|| format!("invalid or missing {}", what))
+}
+
+pub fn parse_psplib(content: &str) -> Result<RcpspInstance, String> {
+    let mut n_jobs = 0;
+    let mut n_resources = 0;
+    let mut duration = vec![];
+    let mut consumption = vec![];
+    let mut capacity = vec![];
+    let mut edges = vec![];
+    let mut warnings = vec![];
+    let mut due = vec![];
+
+    let mut section = Section::Header;
+    for line in content.lines() {
+        let line = line.trim();
+
+        if line.is_empty() || line.starts_with('*') || (line.starts_with('-') && !line.contains(':')) {
+            continue;
+        } else if line.starts_with("PRECEDENCE RELATIONS") {
+            section = Section::Precedence;
+            continue;
+        } else if line.starts_with("REQUESTS/DURATIONS") {
+            section = Section::Requests;
+            continue;
+        } else if line.starts_with("RESOURCEAVAILABILITIES") {
+            section = Section::Availabilities;
+            continue;
+        } else if line.starts_with("DUE DATES") {
+            section = Section::DueDates;
+            continue;
+        }
+
+        match section {
+            Section::Header => {
+                let value = line.split(':').nth(1).map(|v| v.trim());
+                if line.starts_with("jobs") {
+                    n_jobs = parse_number(value, "number of jobs")?;
+                    duration = vec![0; n_jobs];
+                } else if line.starts_with("- renewable") {
+                    n_resources = parse_number(value.and_then(|v| v.split_whitespace().next()), "number of resources")?;
+                    consumption = vec![vec![0; n_resources]; n_jobs];
+                } else if line.starts_with("- nonrenewable") || line.starts_with("- doubly constrained") {
+                    let count: usize = parse_number(value.and_then(|v| v.split_whitespace().next()), "number of resources")?;
+                    if count > 0 {
+                        return Err(format!("non-renewable resources cannot be represented ({})", line));
+                    }
+                } else if line.starts_with("projects") {
+                    let count: usize = parse_number(value, "number of projects")?;
+                    if count != 1 {
+                        return Err("multi-project instances cannot be represented".to_string());
+                    }
+                }
+            },
+            Section::Precedence => {
+                if line.starts_with("jobnr") {
+                    continue;
+                }
+                let mut it = line.split_whitespace();
+                let i = parse_number::<usize>(it.next(), "job number")? - 1;
+                let modes: usize = parse_number(it.next(), "number of modes")?;
+                if modes != 1 {
+                    return Err(format!("job {} has {} modes, only single-mode instances can be represented", i + 1, modes));
+                }
+                let n_successors: usize = parse_number(it.next(), "number of successors")?;
+                let mut distinct = HashSet::new();
+                for _ in 0..n_successors {
+                    let j = parse_number::<usize>(it.next(), "successor")? - 1;
+                    if i == j {
+                        return Err(format!("job {} is its own successor", i + 1));
+                    }
+                    if distinct.insert(j) {
+                        edges.push((i, j));
+                    }
+                }
+                if distinct.len() != n_successors {
+                    warnings.push(format!("job {} lists {} successors but only {} are distinct", i + 1, n_successors, distinct.len()));
+                }
+            },
+            Section::Requests => {
+                if line.starts_with("jobnr") {
+                    continue;
+                }
+                let mut it = line.split_whitespace();
+                let i = parse_number::<usize>(it.next(), "job number")? - 1;
+                let _mode: usize = parse_number(it.next(), "mode")?;
+                if i >= n_jobs {
+                    return Err(format!("job {} is out of range", i + 1));
+                }
+                duration[i] = parse_number(it.next(), "duration")?;
+                for r in 0..n_resources {
+                    consumption[i][r] = parse_number(it.next(), "resource request")?;
+                }
+            },
+            Section::Availabilities => {
+                if line.starts_with('R') {
+                    continue;
+                }
+                for c in line.split_whitespace() {
+                    capacity.push(parse_number(Some(c), "resource availability")?);
+                }
+            },
+            Section::DueDates => {
+                if line.starts_with("jobnr") {
+                    continue;
+                }
+                let mut it = line.split_whitespace();
+                let i = parse_number::<usize>(it.next(), "job number")?;
+                if i == 0 || i > n_jobs {
+                    return Err(format!("due date given for job {} which does not exist", i));
+                }
+                due.push((i - 1, parse_number(it.next(), "due date")?));
+            },
+        }
+    }
+
+    if capacity.len() != n_resources {
+        return Err(format!("expected {} resource availabilities, found {}", n_resources, capacity.len()));
+    }
+    if let Some((i, j)) = edges.iter().find(|(i, j)| *i >= n_jobs || *j >= n_jobs) {
+        return Err(format!("precedence {} -> {} refers to an unknown job", i + 1, j + 1));
+    }
+
+    let mut instance = RcpspInstance::new(duration, consumption, capacity, edges);
+    for (i, date) in due {
+        instance.due[i] = Some(date);
+    }
+    instance.warnings = warnings;
+    Ok(instance)
+}
+
+#[cfg(test)]
+mod tests {
+    use std::io::BufReader;
+
+    use crate::instance::{ConsumptionSegment, ShiftPattern};
+
+    use super::*;
+
+    fn custom() -> RcpspInstance {
+        RcpspInstance::try_from(BufReader::new("5 2\n2 1\n0 0 0 3 2 3 4\n3 1 1 1 5\n2 1 0 1 5\n2 2 1 1 5\n0 0 0 0\n".as_bytes())).unwrap()
+    }
+
+    #[test]
+    fn custom_to_psplib_and_back_is_identical() {
+        let instance = custom();
+        let psplib = parse_psplib(&to_psplib(&instance)).unwrap();
+        assert_eq!(psplib, instance);
+        let back = RcpspInstance::try_from(BufReader::new(to_custom(&psplib).as_bytes())).unwrap();
+        assert_eq!(back, instance);
+    }
+
+    #[test]
+    fn ramps_are_kept_in_custom_and_rejected_in_psplib() {
+        let mut instance = custom();
+        instance.segments[1] = Some(vec![
+            ConsumptionSegment { duration: 1, consumption: vec![1, 1] },
+            ConsumptionSegment { duration: 2, consumption: vec![0, 1] },
+        ]);
+        let back = RcpspInstance::try_from(BufReader::new(to_custom(&instance).as_bytes())).unwrap();
+        assert_eq!(back, instance);
+
+        let path = std::env::temp_dir().join("rcpsp_ramp.sm");
+        let result = write_instance(Format::Psplib, &instance, path.to_str().unwrap());
+        assert_eq!(result.unwrap_err(), "job 2 has a varying consumption, which cannot be represented in the PSPLIB format");
+    }
+
+    #[test]
+    fn lags_are_kept_in_custom_and_rejected_in_psplib() {
+        let mut instance = custom();
+        instance.time_lags.insert((0, 2), -1);
+        let back = RcpspInstance::try_from(BufReader::new(to_custom(&instance).as_bytes())).unwrap();
+        assert_eq!(back, instance);
+
+        let path = std::env::temp_dir().join("rcpsp_lag.sm");
+        let result = write_instance(Format::Psplib, &instance, path.to_str().unwrap());
+        assert_eq!(result.unwrap_err(), "precedence 1 -> 3 has a time lag, which cannot be represented in the PSPLIB format");
+    }
+
+    #[test]
+    fn shifts_are_kept_in_custom_and_rejected_in_psplib() {
+        let mut instance = custom();
+        instance.shifts[1] = Some(ShiftPattern { period: 8, capacity: vec![1, 0] });
+        let back = RcpspInstance::try_from(BufReader::new(to_custom(&instance).as_bytes())).unwrap();
+        assert_eq!(back, instance);
+
+        let path = std::env::temp_dir().join("rcpsp_shift.sm");
+        let result = write_instance(Format::Psplib, &instance, path.to_str().unwrap());
+        assert_eq!(result.unwrap_err(), "resource 2 has a periodic capacity, which cannot be represented in the PSPLIB format");
+    }
+
+    #[test]
+    fn availability_times_are_kept_in_custom_and_rejected_in_psplib() {
+        let mut instance = custom();
+        instance.available_from[0] = 5;
+        let back = RcpspInstance::try_from(BufReader::new(to_custom(&instance).as_bytes())).unwrap();
+        assert_eq!(back, instance);
+
+        let path = std::env::temp_dir().join("rcpsp_ready.sm");
+        let result = write_instance(Format::Psplib, &instance, path.to_str().unwrap());
+        assert_eq!(result.unwrap_err(), "resource 1 is not available from time 0, which cannot be represented in the PSPLIB format");
+    }
+
+    #[test]
+    fn psplib_rejects_multiple_modes() {
+        let content = to_psplib(&custom()).replace("   1        1          3", "   1        2          3");
+        assert_eq!(parse_psplib(&content).unwrap_err(), "job 1 has 2 modes, only single-mode instances can be represented");
+    }
+}
@@ -0,0 +1,123 @@
+use std::mem::size_of;
+
+use ddo::{Fringe, SubProblem, NoDupFringe, SimpleFringe, MaxUB, Decision};
+
+use crate::{state::State, heuristics::RcpspRanking};
+
+/// This fringe deduplicates the nodes it contains until the estimated memory used
+/// by their states exceeds the given budget. It then moves all its nodes to a fringe
+/// without deduplication, which does not need to remember the states seen.
+pub struct MemBoundedFringe<'a> {
+    nodup: NoDupFringe<MaxUB<'a, RcpspRanking>>,
+    simple: SimpleFringe<MaxUB<'a, RcpspRanking>>,
+    max_bytes: usize,
+    bytes: usize,
+    switched: bool,
+}
+
+impl <'a> MemBoundedFringe<'a> {
+    pub fn new(ranking: &'a RcpspRanking, max_bytes: usize) -> Self {
+        Self {
+            nodup: NoDupFringe::new(MaxUB::new(ranking)),
+            simple: SimpleFringe::new(MaxUB::new(ranking)),
+            max_bytes,
+            bytes: 0,
+            switched: false,
+        }
+    }
+
+    fn node_size(node: &SubProblem<State>) -> usize {
+        node.state.size_in_bytes() + node.path.len() * size_of::<Decision>()
+    }
+}
+
+impl Fringe for MemBoundedFringe<'_> {
+    type State = State;
+
+    fn push(&mut self, node: SubProblem<State>) {
+        if self.switched {
+            self.simple.push(node);
+            return;
+        }
+
+        self.bytes += Self::node_size(&node);
+        self.nodup.push(node);
+
+        if self.bytes > self.max_bytes {
+            self.switched = true;
+            while let Some(node) = self.nodup.pop() {
+                self.simple.push(node);
+            }
+        }
+    }
+
+    fn pop(&mut self) -> Option<SubProblem<State>> {
+        if self.switched {
+            self.simple.pop()
+        } else {
+            let node = self.nodup.pop();
+            if let Some(node) = &node {
+                self.bytes = self.bytes.saturating_sub(Self::node_size(node));
+            }
+            node
+        }
+    }
+
+    fn clear(&mut self) {
+        self.nodup.clear();
+        self.simple.clear();
+        self.bytes = 0;
+        self.switched = false;
+    }
+
+    fn len(&self) -> usize {
+        self.nodup.len() + self.simple.len()
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use std::{io::BufReader, sync::Arc};
+
+    use ddo::{Problem, ParBarrierSolverFc, NbUnassignedWitdh, NoCutoff, Solver};
+
+    use crate::{instance::RcpspInstance, model::Rcpsp, relax::RcpspRelax};
+
+    use super::*;
+
+    fn problem() -> Rcpsp {
+        Rcpsp::new(RcpspInstance::try_from(BufReader::new("5 1\n2\n0 0 3 2 3 4\n3 1 1 5\n2 1 1 5\n2 2 1 5\n0 0 0\n".as_bytes())).unwrap())
+    }
+
+    #[test]
+    fn exceeding_the_budget_drops_the_deduplication() {
+        let problem = problem();
+        let ranking = RcpspRanking::default();
+        let mut fringe = MemBoundedFringe::new(&ranking, 1);
+        let root = SubProblem { state: Arc::new(problem.initial_state()), value: 0, path: vec![], ub: isize::MAX, depth: 0 };
+
+        fringe.push(root.clone());
+        assert!(fringe.switched);
+        // the same state is no longer deduplicated once switched
+        fringe.push(root);
+        assert_eq!(fringe.len(), 2);
+
+        fringe.clear();
+        assert!(!fringe.switched);
+        assert_eq!(fringe.len(), 0);
+    }
+
+    #[test]
+    fn optimum_is_proven_after_the_switch() {
+        let problem = problem();
+        let relaxation = RcpspRelax::new(&problem);
+        let ranking = RcpspRanking::default();
+        let width = NbUnassignedWitdh(problem.nb_variables());
+        let mut fringe = MemBoundedFringe::new(&ranking, 1);
+        let mut solver = ParBarrierSolverFc::new(&problem, &relaxation, &ranking, &width, &NoCutoff, &mut fringe);
+
+        let completion = solver.maximize();
+        assert!(completion.is_exact);
+        assert_eq!(completion.best_value, Some(-5));
+    }
+}
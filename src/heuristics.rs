@@ -1,14 +1,209 @@
-use ddo::{StateRanking};
+use ddo::{StateRanking, WidthHeuristic, SubProblem};
 
 use crate::state::State;
 
+/// The criteria used to rank the states, the best states being kept when a layer is
+/// too wide and the others being merged
+#[derive(Debug, Clone, Copy, PartialEq, Eq, clap::ValueEnum)]
+pub enum RankingMode {
+    /// Prefer the deepest states
+    Depth,
+    /// Prefer the states whose sink can start the earliest
+    MakespanEstimate,
+    /// Prefer the deepest states, then those whose sink can start the earliest
+    DepthThenMakespan,
+}
+
+/// This ranking compares the states according to its mode. Among states that are equivalent
+/// for the mode, those whose fraction of maybe done jobs exceeds the merge looseness are
+/// ranked last, so that they are merged together first instead of loosening the merge of
+/// more precise states. The remaining ties are broken by the content of the states, so
+/// that the layers are restricted and merged the same way in every run.
 #[derive(Debug, Copy, Clone)]
-pub struct RcpspRanking;
+pub struct RcpspRanking {
+    pub merge_looseness: f64,
+    pub mode: RankingMode,
+    /// The unique job without successors, whose earliest start estimates the makespan
+    pub sink: usize,
+}
+
+impl Default for RcpspRanking {
+    fn default() -> Self {
+        Self { merge_looseness: 1.0, mode: RankingMode::Depth, sink: 0 }
+    }
+}
 
 impl StateRanking for RcpspRanking {
     type State = State;
 
     fn compare(&self, sa: &Self::State, sb: &Self::State) -> std::cmp::Ordering {
-        sa.depth.cmp(&sb.depth)
+        let tight_a = sa.looseness() <= self.merge_looseness;
+        let tight_b = sb.looseness() <= self.merge_looseness;
+        let makespan_a = sa.origin + sa.earliest[self.sink];
+        let makespan_b = sb.origin + sb.earliest[self.sink];
+        let primary = match self.mode {
+            RankingMode::Depth => sa.depth.cmp(&sb.depth),
+            RankingMode::MakespanEstimate => makespan_b.cmp(&makespan_a),
+            RankingMode::DepthThenMakespan => sa.depth.cmp(&sb.depth).then(makespan_b.cmp(&makespan_a)),
+        };
+        primary.then(tight_a.cmp(&tight_b))
+            .then_with(|| sa.content_cmp(sb))
+    }
+}
+
+/// This width heuristic allows a number of nodes per layer equal to a multiple
+/// of the number of unassigned variables
+#[derive(Debug, Copy, Clone)]
+pub struct ScaledUnassignedWidth {
+    pub nb_vars: usize,
+    pub factor: f64,
+}
+
+impl <X> WidthHeuristic<X> for ScaledUnassignedWidth {
+    fn max_width(&self, sub: &SubProblem<X>) -> usize {
+        let unassigned = self.nb_vars - sub.depth;
+        ((self.factor * unassigned as f64).ceil() as usize).max(1)
+    }
+}
+
+/// This width heuristic starts from a base width at the root and halves it each time a
+/// tenth of the variables are assigned, which bounds the work spent on the subproblems
+/// rooted deep in the search, whose layers are the most expensive to compile
+#[derive(Debug, Copy, Clone)]
+pub struct AdaptiveWidth {
+    pub nb_vars: usize,
+    pub base: usize,
+}
+
+impl <X> WidthHeuristic<X> for AdaptiveWidth {
+    fn max_width(&self, sub: &SubProblem<X>) -> usize {
+        let halvings = (10 * sub.depth) / self.nb_vars.max(1);
+        self.base.checked_shr(halvings as u32).unwrap_or(0).max(1)
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use std::sync::Arc;
+
+    use ddo::{Decision, Problem, Relaxation, Variable, Solver, FixedWidth, NoDupFringe, MaxUB, ParBarrierSolverFc, NoCutoff};
+    use fixedbitset::FixedBitSet;
+
+    use super::*;
+    use crate::{instance::RcpspInstance, model::Rcpsp, relax::RcpspRelax};
+
+    fn at_depth(depth: usize) -> SubProblem<()> {
+        SubProblem { state: Arc::new(()), value: 0, path: vec![], ub: isize::MAX, depth }
+    }
+
+    #[test]
+    fn width_is_the_rounded_up_multiple_of_the_unassigned_variables() {
+        let width = ScaledUnassignedWidth { nb_vars: 10, factor: 1.5 };
+        let widths = [0, 3, 7, 9, 10].map(|depth| width.max_width(&at_depth(depth)));
+        assert_eq!(widths, [15, 11, 5, 2, 1]);
+
+        let width = ScaledUnassignedWidth { nb_vars: 10, factor: 0.25 };
+        assert_eq!([0, 8].map(|depth| width.max_width(&at_depth(depth))), [3, 1]);
+    }
+
+    /// Keeps the best width - 1 states of the layer and merges the others, as the solver
+    /// does, then returns the number of states with a bound and the summed looseness
+    fn relaxed_layer(relax: &RcpspRelax, ranking: &RcpspRanking, mut layer: Vec<State>, width: usize) -> (usize, f64) {
+        layer.sort_by(|a, b| ranking.compare(b, a));
+        let merged = relax.merge(&mut layer[width - 1..].iter());
+        layer.truncate(width - 1);
+        layer.push(merged);
+
+        let bounded = layer.iter().filter(|s| relax.fast_upper_bound(s) != isize::MAX).count();
+        (bounded, layer.iter().map(|s| s.looseness()).sum())
+    }
+
+    #[test]
+    fn merge_looseness_keeps_the_tight_states_out_of_the_merge() {
+        // four parallel jobs, two states are exact and two were already merged
+        let edges = (1..5).flat_map(|i| [(0, i), (i, 5)]).collect();
+        let pb = Rcpsp::new(RcpspInstance::new(vec![0, 1, 1, 1, 1, 0], vec![vec![0]; 6], vec![1], edges));
+        let relax = RcpspRelax::new(&pb);
+
+        let state = |done: usize, maybe: &[usize]| {
+            let mut state = pb.initial.clone();
+            state.done.insert(0);
+            state.done.insert(done);
+            state.depth = 2;
+            if !maybe.is_empty() {
+                let mut maybe_done = FixedBitSet::with_capacity(6);
+                maybe.iter().for_each(|j| maybe_done.insert(*j));
+                state.maybe_done = Some(maybe_done);
+            }
+            state
+        };
+        let layer = vec![state(3, &[1, 2, 4]), state(4, &[1, 2, 3]), state(1, &[]), state(2, &[])];
+
+        let (loose_bounded, loose_looseness) = relaxed_layer(&relax, &RcpspRanking::default(), layer.clone(), 3);
+        let (tight_bounded, tight_looseness) = relaxed_layer(&relax, &RcpspRanking { merge_looseness: 0.25, ..RcpspRanking::default() }, layer, 3);
+        assert_eq!((loose_bounded, tight_bounded), (0, 2));
+        assert!(tight_looseness < loose_looseness);
+    }
+
+    #[test]
+    fn ties_are_broken_by_the_content_of_the_states() {
+        // jobs 1 and 2 are independent and have the same duration
+        let inst = RcpspInstance::new(vec![0, 2, 2, 0], vec![vec![1]; 4], vec![2], vec![(0, 1), (0, 2), (1, 3), (2, 3)]);
+        let pb = Rcpsp::new(inst);
+        let ranking = RcpspRanking::default();
+
+        let (root, _) = pb.combined_transition(&pb.initial, Decision { variable: Variable(0), value: pb.encode(0, 0) });
+        let (a, _) = pb.combined_transition(&root, Decision { variable: Variable(1), value: pb.encode(1, 0) });
+        let (b, _) = pb.combined_transition(&root, Decision { variable: Variable(1), value: pb.encode(2, 0) });
+        assert_ne!(ranking.compare(&a, &b), std::cmp::Ordering::Equal);
+        assert_eq!(ranking.compare(&a, &b), ranking.compare(&b, &a).reverse());
+        assert_eq!(ranking.compare(&a, &a.clone()), std::cmp::Ordering::Equal);
+    }
+
+    #[test]
+    fn adaptive_width_decreases_with_the_depth() {
+        let width = AdaptiveWidth { nb_vars: 20, base: 64 };
+        let widths = [0, 1, 2, 5, 10, 19, 20].map(|depth| width.max_width(&at_depth(depth)));
+        assert_eq!(widths, [64, 64, 32, 16, 2, 1, 1]);
+        assert!(widths.windows(2).all(|w| w[0] >= w[1]));
+
+        // the width never drops to zero, even with many halvings
+        let width = AdaptiveWidth { nb_vars: 1, base: 3 };
+        assert_eq!(width.max_width(&at_depth(1)), 1);
+    }
+
+    #[test]
+    fn each_mode_gives_a_total_order_and_the_optimum() {
+        let pb = Rcpsp::new(RcpspInstance::new(vec![3, 2, 2, 1], vec![vec![1], vec![1], vec![1], vec![1]], vec![2], vec![(0, 3), (1, 3)]));
+        let mut states = vec![pb.initial.clone()];
+        let mut layer = vec![pb.initial.clone()];
+        for _ in 0..3 {
+            let mut next = vec![];
+            for state in layer.iter() {
+                pb.for_each_in_domain(Variable(state.depth), state, &mut |d: Decision| next.push(pb.combined_transition(state, d).0));
+            }
+            states.extend(next.iter().cloned());
+            layer = next;
+        }
+
+        for mode in [RankingMode::Depth, RankingMode::MakespanEstimate, RankingMode::DepthThenMakespan] {
+            let ranking = RcpspRanking { mode, sink: pb.sink, ..RcpspRanking::default() };
+            for a in states.iter() {
+                for b in states.iter() {
+                    let order = ranking.compare(a, b);
+                    assert_eq!(order, ranking.compare(b, a).reverse());
+                    assert_eq!(order == std::cmp::Ordering::Equal, a.content_cmp(b) == std::cmp::Ordering::Equal);
+                    for c in states.iter().filter(|c| order.is_le() && ranking.compare(b, c).is_le()) {
+                        assert!(ranking.compare(a, c).is_le(), "{:?} is not transitive", mode);
+                    }
+                }
+            }
+
+            let relaxation = RcpspRelax::new(&pb);
+            let width = FixedWidth(2);
+            let mut fringe = NoDupFringe::new(MaxUB::new(&ranking));
+            let mut solver = ParBarrierSolverFc::new(&pb, &relaxation, &ranking, &width, &NoCutoff, &mut fringe);
+            assert_eq!(solver.maximize().best_value, Some(-4), "{:?} misses the optimum", mode);
+        }
     }
 }
@@ -1,9 +1,9 @@
-use std::{fs::File, io::{BufRead, BufReader, Lines, Read}, collections::HashSet};
+use std::{fs::{File, read_to_string}, io::{BufRead, BufReader, Lines, Read, Write}, collections::{HashSet, HashMap}, path::Path, str::FromStr};
 
 use fixedbitset::FixedBitSet;
 
 /// This structure represents the RCPSP instance.
-#[derive(Debug, Clone)]
+#[derive(Debug, Clone, PartialEq, Eq)]
 pub struct RcpspInstance {
     // Number of jobs
     pub n_jobs: usize,
@@ -20,20 +20,179 @@ pub struct RcpspInstance {
     pub consumption: Vec<Vec<isize>>,
     // Capacity of the resources
     pub capacity: Vec<isize>,
+    // Optional piecewise-constant consumption of the jobs over their duration
+    pub segments: Vec<Option<Vec<ConsumptionSegment>>>,
+    // Minimum delay between the start of a job and the start of its successor, when
+    // it differs from the duration of the job (it may be negative to allow overlaps)
+    pub time_lags: HashMap<(usize, usize), isize>,
+    // Factor by which the durations and consumptions were multiplied when read
+    pub scale: isize,
+    // Optional capacity of each resource repeating periodically over time (e.g. shifts)
+    pub shifts: Vec<Option<ShiftPattern>>,
+    // Time from which each resource is available, its capacity being zero before
+    pub available_from: Vec<isize>,
+    // Optional requirement of each job that can be satisfied by any of several resources,
+    // in addition to its consumption
+    pub flexible: Vec<Option<FlexibleRequirement>>,
+    // Weight of each job for the weighted objectives, unrelated to its consumption
+    pub job_weight: Vec<isize>,
+    // Source and sink inserted by with_virtual_terminals, which are not part of the project
+    pub virtual_jobs: FixedBitSet,
+    // Warnings raised while reading the instance, left to the caller to report
+    pub warnings: Vec<String>,
+    // Jobs that may be dropped instead of being scheduled
+    pub optional: FixedBitSet,
+    // Penalty added to the objective when each optional job is dropped
+    pub drop_penalty: Vec<isize>,
+    // Time by which all the jobs must be done, when given in the header
+    pub horizon: Option<isize>,
+    // Time by which each job should be done, if given
+    pub due: Vec<Option<isize>>,
+    // Cost of each unit of each resource used during one unit of time
+    pub resource_cost: Vec<isize>,
 }
 
-impl From<File> for RcpspInstance {
-    fn from(file: File) -> Self {
-        Self::from(BufReader::new(file))
+/// A consumption of any one of several interchangeable resources, chosen when the job is scheduled
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub struct FlexibleRequirement {
+    pub amount: isize,
+    pub resources: Vec<usize>,
+}
+
+/// Relation between the start or finish of a job and the start or finish of its successor
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub enum PrecedenceType {
+    /// The successor starts after the job finishes
+    FinishStart,
+    /// The successor starts after the job starts
+    StartStart,
+    /// The successor finishes after the job finishes
+    FinishFinish,
+    /// The successor finishes after the job starts
+    StartFinish,
+}
+
+impl PrecedenceType {
+    /// Parses the tags FS, SS, FF and SF
+    pub fn parse(tag: &str) -> Option<Self> {
+        match tag {
+            "FS" => Some(PrecedenceType::FinishStart),
+            "SS" => Some(PrecedenceType::StartStart),
+            "FF" => Some(PrecedenceType::FinishFinish),
+            "SF" => Some(PrecedenceType::StartFinish),
+            _ => None,
+        }
     }
+
+    /// Minimum delay between the start of a job and the start of its successor
+    pub fn lag(&self, duration: isize, successor_duration: isize) -> isize {
+        match self {
+            PrecedenceType::FinishStart => duration,
+            PrecedenceType::StartStart => 0,
+            PrecedenceType::FinishFinish => duration - successor_duration,
+            PrecedenceType::StartFinish => - successor_duration,
+        }
+    }
+}
+
+/// Capacity of a resource that cycles through consecutive periods of the same length
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub struct ShiftPattern {
+    pub period: isize,
+    pub capacity: Vec<isize>,
 }
-impl <S: Read> From<BufReader<S>> for RcpspInstance {
-    fn from(buf: BufReader<S>) -> Self {
-        Self::from(buf.lines())
+
+impl ShiftPattern {
+    /// Length of a full cycle of the pattern
+    pub fn cycle(&self) -> isize {
+        self.period * self.capacity.len() as isize
     }
 }
-impl <B: BufRead> From<Lines<B>> for RcpspInstance {
-    fn from(lines: Lines<B>) -> Self {
+
+/// A part of a job during which its consumption of each resource is constant
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub struct ConsumptionSegment {
+    pub duration: isize,
+    pub consumption: Vec<isize>,
+}
+
+/// An error found while reading an instance, the lines being numbered from 1
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub enum ParseError {
+    /// A line could not be read
+    Io(String),
+    /// A token is missing or is not a number
+    InvalidNumber { line: usize, what: &'static str },
+    /// The number of capacities differs from the number of resources
+    CapacityCount { given: usize, expected: usize },
+    /// The file ends before all the jobs are described
+    Truncated { expected: usize, found: usize },
+    /// A job is listed among its own successors
+    SelfLoop { job: usize },
+    /// A job or a resource that does not exist is referred to
+    UnknownIndex { line: usize, what: &'static str, index: usize },
+    /// A value is given that is inconsistent with the rest of the instance
+    Invalid { line: usize, message: String },
+}
+
+impl std::fmt::Display for ParseError {
+    fn fmt(&self, f: &mut std::fmt::Formatter<'_>) -> std::fmt::Result {
+        match self {
+            ParseError::Io(e) => write!(f, "cannot read the instance: {}", e),
+            ParseError::InvalidNumber { line, what } => write!(f, "line {}: invalid or missing {}", line, what),
+            ParseError::CapacityCount { given, expected } => write!(f, "line 2 gives {} capacities for {} resources", given, expected),
+            ParseError::Truncated { expected, found } => write!(f, "instance is truncated: expected {} jobs but only {} were found", expected, found),
+            ParseError::SelfLoop { job } => write!(f, "job {} is its own successor", job),
+            ParseError::UnknownIndex { line, what, index } => write!(f, "line {}: {} {} does not exist", line, what, index),
+            ParseError::Invalid { line, message } => write!(f, "line {}: {}", line, message),
+        }
+    }
+}
+
+impl std::error::Error for ParseError {}
+
+impl TryFrom<File> for RcpspInstance {
+    type Error = ParseError;
+
+    fn try_from(file: File) -> Result<Self, ParseError> {
+        Self::try_from(BufReader::new(file))
+    }
+}
+impl <S: Read> TryFrom<BufReader<S>> for RcpspInstance {
+    type Error = ParseError;
+
+    fn try_from(buf: BufReader<S>) -> Result<Self, ParseError> {
+        Self::try_from(buf.lines())
+    }
+}
+impl <B: BufRead> TryFrom<Lines<B>> for RcpspInstance {
+    type Error = ParseError;
+
+    fn try_from(lines: Lines<B>) -> Result<Self, ParseError> {
+        Self::from_scaled(lines, 1)
+    }
+}
+
+/// Parses the next token as a number
+fn parse_token<'a, T: FromStr>(it: &mut impl Iterator<Item = &'a str>, line: usize, what: &'static str) -> Result<T, ParseError> {
+    it.next().and_then(|t| t.parse::<T>().ok())
+        .ok_or(ParseError::InvalidNumber { line, what })
+}
+
+/// Parses the next token as a job or resource number between 1 and the given count, and
+/// returns the corresponding 0-based index
+fn parse_index<'a>(it: &mut impl Iterator<Item = &'a str>, line: usize, what: &'static str, count: usize) -> Result<usize, ParseError> {
+    let index = parse_token::<usize>(it, line, what)?;
+    if index == 0 || index > count {
+        return Err(ParseError::UnknownIndex { line, what, index });
+    }
+    Ok(index - 1)
+}
+
+impl RcpspInstance {
+    /// Reads an instance whose durations, consumptions and capacities may be fractional,
+    /// multiplying them by the given factor and rounding them to integers
+    pub fn from_scaled<B: BufRead>(lines: Lines<B>, scale: isize) -> Result<Self, ParseError> {
         let mut lc = 0;
         
         let mut n_jobs = 0;
@@ -43,17 +202,35 @@ impl <B: BufRead> From<Lines<B>> for RcpspInstance {
         let mut predecessors_set = vec![];
         let mut successors_set = vec![];
         let mut duration = vec![];
-        let mut weight = vec![];
+        let mut consumption = vec![];
         let mut capacity = vec![];
+        let mut segments = vec![];
+        let mut time_lags = HashMap::new();
+        let mut relations = vec![];
+        let mut warnings = vec![];
+        let mut shifts = vec![];
+        let mut available_from = vec![];
+        let mut flexible = vec![];
+        let mut job_weight = vec![];
+        let mut optional = FixedBitSet::new();
+        let mut drop_penalty = vec![];
+        let mut horizon = None;
+        let mut due = vec![];
+        let mut due_section = false;
+        let mut resource_cost = vec![];
 
         for line in lines {
-            let line = line.unwrap();
+            let line = line.map_err(|e| ParseError::Io(e.to_string()))?;
             let line = line.trim();
+            let ln = lc + 1;
 
             if lc == 0 {
                 let mut it = line.split_whitespace();
-                n_jobs = it.next().unwrap().to_string().parse::<usize>().unwrap();
-                n_resources = it.next().unwrap().to_string().parse::<usize>().unwrap();
+                n_jobs = parse_token::<usize>(&mut it, ln, "number of jobs")?;
+                n_resources = parse_token::<usize>(&mut it, ln, "number of resources")?;
+                if it.clone().next().is_some() { // legacy header
+                    horizon = Some(Self::parse_scaled(&mut it, scale, ln, "horizon", &mut warnings)?);
+                }
 
                 (0..n_jobs).for_each(|_| {
                     predecessors.push(FixedBitSet::with_capacity(n_jobs));
@@ -62,34 +239,976 @@ impl <B: BufRead> From<Lines<B>> for RcpspInstance {
                     successors_set.push(HashSet::new());
                     duration.push(0);
                 });
-                weight = vec![vec![0; n_resources]; n_jobs];
+                consumption = vec![vec![0; n_resources]; n_jobs];
+                segments = vec![None; n_jobs];
+                shifts = vec![None; n_resources];
+                available_from = vec![0; n_resources];
+                resource_cost = vec![0; n_resources];
+                flexible = vec![None; n_jobs];
+                job_weight = vec![1; n_jobs];
+                optional = FixedBitSet::with_capacity(n_jobs);
+                drop_penalty = vec![0; n_jobs];
+                due = vec![None; n_jobs];
             } else if lc == 1 {
-                for cap in line.split_whitespace() {
-                    capacity.push(cap.to_string().parse::<isize>().unwrap());
+                let mut it = line.split_whitespace();
+                while it.clone().next().is_some() {
+                    capacity.push(Self::parse_scaled(&mut it, scale, ln, "capacity", &mut warnings)?);
+                }
+                if capacity.len() != n_resources {
+                    return Err(ParseError::CapacityCount { given: capacity.len(), expected: n_resources });
                 }
             } else if (2..(2+n_jobs)).contains(&lc) {
-                let i = (lc - 2) as usize;
+                let i = lc - 2;
+                if line.is_empty() {
+                    return Err(ParseError::Truncated { expected: n_jobs, found: i });
+                }
                 let mut it = line.split_whitespace();
 
-                duration[i] = it.next().unwrap().to_string().parse::<isize>().unwrap();
+                duration[i] = Self::parse_scaled(&mut it, scale, ln, "duration", &mut warnings)?;
 
                 for j in 0..n_resources {
-                    weight[i][j] = it.next().unwrap().to_string().parse::<isize>().unwrap();
+                    consumption[i][j] = Self::parse_scaled(&mut it, scale, ln, "consumption", &mut warnings)?;
                 }
 
-                let n_successors = it.next().unwrap().to_string().parse::<usize>().unwrap();
+                let n_successors = parse_token::<usize>(&mut it, ln, "number of successors")?;
                 for _ in 0..n_successors {
-                    let j = it.next().unwrap().to_string().parse::<usize>().unwrap() - 1;
+                    let j = parse_index(&mut it, ln, "job", n_jobs)?;
+                    if i == j {
+                        return Err(ParseError::SelfLoop { job: i + 1 });
+                    }
                     predecessors[j].insert(i);
                     successors[i].insert(j);
                     predecessors_set[j].insert(i);
                     successors_set[i].insert(j);
                 }
+                if successors_set[i].len() != n_successors {
+                    warnings.push(format!("job {} lists {} successors but only {} are distinct", i + 1, n_successors, successors_set[i].len()));
+                }
+            } else if line.starts_with("DUE DATES") { // followed by lines <job> <due date>
+                due_section = true;
+            } else if due_section && line.starts_with(|c: char| c.is_ascii_digit()) {
+                let mut it = line.split_whitespace();
+                let i = parse_index(&mut it, ln, "job", n_jobs)?;
+                due[i] = Some(Self::parse_scaled(&mut it, scale, ln, "due date", &mut warnings)?);
+            } else if line.starts_with("cost") { // cost <resource> <cost per unit and per unit of time>
+                let mut it = line.split_whitespace().skip(1);
+                let r = parse_index(&mut it, ln, "resource", n_resources)?;
+                resource_cost[r] = parse_token::<isize>(&mut it, ln, "cost")?;
+            } else if line.starts_with("ramp") { // ramp <job> <n_segments> (<duration> <consumption>...)...
+                let mut it = line.split_whitespace().skip(1);
+                let i = parse_index(&mut it, ln, "job", n_jobs)?;
+                let n_segments = parse_token::<usize>(&mut it, ln, "number of segments")?;
+
+                let mut job_segments = vec![];
+                for _ in 0..n_segments {
+                    let length = Self::parse_scaled(&mut it, scale, ln, "segment duration", &mut warnings)?;
+                    let level = (0..n_resources)
+                        .map(|_| Self::parse_scaled(&mut it, scale, ln, "segment consumption", &mut warnings))
+                        .collect::<Result<Vec<isize>, ParseError>>()?;
+                    job_segments.push(ConsumptionSegment { duration: length, consumption: level });
+                }
+
+                let total = job_segments.iter().map(|s| s.duration).sum::<isize>();
+                if total != duration[i] {
+                    return Err(ParseError::Invalid { line: ln, message: format!("segments of job {} last {} instead of {}", i + 1, total, duration[i]) });
+                }
+                if flexible[i].is_some() {
+                    return Err(ParseError::Invalid { line: ln, message: format!("job {} cannot have both a varying consumption and a flexible requirement", i + 1) });
+                }
+
+                // the constant consumption is the peak of the segments
+                for j in 0..n_resources {
+                    consumption[i][j] = job_segments.iter().map(|s| s.consumption[j]).max().unwrap_or(0);
+                }
+                segments[i] = Some(job_segments);
+            } else if line.starts_with("lag") { // lag <job> <successor> <delay between their start times>
+                let mut it = line.split_whitespace().skip(1);
+                let i = parse_index(&mut it, ln, "job", n_jobs)?;
+                let j = parse_index(&mut it, ln, "job", n_jobs)?;
+                let lag = Self::parse_scaled(&mut it, scale, ln, "lag", &mut warnings)?;
+                if !successors_set[i].contains(&j) {
+                    return Err(ParseError::Invalid { line: ln, message: format!("lag given for {} -> {} which is not a precedence", i + 1, j + 1) });
+                }
+                time_lags.insert((i, j), lag);
+            } else if line.starts_with("rel") { // rel <job> <successor> <FS|SS|FF|SF>
+                let mut it = line.split_whitespace().skip(1);
+                let i = parse_index(&mut it, ln, "job", n_jobs)?;
+                let j = parse_index(&mut it, ln, "job", n_jobs)?;
+                let tag = it.next().unwrap_or("");
+                if !successors_set[i].contains(&j) {
+                    return Err(ParseError::Invalid { line: ln, message: format!("relation given for {} -> {} which is not a precedence", i + 1, j + 1) });
+                }
+                match PrecedenceType::parse(tag) {
+                    Some(relation) => relations.push((ln, (i, j), relation)),
+                    None => return Err(ParseError::Invalid { line: ln, message: format!("unknown relation {} for {} -> {}", tag, i + 1, j + 1) }),
+                }
+            } else if line.starts_with("shift") { // shift <resource> <period length> <n_periods> <capacity>...
+                let mut it = line.split_whitespace().skip(1);
+                let r = parse_index(&mut it, ln, "resource", n_resources)?;
+                let period = Self::parse_scaled(&mut it, scale, ln, "period length", &mut warnings)?;
+                let n_periods = parse_token::<usize>(&mut it, ln, "number of periods")?;
+                let pattern = (0..n_periods)
+                    .map(|_| Self::parse_scaled(&mut it, scale, ln, "capacity", &mut warnings))
+                    .collect::<Result<Vec<isize>, ParseError>>()?;
+                if period <= 0 || pattern.is_empty() {
+                    return Err(ParseError::Invalid { line: ln, message: format!("shift pattern of resource {} must have at least one period of positive length", r + 1) });
+                }
+
+                // the constant capacity is the peak of the pattern
+                capacity[r] = pattern.iter().copied().max().unwrap_or(0);
+                shifts[r] = Some(ShiftPattern { period, capacity: pattern });
+            } else if line.starts_with("ready") { // ready <resource> <time from which it is available>
+                let mut it = line.split_whitespace().skip(1);
+                let r = parse_index(&mut it, ln, "resource", n_resources)?;
+                let time = Self::parse_scaled(&mut it, scale, ln, "availability time", &mut warnings)?;
+                if time < 0 {
+                    return Err(ParseError::Invalid { line: ln, message: format!("resource {} cannot be available before time 0", r + 1) });
+                }
+                available_from[r] = time;
+            } else if line.starts_with("flex") { // flex <job> <amount> <n_resources> <resource>...
+                let mut it = line.split_whitespace().skip(1);
+                let i = parse_index(&mut it, ln, "job", n_jobs)?;
+                let amount = Self::parse_scaled(&mut it, scale, ln, "amount", &mut warnings)?;
+                let n_alternatives = parse_token::<usize>(&mut it, ln, "number of resources")?;
+                let resources = (0..n_alternatives)
+                    .map(|_| parse_index(&mut it, ln, "resource", n_resources))
+                    .collect::<Result<Vec<usize>, ParseError>>()?;
+                if resources.is_empty() {
+                    return Err(ParseError::Invalid { line: ln, message: format!("flexible requirement of job {} must refer to at least one resource", i + 1) });
+                }
+                if segments[i].is_some() {
+                    return Err(ParseError::Invalid { line: ln, message: format!("job {} cannot have both a varying consumption and a flexible requirement", i + 1) });
+                }
+                flexible[i] = Some(FlexibleRequirement { amount, resources });
+            } else if line.starts_with("weight") { // weight <job> <weight of the job>
+                let mut it = line.split_whitespace().skip(1);
+                let i = parse_index(&mut it, ln, "job", n_jobs)?;
+                let weight = parse_token::<isize>(&mut it, ln, "weight")?;
+                if weight < 0 {
+                    return Err(ParseError::Invalid { line: ln, message: format!("weight of job {} cannot be negative", i + 1) });
+                }
+                job_weight[i] = weight;
+            } else if line.starts_with("optional") { // optional <job> <penalty if dropped>
+                let mut it = line.split_whitespace().skip(1);
+                let i = parse_index(&mut it, ln, "job", n_jobs)?;
+                let penalty = Self::parse_scaled(&mut it, scale, ln, "penalty", &mut warnings)?;
+                if penalty < 0 {
+                    return Err(ParseError::Invalid { line: ln, message: format!("penalty of optional job {} cannot be negative", i + 1) });
+                }
+                optional.insert(i);
+                drop_penalty[i] = penalty;
             }
             
             lc += 1;
         }
 
-        RcpspInstance { n_jobs, n_resources, predecessors, successors, predecessors_set, successors_set, duration, consumption: weight, capacity }
+        if lc > 0 && lc < 2 + n_jobs {
+            return Err(ParseError::Truncated { expected: n_jobs, found: lc.saturating_sub(2) });
+        }
+
+        // the relations are converted once the durations are final
+        for (ln, (i, j), relation) in relations {
+            if time_lags.contains_key(&(i, j)) {
+                return Err(ParseError::Invalid { line: ln, message: format!("precedence {} -> {} has both a lag and a relation", i + 1, j + 1) });
+            }
+            time_lags.insert((i, j), relation.lag(duration[i], duration[j]));
+        }
+
+        Ok(RcpspInstance { n_jobs, n_resources, predecessors, successors, predecessors_set, successors_set, duration, consumption, capacity, segments, time_lags, scale, shifts, available_from, flexible, job_weight, optional, drop_penalty, horizon, due, resource_cost, virtual_jobs: FixedBitSet::with_capacity(n_jobs), warnings })
+    }
+}
+
+impl RcpspInstance {
+    /// Creates an instance from the duration and consumption of each job, the capacity
+    /// of each resource and the list of precedence edges (i, j) where i precedes j
+    pub fn new(duration: Vec<isize>, consumption: Vec<Vec<isize>>, capacity: Vec<isize>, edges: Vec<(usize, usize)>) -> Self {
+        let n_jobs = duration.len();
+        let n_resources = capacity.len();
+
+        let mut predecessors = vec![FixedBitSet::with_capacity(n_jobs); n_jobs];
+        let mut successors = vec![FixedBitSet::with_capacity(n_jobs); n_jobs];
+        let mut predecessors_set = vec![HashSet::new(); n_jobs];
+        let mut successors_set = vec![HashSet::new(); n_jobs];
+        for (i, j) in edges {
+            predecessors[j].insert(i);
+            successors[i].insert(j);
+            predecessors_set[j].insert(i);
+            successors_set[i].insert(j);
+        }
+
+        let segments = vec![None; n_jobs];
+        let shifts = vec![None; n_resources];
+        let available_from = vec![0; n_resources];
+        let flexible = vec![None; n_jobs];
+        let job_weight = vec![1; n_jobs];
+        let optional = FixedBitSet::with_capacity(n_jobs);
+        let drop_penalty = vec![0; n_jobs];
+        let due = vec![None; n_jobs];
+        let resource_cost = vec![0; n_resources];
+
+        RcpspInstance { n_jobs, n_resources, predecessors, successors, predecessors_set, successors_set, duration, consumption, capacity, segments, time_lags: HashMap::new(), scale: 1, shifts, available_from, flexible, job_weight, optional, drop_penalty, horizon: None, due, resource_cost, virtual_jobs: FixedBitSet::with_capacity(n_jobs), warnings: vec![] }
+    }
+
+    /// Time by which the given job should be done, which defaults to the horizon of the
+    /// instance, if any, or to the sum of the durations otherwise
+    pub fn due_date(&self, job: usize) -> isize {
+        self.due[job].or(self.horizon).unwrap_or_else(|| self.duration.iter().sum())
+    }
+
+    /// Checks that the vectors describing the jobs and the resources have consistent lengths
+    pub fn validate(&self) -> Result<(), String> {
+        if self.capacity.len() != self.n_resources {
+            return Err(format!("{} capacities are given for {} resources", self.capacity.len(), self.n_resources));
+        }
+        if self.duration.len() != self.n_jobs || self.consumption.len() != self.n_jobs {
+            return Err(format!("{} durations and {} consumptions are given for {} jobs", self.duration.len(), self.consumption.len(), self.n_jobs));
+        }
+        if let Some(i) = self.consumption.iter().position(|c| c.len() != self.n_resources) {
+            return Err(format!("job {} consumes {} resources instead of {}", i + 1, self.consumption[i].len(), self.n_resources));
+        }
+        Ok(())
+    }
+
+    /// Successors of each job along with the minimum delay between their start times
+    pub fn lagged_successors(&self) -> Vec<Vec<(usize, isize)>> {
+        (0..self.n_jobs).map(|i| {
+            let mut successors = self.successors_set[i].iter()
+                .map(|j| (*j, self.time_lags.get(&(i, *j)).copied().unwrap_or(self.duration[i])))
+                .collect::<Vec<(usize, isize)>>();
+            successors.sort_unstable();
+            successors
+        }).collect()
+    }
+
+    fn parse_scaled<'a>(it: &mut impl Iterator<Item = &'a str>, scale: isize, line: usize, what: &'static str, warnings: &mut Vec<String>) -> Result<isize, ParseError> {
+        let token = it.next().ok_or(ParseError::InvalidNumber { line, what })?;
+        if scale == 1 {
+            if let Ok(value) = token.parse::<isize>() {
+                return Ok(value);
+            }
+        }
+
+        let value = token.parse::<f64>().map_err(|_| ParseError::InvalidNumber { line, what })? * scale as f64;
+        let rounded = value.round();
+        if (value - rounded).abs() > 1e-9 {
+            warnings.push(format!("{} scaled by {} is not integral, rounded to {}", token, scale, rounded));
+        }
+        Ok(rounded as isize)
+    }
+
+    /// Number of ways to choose the resources consumed by the given job
+    pub fn nb_choices(&self, job: usize) -> usize {
+        self.flexible[job].as_ref().map_or(1, |f| f.resources.len())
+    }
+
+    /// Consumption of the given job when its flexible requirement, if any, is satisfied
+    /// by the alternative with the given index
+    pub fn chosen_consumption(&self, job: usize, choice: usize) -> Vec<isize> {
+        let mut consumption = self.consumption[job].clone();
+        if let Some(flexible) = &self.flexible[job] {
+            consumption[flexible.resources[choice]] += flexible.amount;
+        }
+        consumption
+    }
+
+    /// Writes the precedence network as a Graphviz digraph whose nodes are labeled with
+    /// the number and duration of each job, the given edges being drawn in red
+    pub fn to_dot(&self, w: &mut dyn Write, highlighted: &[(usize, usize)]) -> std::io::Result<()> {
+        writeln!(w, "digraph rcpsp {{")?;
+        for i in 0..self.n_jobs {
+            writeln!(w, "  {} [label=\"{} ({})\"];", i + 1, i + 1, self.duration[i])?;
+        }
+        for i in 0..self.n_jobs {
+            let mut successors = self.successors_set[i].iter().copied().collect::<Vec<usize>>();
+            successors.sort_unstable();
+            for j in successors {
+                if highlighted.contains(&(i, j)) {
+                    writeln!(w, "  {} -> {} [color=red];", i + 1, j + 1)?;
+                } else {
+                    writeln!(w, "  {} -> {};", i + 1, j + 1)?;
+                }
+            }
+        }
+        writeln!(w, "}}")
+    }
+
+    /// Total consumption of the given resource by the given job over its duration
+    pub fn energy(&self, job: usize, resource: usize) -> isize {
+        match &self.segments[job] {
+            Some(segments) => segments.iter().map(|s| s.duration * s.consumption[resource]).sum(),
+            None => self.duration[job] * self.consumption[job][resource],
+        }
+    }
+
+    /// Orders the jobs so that each job comes after all its predecessors, starting with
+    /// the smallest index among the jobs available. The jobs that are part of or follow
+    /// a precedence cycle are left out.
+    pub fn topological_order(&self) -> Vec<usize> {
+        let mut remaining = self.predecessors_set.iter().map(|p| p.len()).collect::<Vec<usize>>();
+        let mut open = (0..self.n_jobs).rev().filter(|i| remaining[*i] == 0).collect::<Vec<usize>>();
+        let mut order = vec![];
+
+        while let Some(i) = open.pop() {
+            order.push(i);
+            // the sets iterate in an arbitrary order, which would change the order between runs
+            let mut successors = self.successors_set[i].iter().copied().collect::<Vec<usize>>();
+            successors.sort_unstable_by(|a, b| b.cmp(a));
+            for j in successors {
+                remaining[j] -= 1;
+                if remaining[j] == 0 {
+                    open.push(j);
+                }
+            }
+        }
+
+        order
+    }
+
+    /// Computes the set of jobs that transitively succeed each job
+    pub fn descendants(&self) -> Vec<FixedBitSet> {
+        let mut descendants = vec![FixedBitSet::with_capacity(self.n_jobs); self.n_jobs];
+        for i in self.topological_order().into_iter().rev() {
+            for j in self.successors_set[i].iter().copied() {
+                let reachable = descendants[j].clone();
+                descendants[i].insert(j);
+                descendants[i].union_with(&reachable);
+            }
+        }
+        descendants
+    }
+
+    /// Removes the precedence edges i -> j that are implied by a path i -> k -> ... -> j,
+    /// and returns the number of edges removed. Only the edges without a time lag or a
+    /// relation are implied by the paths made of such edges, so the others are kept and
+    /// are not used to imply anything.
+    pub fn transitive_reduction(&mut self) -> usize {
+        let plain = |i: usize, j: usize| !self.time_lags.contains_key(&(i, j));
+
+        let mut descendants = vec![FixedBitSet::with_capacity(self.n_jobs); self.n_jobs];
+        for i in self.topological_order().into_iter().rev() {
+            for j in self.successors_set[i].iter().copied().filter(|j| plain(i, *j)) {
+                let reachable = descendants[j].clone();
+                descendants[i].insert(j);
+                descendants[i].union_with(&reachable);
+            }
+        }
+
+        let mut redundant = vec![];
+        for i in 0..self.n_jobs {
+            for j in self.successors_set[i].iter().copied().filter(|j| plain(i, *j)) {
+                if self.successors_set[i].iter().any(|k| *k != j && plain(i, *k) && descendants[*k].contains(j)) {
+                    redundant.push((i, j));
+                }
+            }
+        }
+
+        for (i, j) in redundant.iter().copied() {
+            self.predecessors[j].set(i, false);
+            self.successors[i].set(j, false);
+            self.predecessors_set[j].remove(&i);
+            self.successors_set[i].remove(&j);
+        }
+
+        redundant.len()
+    }
+
+    /// Combines several projects into a single instance whose jobs are those of all the
+    /// projects and whose resources are shared, which requires the projects to give the
+    /// same capacities, shifts and availability times. The jobs of each project are numbered after those of
+    /// the previous projects.
+    pub fn combine(projects: &[RcpspInstance]) -> Result<RcpspInstance, String> {
+        let first = projects.first().ok_or_else(|| "no project to combine".to_string())?;
+        if let Some(p) = projects.iter().position(|p| p.n_resources != first.n_resources) {
+            return Err(format!("project {} has {} resources instead of {}", p + 1, projects[p].n_resources, first.n_resources));
+        }
+        if let Some(p) = projects.iter().position(|p| p.scale != first.scale) {
+            return Err(format!("project {} is scaled by {} instead of {}", p + 1, projects[p].scale, first.scale));
+        }
+        if let Some(p) = projects.iter().position(|p| p.capacity != first.capacity) {
+            return Err(format!("project {} has capacities {:?} instead of {:?}", p + 1, projects[p].capacity, first.capacity));
+        }
+        if let Some(p) = projects.iter().position(|p| p.shifts != first.shifts) {
+            return Err(format!("project {} has other shifts than project 1", p + 1));
+        }
+        if let Some(p) = projects.iter().position(|p| p.available_from != first.available_from) {
+            return Err(format!("project {} has other resource availability times than project 1", p + 1));
+        }
+
+        let mut duration = vec![];
+        let mut consumption = vec![];
+        let mut edges = vec![];
+        let mut offsets = vec![];
+        for project in projects.iter() {
+            let offset = duration.len();
+            for i in 0..project.n_jobs {
+                for j in project.successors_set[i].iter().copied() {
+                    edges.push((i + offset, j + offset));
+                }
+            }
+            duration.extend(project.duration.iter().copied());
+            consumption.extend(project.consumption.iter().cloned());
+            offsets.push(offset);
+        }
+
+        let mut instance = Self::new(duration, consumption, first.capacity.clone(), edges);
+        for (project, offset) in projects.iter().zip(offsets) {
+            for (i, segments) in project.segments.iter().enumerate() {
+                instance.segments[i + offset] = segments.clone();
+            }
+            for (i, flexible) in project.flexible.iter().enumerate() {
+                instance.flexible[i + offset] = flexible.clone();
+            }
+            for (i, weight) in project.job_weight.iter().enumerate() {
+                instance.job_weight[i + offset] = *weight;
+            }
+            for i in project.optional.ones() {
+                instance.optional.insert(i + offset);
+                instance.drop_penalty[i + offset] = project.drop_penalty[i];
+            }
+            for (i, due) in project.due.iter().enumerate() {
+                instance.due[i + offset] = *due;
+            }
+            for ((i, j), lag) in project.time_lags.iter() {
+                instance.time_lags.insert((i + offset, j + offset), *lag);
+            }
+        }
+        instance.scale = first.scale;
+        instance.shifts = first.shifts.clone();
+        instance.available_from = first.available_from.clone();
+        instance.resource_cost = first.resource_cost.clone();
+        instance.horizon = projects.iter().map(|p| p.horizon).max().flatten();
+        Ok(instance)
+    }
+
+    /// Expresses the instance in units of the given time granularity, rounding the durations
+    /// and time lags up so that any schedule of the coarse instance remains feasible once
+    /// its start times are multiplied by the granularity. Each unit of a varying consumption
+    /// is the peak of the segments that overlap it, so that it covers the original consumption.
+    pub fn coarsen(&mut self, bucket: isize) -> Result<(), String> {
+        let round_up = |t: isize| (t + bucket - 1).div_euclid(bucket);
+
+        if let Some(r) = self.shifts.iter().position(|s| s.as_ref().map_or(false, |s| s.period % bucket != 0)) {
+            return Err(format!("the shift period of resource {} is not a multiple of {}", r + 1, bucket));
+        }
+        for shift in self.shifts.iter_mut().flatten() {
+            shift.period /= bucket;
+        }
+
+        for i in 0..self.n_jobs {
+            match &mut self.segments[i] {
+                Some(segments) => {
+                    let duration = round_up(self.duration[i]);
+                    let mut coarse: Vec<ConsumptionSegment> = vec![];
+                    for unit in 0..duration {
+                        let (from, to) = (unit * bucket, (unit + 1) * bucket);
+                        let mut level = vec![0; self.n_resources];
+                        let mut at = 0;
+                        for segment in segments.iter() {
+                            if at < to && at + segment.duration > from {
+                                level.iter_mut().zip(segment.consumption.iter()).for_each(|(l, c)| *l = (*l).max(*c));
+                            }
+                            at += segment.duration;
+                        }
+                        match coarse.last_mut() {
+                            Some(last) if last.consumption == level => last.duration += 1,
+                            _ => coarse.push(ConsumptionSegment { duration: 1, consumption: level }),
+                        }
+                    }
+                    *segments = coarse;
+                    self.duration[i] = duration;
+                },
+                None => self.duration[i] = round_up(self.duration[i]),
+            }
+        }
+        self.time_lags.values_mut().for_each(|lag| *lag = round_up(*lag));
+        self.available_from.iter_mut().for_each(|t| *t = round_up(*t));
+        self.horizon = self.horizon.map(|h| h.div_euclid(bucket));
+        self.due.iter_mut().flatten().for_each(|d| *d = d.div_euclid(bucket));
+        self.resource_cost.iter_mut().for_each(|c| *c *= bucket);
+        Ok(())
+    }
+
+    /// Interprets the consumptions read as the total energy consumed by each job over its
+    /// duration and converts them to consumptions per unit of time, rounding them up with
+    /// a warning when the energy is not a multiple of the duration. The jobs with a varying
+    /// consumption are left unchanged since their segments already give rates.
+    pub fn energy_to_rate(&mut self) {
+        let warnings = &mut self.warnings;
+        let mut to_rate = |job: usize, energy: isize, duration: isize| {
+            if duration <= 0 {
+                if energy != 0 {
+                    warnings.push(format!("job {} has no duration, its energy {} is ignored", job + 1, energy));
+                }
+                return 0;
+            }
+            if energy % duration != 0 {
+                warnings.push(format!("energy {} of job {} is not a multiple of its duration {}, rounded up", energy, job + 1, duration));
+            }
+            (energy + duration - 1).div_euclid(duration)
+        };
+
+        for i in 0..self.n_jobs {
+            if self.segments[i].is_some() {
+                continue;
+            }
+            let duration = self.duration[i];
+            self.consumption[i].iter_mut().for_each(|c| *c = to_rate(i, *c, duration));
+            if let Some(flexible) = &mut self.flexible[i] {
+                flexible.amount = to_rate(i, flexible.amount, duration);
+            }
+        }
+    }
+
+    /// Limits the number of jobs running simultaneously by adding a resource with the
+    /// given capacity, of which each job consumes one unit
+    pub fn add_parallel_limit(&mut self, max_parallel: isize) {
+        let unit = self.scale;
+        self.n_resources += 1;
+        self.capacity.push(max_parallel * unit);
+        self.shifts.push(None);
+        self.available_from.push(0);
+        self.resource_cost.push(0);
+        for i in 0..self.n_jobs {
+            self.consumption[i].push(unit);
+            if let Some(segments) = &mut self.segments[i] {
+                segments.iter_mut().for_each(|s| s.consumption.push(unit));
+            }
+        }
+    }
+
+    /// Ensures that job 0 is the unique job without predecessors and that a single
+    /// job has no successors, by inserting a virtual source and/or sink with zero
+    /// duration and consumption when needed. Since the makespan is the start of the sink,
+    /// a virtual sink is also inserted after a unique last job that has a duration. The
+    /// virtual sink is the last job, and when a virtual source is inserted, all the
+    /// original jobs are shifted by one index.
+    pub fn with_virtual_terminals(self) -> Self {
+        let roots = (0..self.n_jobs).filter(|i| self.predecessors_set[*i].is_empty()).collect::<Vec<usize>>();
+        let leaves = (0..self.n_jobs).filter(|i| self.successors_set[*i].is_empty()).collect::<Vec<usize>>();
+
+        let add_source = roots != vec![0];
+        let add_sink = leaves.len() != 1 || self.duration[leaves[0]] != 0;
+        if !add_source && !add_sink {
+            return self;
+        }
+
+        let shift = add_source as usize;
+        let n_jobs = self.n_jobs + shift + add_sink as usize;
+
+        let mut duration = vec![0; n_jobs];
+        let mut consumption = vec![vec![0; self.n_resources]; n_jobs];
+        let mut edges = vec![];
+        for i in 0..self.n_jobs {
+            duration[i + shift] = self.duration[i];
+            consumption[i + shift] = self.consumption[i].clone();
+            for j in self.successors_set[i].iter().copied() {
+                edges.push((i + shift, j + shift));
+            }
+        }
+        if add_source {
+            roots.iter().for_each(|i| edges.push((0, i + shift)));
+        }
+        if add_sink {
+            leaves.iter().for_each(|i| edges.push((i + shift, n_jobs - 1)));
+        }
+        if self.n_jobs == 0 { // the virtual source directly precedes the virtual sink
+            edges.push((0, n_jobs - 1));
+        }
+
+        let mut instance = Self::new(duration, consumption, self.capacity, edges);
+        for (i, segments) in self.segments.into_iter().enumerate() {
+            instance.segments[i + shift] = segments;
+        }
+        for (i, flexible) in self.flexible.into_iter().enumerate() {
+            instance.flexible[i + shift] = flexible;
+        }
+        if add_source {
+            instance.job_weight[0] = 0;
+            instance.virtual_jobs.insert(0);
+        }
+        if add_sink {
+            instance.job_weight[n_jobs - 1] = 0;
+            instance.virtual_jobs.insert(n_jobs - 1);
+        }
+        for (i, weight) in self.job_weight.into_iter().enumerate() {
+            instance.job_weight[i + shift] = weight;
+        }
+        for i in self.optional.ones() {
+            instance.optional.insert(i + shift);
+            instance.drop_penalty[i + shift] = self.drop_penalty[i];
+        }
+        for (i, due) in self.due.into_iter().enumerate() {
+            instance.due[i + shift] = due;
+        }
+        for ((i, j), lag) in self.time_lags.into_iter() {
+            instance.time_lags.insert((i + shift, j + shift), lag);
+        }
+        instance.scale = self.scale;
+        instance.shifts = self.shifts;
+        instance.available_from = self.available_from;
+        instance.resource_cost = self.resource_cost;
+        instance.horizon = self.horizon;
+        instance
+    }
+}
+
+/// Builds an instance job by job, keeping all the precedence representations consistent
+#[derive(Debug, Clone, Default)]
+pub struct RcpspInstanceBuilder {
+    duration: Vec<isize>,
+    consumption: Vec<Vec<isize>>,
+    capacity: Vec<isize>,
+    edges: Vec<(usize, usize)>,
+}
+
+impl RcpspInstanceBuilder {
+    /// Adds a job and returns its index
+    pub fn add_job(&mut self, duration: isize, consumption: Vec<isize>) -> usize {
+        self.duration.push(duration);
+        self.consumption.push(consumption);
+        self.duration.len() - 1
+    }
+
+    /// Imposes that job i finishes before job j starts
+    pub fn add_precedence(&mut self, i: usize, j: usize) -> &mut Self {
+        self.edges.push((i, j));
+        self
+    }
+
+    pub fn set_capacity(&mut self, capacity: Vec<isize>) -> &mut Self {
+        self.capacity = capacity;
+        self
+    }
+
+    pub fn build(&self) -> Result<RcpspInstance, String> {
+        let n_jobs = self.duration.len();
+        let n_resources = self.capacity.len();
+
+        if let Some(i) = self.consumption.iter().position(|c| c.len() != n_resources) {
+            return Err(format!("job {} consumes {} resources instead of {}", i + 1, self.consumption[i].len(), n_resources));
+        }
+        if let Some((i, j)) = self.edges.iter().find(|(i, j)| *i >= n_jobs || *j >= n_jobs) {
+            return Err(format!("precedence {} -> {} refers to an unknown job", i + 1, j + 1));
+        }
+        if let Some((i, _)) = self.edges.iter().find(|(i, j)| i == j) {
+            return Err(format!("job {} is its own successor", i + 1));
+        }
+
+        Ok(RcpspInstance::new(self.duration.clone(), self.consumption.clone(), self.capacity.clone(), self.edges.clone()))
+    }
+}
+
+/// Reads the known optimal (or best upper bound) value stored in a `.opt` or `.bub` file,
+/// which is expected to contain the makespan as its first token
+pub fn read_known_optimum<P: AsRef<Path>>(path: P) -> Option<isize> {
+    read_to_string(path).ok()?
+        .split_whitespace()
+        .next()?
+        .parse::<isize>().ok()
+}
+
+#[cfg(test)]
+mod tests {
+    use std::path::PathBuf;
+
+    use crate::model::{Rcpsp, tests::solve};
+
+    use super::*;
+
+    /// Small instances checked in with their optimal makespan in a `.opt` file
+    const FIXTURES: [&str; 2] = ["serial", "two_resources"];
+
+    fn fixture(name: &str, extension: &str) -> PathBuf {
+        PathBuf::from(env!("CARGO_MANIFEST_DIR")).join("resources").join("tests").join(name).with_extension(extension)
+    }
+
+    #[test]
+    fn fixtures_are_solved_to_their_known_optimum() {
+        for name in FIXTURES {
+            let expected = read_known_optimum(fixture(name, "opt"));
+            assert!(expected.is_some(), "missing optimum for {}", name);
+
+            let problem = Rcpsp::new(RcpspInstance::try_from(File::open(fixture(name, "rcp")).unwrap()).unwrap());
+            assert_eq!(Some(solve(&problem)), expected, "wrong optimum for {}", name);
+        }
+    }
+
+    #[test]
+    fn virtual_terminals_join_several_roots_and_leaves() {
+        // jobs 1 and 2 have no predecessors, jobs 3 and 4 have no successors
+        let content = "4 1\n2\n2 1 1 3\n3 1 1 4\n1 1 0\n4 1 0\n";
+        let instance = RcpspInstance::try_from(BufReader::new(content.as_bytes())).unwrap().with_virtual_terminals();
+        assert_eq!(instance.n_jobs, 6);
+        assert_eq!(instance.duration[0], 0);
+        assert_eq!(instance.duration[5], 0);
+        assert_eq!(instance.successors_set[0], HashSet::from([1, 2]));
+        assert_eq!(instance.predecessors_set[5], HashSet::from([3, 4]));
+
+        // the makespan is the finish time of the latest leaf, 2 -> 4 ending at 7
+        let problem = Rcpsp::new(RcpspInstance::try_from(BufReader::new(content.as_bytes())).unwrap());
+        assert_eq!(solve(&problem), 7);
+    }
+
+    #[test]
+    fn ramp_fits_where_the_flat_peak_does_not() {
+        // job 2 needs the 3 units only during its first half, leaving room for job 3 afterwards
+        let flat = "4 1\n3\n0 0 2 2 3\n4 3 1 4\n2 2 1 4\n0 0 0\n";
+        let ramp = format!("{}ramp 2 2 2 3 2 1\n", flat);
+
+        let instance = RcpspInstance::try_from(BufReader::new(ramp.as_bytes())).unwrap();
+        assert_eq!(instance.consumption[1], vec![3]);
+        assert_eq!(instance.energy(1, 0), 8);
+
+        assert_eq!(solve(&Rcpsp::new(RcpspInstance::try_from(BufReader::new(flat.as_bytes())).unwrap())), 6);
+        assert_eq!(solve(&Rcpsp::new(instance)), 4);
+    }
+
+    #[test]
+    fn builder_matches_the_parsed_instance() {
+        let mut builder = RcpspInstanceBuilder::default();
+        let a = builder.add_job(2, vec![1]);
+        let b = builder.add_job(1, vec![1]);
+        let c = builder.add_job(1, vec![1]);
+        builder.set_capacity(vec![2]).add_precedence(a, b).add_precedence(b, c);
+        let parsed = RcpspInstance::try_from(BufReader::new("3 1\n2\n2 1 1 2\n1 1 1 3\n1 1 0\n".as_bytes())).unwrap();
+        assert_eq!(builder.build().unwrap(), parsed);
+
+        builder.add_precedence(c, 3);
+        assert_eq!(builder.build().unwrap_err(), "precedence 3 -> 4 refers to an unknown job");
+    }
+
+    #[test]
+    fn builder_rejects_a_consumption_of_the_wrong_size() {
+        let mut builder = RcpspInstanceBuilder::default();
+        builder.add_job(1, vec![1, 1]);
+        builder.set_capacity(vec![1]);
+        assert_eq!(builder.build().unwrap_err(), "job 1 consumes 2 resources instead of 1");
+    }
+
+    #[test]
+    fn transitive_reduction_removes_the_implied_edges() {
+        // 1 -> 2 -> 3 -> 4 with the redundant edges 1 -> 3, 1 -> 4 and 2 -> 4
+        let content = "4 1\n1\n1 1 3 2 3 4\n1 1 2 3 4\n1 1 1 4\n1 1 0\n";
+        let mut instance = RcpspInstance::try_from(BufReader::new(content.as_bytes())).unwrap();
+        let descendants = instance.descendants();
+
+        assert_eq!(instance.transitive_reduction(), 3);
+        assert_eq!(instance.successors_set, vec![HashSet::from([1]), HashSet::from([2]), HashSet::from([3]), HashSet::new()]);
+        assert_eq!(instance.predecessors_set, vec![HashSet::new(), HashSet::from([0]), HashSet::from([1]), HashSet::from([2])]);
+        assert_eq!(instance.successors[0].ones().collect::<Vec<usize>>(), vec![1]);
+        assert_eq!(instance.predecessors[3].ones().collect::<Vec<usize>>(), vec![2]);
+        assert_eq!(instance.descendants(), descendants);
+        assert_eq!(instance.transitive_reduction(), 0);
+    }
+
+    #[test]
+    fn transitive_reduction_keeps_the_edges_with_a_lag() {
+        // 1 -> 2 -> 3 and 1 -> 3 with a lag longer than the path
+        let content = "3 1\n2\n2 1 2 2 3\n1 1 1 3\n1 1 0\n";
+        let mut instance = RcpspInstance::try_from(BufReader::new(format!("{}lag 1 3 5\n", content).as_bytes())).unwrap();
+        assert_eq!(instance.transitive_reduction(), 0);
+        assert!(instance.successors_set[0].contains(&2));
+
+        // the same path with an overlap does not imply the edge 1 -> 3
+        let mut instance = RcpspInstance::try_from(BufReader::new(format!("{}rel 1 2 SS\n", content).as_bytes())).unwrap();
+        assert_eq!(instance.transitive_reduction(), 0);
+
+        let mut instance = RcpspInstance::try_from(BufReader::new(content.as_bytes())).unwrap();
+        assert_eq!(instance.transitive_reduction(), 1);
+        assert!(!instance.successors_set[0].contains(&2));
+    }
+
+    #[test]
+    fn self_loop_is_rejected() {
+        let content = "3 1\n2\n0 1 1 2\n1 1 1 2\n0 0 0\n";
+        assert_eq!(RcpspInstance::try_from(BufReader::new(content.as_bytes())).unwrap_err(), ParseError::SelfLoop { job: 2 });
+    }
+
+    #[test]
+    fn duplicated_edge_is_kept_once_with_a_warning() {
+        let content = "3 1\n2\n0 1 2 2 2\n1 1 1 3\n0 0 0\n";
+        let instance = RcpspInstance::try_from(BufReader::new(content.as_bytes())).unwrap();
+        assert_eq!(instance.successors_set[0].len(), 1);
+        assert_eq!(instance.predecessors_set[1].len(), 1);
+        assert_eq!(instance.warnings, vec!["job 1 lists 2 successors but only 1 are distinct".to_string()]);
+    }
+
+    #[test]
+    fn invalid_tokens_and_indices_are_reported_with_their_line() {
+        let parse = |content: &str| RcpspInstance::try_from(BufReader::new(content.as_bytes()));
+        let jobs = "2 1\n2\n1 1 1 2\n0 0 0\n";
+        assert_eq!(parse("2 1\n2\n1 x 1 2\n0 0 0\n").unwrap_err(), ParseError::InvalidNumber { line: 3, what: "consumption" });
+        assert_eq!(parse("2 1\n2\n1 1 1 3\n0 0 0\n").unwrap_err(), ParseError::UnknownIndex { line: 3, what: "job", index: 3 });
+        assert_eq!(parse(&format!("{}lag 1 5 1\n", jobs)).unwrap_err(), ParseError::UnknownIndex { line: 5, what: "job", index: 5 });
+        assert_eq!(parse(&format!("{}ramp 0 1 1 1\n", jobs)).unwrap_err(), ParseError::UnknownIndex { line: 5, what: "job", index: 0 });
+        assert_eq!(parse(&format!("{}ready 2 1\n", jobs)).unwrap_err(), ParseError::UnknownIndex { line: 5, what: "resource", index: 2 });
+        assert_eq!(parse(&format!("{}weight 3 2\n", jobs)).unwrap_err(), ParseError::UnknownIndex { line: 5, what: "job", index: 3 });
+        assert_eq!(parse(&format!("{}optional 1\n", jobs)).unwrap_err(), ParseError::InvalidNumber { line: 5, what: "penalty" });
+        assert_eq!(parse(&format!("{}cost 0 2\n", jobs)).unwrap_err(), ParseError::UnknownIndex { line: 5, what: "resource", index: 0 });
+        assert_eq!(parse(&format!("{}lag 1 2\n", jobs)).unwrap_err(), ParseError::InvalidNumber { line: 5, what: "lag" });
+    }
+
+    #[test]
+    fn combined_projects_share_the_resources() {
+        let project = "3 1\n1\n0 0 1 2\n3 1 1 3\n0 0 0\n";
+        let project = RcpspInstance::try_from(BufReader::new(project.as_bytes())).unwrap();
+        assert_eq!(solve(&Rcpsp::new(project.clone())), 3);
+
+        let combined = RcpspInstance::combine(&[project.clone(), project.clone()]).unwrap();
+        assert_eq!(combined.n_jobs, 6);
+        assert_eq!(combined.successors_set[3], HashSet::from([4]));
+        assert_eq!(combined.capacity, vec![1]);
+        // the jobs of both projects cannot run at the same time
+        assert_eq!(solve(&Rcpsp::new(combined)), 6);
+
+        let mut larger = project.clone();
+        larger.capacity = vec![2];
+        assert!(RcpspInstance::combine(&[project.clone(), larger]).is_err());
+        let mut shifted = project.clone();
+        shifted.shifts = vec![Some(ShiftPattern { period: 2, capacity: vec![1, 0] })];
+        assert!(RcpspInstance::combine(&[project.clone(), shifted]).is_err());
+        let mut delayed = project.clone();
+        delayed.available_from = vec![2];
+        assert!(RcpspInstance::combine(&[project.clone(), delayed]).is_err());
+        assert!(RcpspInstance::combine(&[]).is_err());
+    }
+
+    #[test]
+    fn coarsened_segments_cover_the_original_consumption() {
+        let mut instance = RcpspInstance::try_from(BufReader::new("1 2\n2 2\n4 1 1 0\nramp 1 2 3 1 0 1 0 1\n".as_bytes())).unwrap();
+        instance.coarsen(2).unwrap();
+        assert_eq!(instance.duration[0], 2);
+        assert_eq!(instance.segments[0], Some(vec![
+            ConsumptionSegment { duration: 1, consumption: vec![1, 0] },
+            ConsumptionSegment { duration: 1, consumption: vec![1, 1] },
+        ]));
+    }
+
+    #[test]
+    fn job_weights_are_parsed_apart_from_the_consumption() {
+        let content = "3 2\n4 3\n0 0 0 1 2\n2 3 1 1 3\n0 0 0 0\nweight 2 5\n";
+        let instance = RcpspInstance::try_from(BufReader::new(content.as_bytes())).unwrap();
+        assert_eq!(instance.job_weight, vec![1, 5, 1]);
+        assert_eq!(instance.consumption, vec![vec![0, 0], vec![3, 1], vec![0, 0]]);
+
+        let negative = RcpspInstance::try_from(BufReader::new(format!("{}weight 2 -1\n", content).as_bytes()));
+        assert_eq!(negative.unwrap_err(), ParseError::Invalid { line: 7, message: "weight of job 2 cannot be negative".to_string() });
+    }
+
+    #[test]
+    fn dot_output_has_one_edge_per_precedence() {
+        let content = "5 1\n2\n0 0 2 2 3\n3 1 1 4\n1 2 1 5\n3 1 1 5\n0 0 0\n";
+        let instance = RcpspInstance::try_from(BufReader::new(content.as_bytes())).unwrap();
+        let mut out = vec![];
+        instance.to_dot(&mut out, &[(0, 1), (1, 3)]).unwrap();
+        let dot = String::from_utf8(out).unwrap();
+
+        let lines = dot.lines().collect::<Vec<&str>>();
+        assert_eq!(lines.first(), Some(&"digraph rcpsp {"));
+        assert_eq!(lines.last(), Some(&"}"));
+        assert!(lines[1..lines.len() - 1].iter().all(|l| l.starts_with("  ") && l.ends_with(';')));
+        assert_eq!(lines.iter().filter(|l| l.contains("[label=")).count(), 5);
+        assert_eq!(lines.iter().filter(|l| l.contains(" -> ")).count(), 5);
+        assert_eq!(lines.iter().filter(|l| l.contains("[color=red]")).count(), 2);
+        assert!(lines.contains(&"  2 -> 4 [color=red];"));
+        assert!(lines.contains(&"  3 -> 5;"));
+    }
+
+    #[test]
+    fn energies_give_the_same_schedules_as_the_rates() {
+        let rates = "4 1\n3\n0 0 2 2 3\n3 2 1 4\n2 1 1 4\n0 0 0\n";
+        let energies = "4 1\n3\n0 0 2 2 3\n3 6 1 4\n2 2 1 4\n0 0 0\n";
+        let mut instance = RcpspInstance::try_from(BufReader::new(energies.as_bytes())).unwrap();
+        instance.energy_to_rate();
+        let expected = RcpspInstance::try_from(BufReader::new(rates.as_bytes())).unwrap();
+        assert_eq!(instance.consumption, expected.consumption);
+        assert!(instance.warnings.is_empty());
+
+        let with_energies = Rcpsp::new(instance);
+        let with_rates = Rcpsp::new(expected);
+        let starts = vec![0, 0, 3, 5];
+        for t in 0..5 {
+            assert_eq!(with_energies.profile_at(&starts, 0, t), with_rates.profile_at(&starts, 0, t));
+        }
+        assert_eq!(solve(&with_energies), solve(&with_rates));
+
+        let mut rounded = RcpspInstance::try_from(BufReader::new(energies.replace("3 6 1 4", "3 5 1 4").as_bytes())).unwrap();
+        rounded.energy_to_rate();
+        assert_eq!(rounded.consumption[1], vec![2]);
+        assert_eq!(rounded.warnings, vec!["energy 5 of job 2 is not a multiple of its duration 3, rounded up".to_string()]);
+    }
+
+    #[test]
+    fn topological_order_does_not_depend_on_the_set_iteration_order() {
+        // each parse hashes the sets of successors with its own keys
+        let content = "6 1\n1\n0 0 3 2 3 4\n1 1 2 5 6\n1 1 1 6\n1 1 2 5 6\n1 1 1 6\n0 0 0\n";
+        let first = RcpspInstance::try_from(BufReader::new(content.as_bytes())).unwrap();
+        assert_eq!(first.topological_order(), vec![0, 1, 2, 3, 4, 5]);
+        for _ in 0..20 {
+            let instance = RcpspInstance::try_from(BufReader::new(content.as_bytes())).unwrap();
+            assert_eq!(instance.topological_order(), first.topological_order());
+            assert_eq!(instance.descendants(), first.descendants());
+        }
+    }
+
+    #[test]
+    fn capacity_count_must_match_the_resources() {
+        let content = "2 2\n2\n0 1 1 1 2\n0 0 0 0\n";
+        assert_eq!(RcpspInstance::try_from(BufReader::new(content.as_bytes())).unwrap_err(), ParseError::CapacityCount { given: 1, expected: 2 });
+        let content = "2 1\n2 3\n0 1 1 2\n0 0 0\n";
+        assert_eq!(RcpspInstance::try_from(BufReader::new(content.as_bytes())).unwrap_err(), ParseError::CapacityCount { given: 2, expected: 1 });
+    }
+
+    #[test]
+    fn truncated_instance_is_rejected() {
+        let content = "3 1\n2\n0 1 1 2\n";
+        assert_eq!(RcpspInstance::try_from(BufReader::new(content.as_bytes())).unwrap_err(), ParseError::Truncated { expected: 3, found: 1 });
+        let content = "3 1\n2\n0 1 1 2\n\n0 0 0\n";
+        assert_eq!(RcpspInstance::try_from(BufReader::new(content.as_bytes())).unwrap_err(), ParseError::Truncated { expected: 3, found: 1 });
+    }
+
+    #[test]
+    fn horizon_is_read_from_a_legacy_header() {
+        let jobs = "2\n0 0 2 2 3\n1 1 1 4\n2 1 1 4\n0 0 0\n";
+        let instance = RcpspInstance::try_from(BufReader::new(format!("4 1\n{}", jobs).as_bytes())).unwrap();
+        assert_eq!(instance.horizon, None);
+        let instance = RcpspInstance::try_from(BufReader::new(format!("4 1 7\n{}", jobs).as_bytes())).unwrap();
+        assert_eq!(instance.horizon, Some(7));
+        assert_eq!(instance.duration, vec![0, 1, 2, 0]);
+
+        let problem = Rcpsp::new(instance);
+        assert_eq!(problem.deadline, Some(7));
+        assert_eq!(problem.horizon, 7);
+    }
+
+    #[test]
+    fn ragged_vectors_are_rejected() {
+        let valid = RcpspInstance::new(vec![0, 2, 0], vec![vec![0, 0], vec![1, 2], vec![0, 0]], vec![1, 2], vec![(0, 1), (1, 2)]);
+        assert_eq!(valid.validate(), Ok(()));
+
+        let ragged = RcpspInstance::new(vec![0, 2, 0], vec![vec![0, 0], vec![1], vec![0, 0]], vec![1, 2], vec![(0, 1), (1, 2)]);
+        assert_eq!(ragged.validate(), Err("job 2 consumes 1 resources instead of 2".to_string()));
+        assert!(Rcpsp::try_new(ragged).is_err());
+
+        let mut capacity = valid.clone();
+        capacity.capacity.push(3);
+        assert_eq!(capacity.validate(), Err("3 capacities are given for 2 resources".to_string()));
+        assert!(Rcpsp::try_new(capacity).is_err());
+    }
+
+    #[test]
+    fn due_dates_are_parsed_per_job() {
+        let parse = |content: &str| RcpspInstance::try_from(BufReader::new(content.as_bytes()));
+        let jobs = "3 1\n2\n1 1 1 2\n2 1 1 3\n0 0 0\n";
+        let instance = parse(&format!("{}DUE DATES\n2 4\n", jobs)).unwrap();
+        assert_eq!(instance.due, vec![None, Some(4), None]);
+        assert_eq!(instance.due_date(1), 4);
+        assert_eq!(instance.due_date(0), 3);
+        assert_eq!(parse(&format!("{}DUE DATES\n4 1\n", jobs)).unwrap_err(), ParseError::UnknownIndex { line: 7, what: "job", index: 4 });
+    }
+
+    #[test]
+    fn missing_optimum_is_none() {
+        assert_eq!(read_known_optimum(fixture("missing", "opt")), None);
     }
 }
@@ -1,54 +1,1064 @@
-use std::{fs::File, time::{Duration, Instant}};
+use std::{fs::File, time::{Duration, Instant}, path::Path, process::exit, sync::{Arc, atomic::{AtomicBool, Ordering}}, io::{BufRead, BufReader, Write}};
 
-use clap::Parser;
-use ddo::{FixedWidth, NoCutoff, MaxUB, Solver, Completion, NoDupFringe, ParBarrierSolverFc, NbUnassignedWitdh, Problem, WidthHeuristic, TimeBudget, Cutoff};
-use heuristics::RcpspRanking;
-use instance::RcpspInstance;
+use clap::{Parser, ValueEnum};
+use ddo::{Solver, Completion, ParBarrierSolverFc, Problem, Cutoff, Decision};
+use cutoff::{Incumbent, StagnationCutoff, TargetCutoff, InterruptCutoff};
+use priority::{PriorityRule, priority_order};
+use sgs::serial_sgs;
+use heuristics::RankingMode;
+use instance::{RcpspInstance, read_known_optimum};
 use model::Rcpsp;
-use relax::RcpspRelax;
+use objective::Objective;
+use relax::{RcpspRelax, MergeEarliest};
+use bounds::{EnergyBound, CriticalPathBound, BoundProvider};
+use format::{Format, read_instance, write_instance};
+use sink::LogSink;
+use config::{SolverConfig, WidthChoice, solve_problem};
+use calendar::{Calendar, TimeUnit, parse_date};
+use checkpoint::{Checkpoint, CheckpointFringe};
 
 mod instance;
 mod model;
 mod state;
 mod relax;
 mod heuristics;
+mod bounds;
+mod fringe;
+mod format;
+mod objective;
+mod sink;
+mod cutoff;
+mod priority;
+mod sgs;
+mod config;
+mod calendar;
+mod checkpoint;
+
+#[derive(Debug, Clone, Copy, clap::ValueEnum)]
+enum Bound {
+    /// Longest path to the sink, ignoring the resources
+    CriticalPath,
+    /// Max of the critical path and the preemptive energy bound
+    Energy,
+}
+
+#[derive(Debug, Clone, Copy, PartialEq, Eq, clap::ValueEnum)]
+enum Normalize {
+    /// Divide by the critical path lower bound of the instance
+    CriticalPath,
+    /// Divide by the sum of the durations of the jobs
+    SumDurations,
+}
+
+#[derive(Debug, Clone, Copy, PartialEq, Eq, clap::ValueEnum)]
+enum Emit {
+    /// Jobs of the best schedule in the order in which they were scheduled
+    Order,
+}
+
+#[derive(Debug, clap::Subcommand)]
+enum Command {
+    /// Converts an instance from one of the supported formats to another
+    Convert {
+        /// Format of the input instance
+        #[clap(long, value_enum)]
+        from: Format,
+        /// Format of the output instance
+        #[clap(long, value_enum)]
+        to: Format,
+        /// Path to the instance to convert
+        input: String,
+        /// Path of the converted instance
+        output: String,
+    },
+    /// Solves several instances and writes one CSV row per instance
+    Batch {
+        /// Path of the CSV file to write
+        #[clap(long)]
+        out: String,
+        /// Max width of any layer (defaults to the number of unassigned variables)
+        #[clap(short, long)]
+        width: Option<usize>,
+        /// Timeout for the resolution of each instance
+        #[clap(short, long)]
+        duration: Option<u64>,
+        /// Number of threads used to solve each instance (0 or none to use
+        /// all available cores)
+        #[clap(short, long, value_parser = clap::builder::RangedU64ValueParser::<usize>::new().range(0..=1024))]
+        threads: Option<usize>,
+        /// Paths to the instance files
+        instances: Vec<String>,
+    },
+    /// Solves the same instance with each of the given widths and prints one row per width
+    Sweep {
+        /// Comma-separated list of the max widths to compare
+        #[clap(long, value_delimiter = ',', required = true)]
+        widths: Vec<usize>,
+        /// Timeout for each resolution
+        #[clap(short, long)]
+        duration: Option<u64>,
+        /// Number of threads used for each resolution (0 or none to use
+        /// all available cores)
+        #[clap(short, long, value_parser = clap::builder::RangedU64ValueParser::<usize>::new().range(0..=1024))]
+        threads: Option<usize>,
+        /// Path to the instance file
+        instance: String,
+    },
+    /// Solves the same instance with each of the given numbers of threads and prints the
+    /// speedup and efficiency relative to the first one. The ties between the states are
+    /// broken the same way in every run, but the threads still pick the open nodes in an
+    /// order that depends on their timing, so the timings with several threads vary.
+    Scale {
+        /// Comma-separated list of the numbers of threads to compare
+        #[clap(long, value_delimiter = ',', required = true, value_parser = clap::builder::RangedU64ValueParser::<usize>::new().range(1..=1024))]
+        threads: Vec<usize>,
+        /// Max width of any layer (defaults to the number of unassigned variables)
+        #[clap(short, long)]
+        width: Option<usize>,
+        /// Timeout for each resolution
+        #[clap(short, long)]
+        duration: Option<u64>,
+        /// Path to the instance file
+        instance: String,
+    },
+}
 
 #[derive(Debug, clap::Parser)]
+#[clap(args_conflicts_with_subcommands = true, subcommand_negates_reqs = true)]
 struct Args {
+    #[clap(subcommand)]
+    command: Option<Command>,
     /// Max width of any layer (defaults to the same number of 
     /// nodes as there are unassigned variables)
     #[clap(short, long)]
     width: Option<usize>,
+    /// Max width of any layer as a multiple of the number of unassigned variables
+    #[clap(long, conflicts_with = "width")]
+    width_factor: Option<f64>,
+    /// Never merge nor drop nodes, so that the diagram is exact. This is meant to check
+    /// the transitions on small instances, since the layers grow exponentially.
+    #[clap(long, conflicts_with_all = ["width", "width_factor"])]
+    no_relax: bool,
+    /// Max width of the layers at the root, halved each time a tenth of the jobs are
+    /// scheduled, so that the subproblems deep in the search remain cheap to compile
+    #[clap(long, conflicts_with_all = ["width", "width_factor", "no_relax"], value_parser = clap::builder::RangedU64ValueParser::<usize>::new().range(1..))]
+    adaptive_width: Option<usize>,
     /// Timeout for the resolution of the problem
     #[clap(short, long)]
     duration: Option<u64>,
-    /// Number of threads used to solve the instance
-    #[clap(short, long)]
+    /// Number of threads used to solve the instance (0 or none to use
+    /// all available cores)
+    #[clap(short, long, value_parser = clap::builder::RangedU64ValueParser::<usize>::new().range(0..=1024))]
     threads: Option<usize>,
+    /// Propagate the whole schedule after each transition instead of
+    /// only the jobs affected by the last scheduled one
+    #[clap(long)]
+    full_propagation: bool,
+    /// Objective to minimize
+    #[clap(long, value_enum, default_value_t = Objective::Makespan)]
+    objective: Objective,
+    /// Lower bound used to prune the nodes of the diagrams
+    #[clap(short, long, value_enum, default_value_t = Bound::CriticalPath)]
+    bound: Bound,
+    /// Check that the value found matches the one stored in the `.opt` (or `.bub`)
+    /// file next to the instance when the resolution completes
+    #[clap(long)]
+    expect_optimum: bool,
+    /// Print the jobs of the best schedule that have no slack
+    #[clap(long)]
+    critical_path: bool,
+    /// Move each job of the best schedule to its earliest feasible start given the
+    /// others and report the resulting makespan
+    #[clap(long)]
+    left_shift: bool,
+    /// Solve the instance again without the limit on the capacity of each resource
+    /// and print the best makespans found, to identify the bottleneck resources.
+    /// Each of these solves is given the same time limit as the main one
+    #[clap(long)]
+    bottleneck: bool,
+    /// Print the utilization of each resource over time in the best schedule
+    #[clap(long)]
+    sparkline: bool,
+    /// Also report the best value divided by the given baseline
+    #[clap(long, value_enum)]
+    normalize: Option<Normalize>,
+    /// Additional representation of the best solution to print, unless quiet is set
+    #[clap(long, value_enum)]
+    emit: Option<Emit>,
+    /// Memory budget (in MB) for the states of the fringe above which the
+    /// nodes are no longer deduplicated
+    #[clap(long)]
+    max_mem_mb: Option<usize>,
+    /// Log each transition (scheduled job, start time and objective delta)
+    /// on the standard error
+    #[clap(long)]
+    trace: bool,
+    /// Log the merges of states performed when a layer exceeds the max width
+    /// on the standard error
+    #[clap(long)]
+    explain: bool,
+    /// Log where the profiles of each merged state differ from the merged profiles
+    /// on the standard error
+    #[clap(long)]
+    explain_profiles: bool,
+    /// Enumerate all the optimal schedules once the optimum is proven
+    #[clap(long)]
+    all_optima: bool,
+    /// Maximum number of optimal schedules enumerated
+    #[clap(long, default_value_t = 1000)]
+    max_optima: usize,
+    /// Stop the resolution when the best solution has not improved for
+    /// this number of seconds, counted from the first solution found
+    #[clap(long)]
+    stagnation_secs: Option<u64>,
+    /// Makespan that no schedule may exceed, the instance being infeasible otherwise.
+    /// With the other objectives, it acts as a cap on the makespan of the schedules.
+    #[clap(long, alias = "makespan-cap")]
+    deadline: Option<isize>,
+    /// Makespan that no schedule may exceed, as a multiple of the length of the critical path
+    #[clap(long, conflicts_with = "deadline")]
+    deadline_factor: Option<f64>,
+    /// Stop the resolution as soon as a schedule with at most this makespan is found
+    /// (only with the makespan objective)
+    #[clap(long)]
+    target: Option<isize>,
+    /// Start times imposed to some jobs, given as a comma-separated list
+    /// of job:start pairs (e.g. 3:10,7:25)
+    #[clap(long, value_delimiter = ',', value_parser = parse_fixed_start)]
+    fix: Vec<(usize, isize)>,
+    /// Start times imposed to some jobs in a second resolution performed after the
+    /// first one, given as a comma-separated list of job:start pairs, to compare the
+    /// best values with and without these placements. This second resolution is given
+    /// the same time limit as the first one
+    #[clap(long, value_delimiter = ',', value_parser = parse_fixed_start, conflicts_with = "fix")]
+    what_if: Vec<(usize, isize)>,
+    /// Priority rule used by the serial schedule generation scheme to
+    /// compute an initial solution
+    #[clap(long, value_enum)]
+    priority_rule: Option<PriorityRule>,
+    /// Seed of the random priority rule
+    #[clap(long, default_value_t = 0)]
+    seed: u64,
+    /// Number of random priority orders (seeded from --seed) with which the serial schedule
+    /// generation scheme is run to compute an initial solution, the best one being kept
+    #[clap(long, default_value_t = 0)]
+    restarts: usize,
+    /// Interpret the consumptions of the jobs as their total energy over their duration
+    /// instead of their consumption per unit of time
+    #[clap(long)]
+    energy_mode: bool,
+    /// Remove the precedence edges implied by transitivity before solving
+    #[clap(long)]
+    reduce: bool,
+    /// Write the precedence network to the given path in the Graphviz DOT format,
+    /// the edges of the critical path being drawn in red
+    #[clap(long)]
+    dot: Option<String>,
+    /// Write the usage of each resource over time in the best schedule to the given path,
+    /// as the times at which the usage changes followed by the usage from then on
+    #[clap(long)]
+    profile_out: Option<String>,
+    /// Write the best schedule to the given path as a CSV file in the layout imported
+    /// by Microsoft Project (ID, name, duration, start, finish and predecessors)
+    #[clap(long)]
+    msproject: Option<String>,
+    /// Date of time 0 (YYYY-MM-DD), so that the times written are rendered as dates
+    #[clap(long, value_parser = parse_date)]
+    start_date: Option<i64>,
+    /// Unit of the times of the instance when they are rendered as dates
+    #[clap(long, value_enum, default_value_t = TimeUnit::Day, requires = "start_date")]
+    unit: TimeUnit,
+    /// Write the best solution and the open nodes of the search to the given path when the
+    /// resolution stops before completion, so that it can be continued with --resume
+    #[clap(long)]
+    checkpoint: Option<String>,
+    /// Continue the resolution from a checkpoint written with the same instance and options
+    #[clap(long)]
+    resume: Option<String>,
+    /// Print the number of transitions computed from the states of each depth
+    #[clap(long)]
+    stats: bool,
+    /// Print the earliest start of each job before solving, given by the precedences,
+    /// the energy of its predecessors and the availability of the resources
+    #[clap(long)]
+    earliest: bool,
+    /// Build a schedule job by job from the standard input instead of solving the instance,
+    /// the jobs that can be scheduled next being listed with their start before each choice
+    #[clap(long)]
+    interactive: bool,
+    /// Print the lower bound on the makespan given by the critical path and energy bounds
+    /// on the initial state
+    #[clap(long)]
+    root_bound: bool,
+    /// Print the intervals where the resources are the most overloaded if every job
+    /// started at its earliest start, with the ratio of their demand to their capacity
+    #[clap(long)]
+    contention: bool,
+    /// Only run the serial schedule generation scheme with each priority rule and report the
+    /// best schedule found, without solving the instance
+    #[clap(long)]
+    heuristic_only: bool,
+    /// Print the total cost of the resources used by the best schedule
+    #[clap(long)]
+    cost: bool,
+    /// Print the lower bounds computed for the initial state and for the states
+    /// along a random schedule instead of solving the instance
+    #[clap(long)]
+    compare_bounds: bool,
+    /// Factor by which the (possibly fractional) durations, consumptions and
+    /// capacities are multiplied to obtain integers
+    #[clap(long, default_value_t = 1, value_parser = clap::builder::RangedI64ValueParser::<isize>::new().range(1..))]
+    scale: isize,
+    /// Max number of jobs that can run simultaneously, modeled by an additional
+    /// resource of which each job consumes one unit
+    #[clap(long, value_parser = clap::builder::RangedI64ValueParser::<isize>::new().range(1..))]
+    max_parallel: Option<isize>,
+    /// Only offer the given number of jobs that can start the earliest from each state.
+    /// The search space may then exclude all optimal schedules, so the solution is
+    /// never reported as optimal
+    #[clap(long, value_parser = clap::builder::RangedU64ValueParser::<usize>::new().range(1..))]
+    beam: Option<usize>,
+    /// Time granularity: the durations and time lags are rounded up to multiples of
+    /// this value and the instance is solved in units of it. The schedules found remain
+    /// feasible once rescaled but may be suboptimal, so they are never reported as optimal.
+    /// The start times printed are expressed in units of the granularity
+    #[clap(long, default_value_t = 1, value_parser = clap::builder::RangedI64ValueParser::<isize>::new().range(1..), conflicts_with_all = ["fix", "what_if"])]
+    bucket: isize,
+    /// Only schedule the jobs that can start the earliest (non-delay schedules).
+    /// The search space may then exclude all optimal schedules, so the solution
+    /// is never reported as optimal
+    #[clap(long)]
+    non_delay: bool,
+    /// Fraction of maybe done jobs above which merged states are merged first,
+    /// to keep the other merged states tighter
+    #[clap(long, default_value_t = 1.0)]
+    merge_looseness: f64,
+    /// Aggregation of the earliest times when merging states. Only min keeps the
+    /// bounds valid, so the solution is never reported as optimal with the others
+    #[clap(long, value_enum, default_value_t = MergeEarliest::Min)]
+    merge_earliest: MergeEarliest,
+    /// Criteria used to choose the states kept when a layer exceeds the max width
+    #[clap(long, value_enum, default_value_t = RankingMode::Depth)]
+    ranking: RankingMode,
+    /// Round the remaining capacities of the merged profiles up to a multiple of this
+    /// granularity, which limits the number of steps at the cost of weaker bounds
+    #[clap(long, default_value_t = 1, value_parser = clap::builder::RangedI64ValueParser::<isize>::new().range(1..))]
+    cap_bucket: isize,
+    /// Only print the best value found on the standard output
+    #[clap(short, long)]
+    quiet: bool,
+    /// Additional projects sharing the resources of the instance, which are all
+    /// scheduled together to minimize the makespan of the last one
+    #[clap(long)]
+    project: Vec<String>,
     /// Path to the instance file containing the instance to solve
-    instance: String,
+    #[clap(required = true)]
+    instance: Option<String>,
+}
+
+/// Parses a job:start pair where the job is numbered from 1
+fn parse_fixed_start(s: &str) -> Result<(usize, isize), String> {
+    let (job, start) = s.split_once(':').ok_or_else(|| format!("expected job:start, found {}", s))?;
+    let job = job.parse::<usize>().map_err(|e| e.to_string())?;
+    let start = start.parse::<isize>().map_err(|e| e.to_string())?;
+    if job == 0 {
+        return Err("jobs are numbered from 1".to_string());
+    }
+    Ok((job - 1, start))
+}
+
+/// Value of the schedule given by the decisions of a heuristic, or None if it violates
+/// the fixed starts or the deadline
+fn heuristic_value(problem: &Rcpsp, decisions: &[Decision]) -> Option<isize> {
+    let starts = problem.schedule(decisions);
+    let on_time = problem.deadline.map_or(true, |deadline| starts[problem.sink] <= deadline);
+    if !problem.respects_fixed(&starts) || !on_time {
+        return None;
+    }
+    Some(problem.objective.schedule_value(&problem.instance, &starts))
+}
+
+/// Writes the given schedule in the CSV layout that project management tools can import,
+/// with one row per job listing its direct predecessors and its times rendered by the
+/// given function
+fn write_msproject(problem: &Rcpsp, starts: &[isize], csv: &mut impl Write, time: &dyn Fn(isize) -> String) -> std::io::Result<()> {
+    let inst = &problem.instance;
+    writeln!(csv, "ID,Name,Duration,Start,Finish,Predecessors")?;
+    for i in 0..inst.n_jobs {
+        let predecessors = inst.predecessors[i].ones().map(|p| (p + 1).to_string()).collect::<Vec<String>>();
+        writeln!(csv, "{},Job {},{},{},{},\"{}\"", i + 1, i + 1, inst.duration[i], time(starts[i]), time(starts[i] + inst.duration[i]), predecessors.join(","))?;
+    }
+    Ok(())
+}
+
+/// Prints the best value found, converted back to the units of the instance when it was scaled
+fn print_best(best: isize, scale: isize, quiet: bool) {
+    if !quiet {
+        println!("Best value: {}", scaled_value(best, scale));
+    } else if scale == 1 {
+        println!("{}", best);
+    } else {
+        println!("{}", best as f64 / scale as f64);
+    }
+}
+
+/// Runs the serial schedule generation scheme with each priority rule and returns the
+/// best value found along with the rule that found it, ignoring the schedules that do not
+/// respect the fixed jobs or the deadline
+fn best_heuristic(problem: &Rcpsp, seed: u64) -> Option<(isize, PriorityRule)> {
+    PriorityRule::value_variants().iter().copied()
+        .filter_map(|rule| heuristic_value(problem, &serial_sgs(problem, &priority_order(rule, problem, seed))).map(|value| (value, rule)))
+        .max_by_key(|(value, _)| *value)
+}
+
+/// Runs the serial schedule generation scheme with the given number of random priority
+/// orders, the k-th one (from 1) being seeded with seed + k, and returns the value and the decisions
+/// of the best schedule, ties being broken by the first order found
+fn best_restart(problem: &Rcpsp, restarts: usize, seed: u64) -> Option<(isize, Vec<Decision>)> {
+    let mut best: Option<(isize, Vec<Decision>)> = None;
+    for k in 1..=restarts {
+        let decisions = serial_sgs(problem, &priority_order(PriorityRule::Random, problem, seed.wrapping_add(k as u64)));
+        if let Some(value) = heuristic_value(problem, &decisions) {
+            if best.as_ref().map_or(true, |(best_value, _)| value > *best_value) {
+                best = Some((value, decisions));
+            }
+        }
+    }
+    best
+}
+
+/// Number of intervals printed for each resource with --contention
+const CONTENTION_INTERVALS: usize = 3;
+
+/// One line per resource giving its most overloaded intervals if every job started at its
+/// earliest start, the highest ratios of the demand to the capacity first
+fn contention_report(problem: &Rcpsp) -> Vec<String> {
+    problem.root_contention().into_iter().enumerate().map(|(r, mut intervals)| {
+        intervals.sort_by(|a, b| b.2.total_cmp(&a.2).then(a.0.cmp(&b.0)));
+        let hottest = intervals.iter()
+            .take(CONTENTION_INTERVALS)
+            .map(|(start, end, ratio)| format!("[{}, {}) {:.2}", start, end, ratio))
+            .collect::<Vec<String>>();
+        format!("R{:<9}: {}", r + 1, hottest.join(", "))
+    }).collect()
+}
+
+/// Max number of time buckets of the utilization sparklines
+const SPARKLINE_BUCKETS: isize = 60;
+
+/// Renders the utilization of each resource in the given schedule as a row of block
+/// characters, each one giving the average utilization over a bucket of time
+fn sparklines(problem: &Rcpsp, starts: &[isize]) -> Vec<String> {
+    const BLOCKS: [char; 9] = [' ', '▁', '▂', '▃', '▄', '▅', '▆', '▇', '█'];
+
+    let inst = &problem.instance;
+    let makespan = (0..inst.n_jobs).map(|i| starts[i] + inst.duration[i]).max().unwrap_or(0);
+    let buckets = SPARKLINE_BUCKETS.min(makespan).max(1);
+
+    (0..inst.n_resources).map(|r| {
+        (0..buckets).map(|b| {
+            let from = b * makespan / buckets;
+            let to = ((b + 1) * makespan / buckets).max(from + 1);
+            let used = (from..to).map(|t| problem.profile_at(starts, r, t)).sum::<isize>();
+            let available = (to - from) * inst.capacity[r];
+            if available <= 0 {
+                return BLOCKS[0];
+            }
+            let level = (used * (BLOCKS.len() as isize - 1) + available - 1) / available;
+            BLOCKS[level.clamp(0, BLOCKS.len() as isize - 1) as usize]
+        }).collect()
+    }).collect()
+}
+
+/// Exit code when the solution found is not proven optimal
+const EXIT_FEASIBLE: i32 = 2;
+/// Exit code when no feasible solution was found
+const EXIT_INFEASIBLE: i32 = 3;
+/// Exit code when the instance or the options are invalid
+const EXIT_INVALID: i32 = 4;
+
+/// Prints the given message on the standard output unless quiet is set
+macro_rules! info {
+    ($quiet:expr, $($arg:tt)*) => {
+        if !$quiet {
+            println!($($arg)*);
+        }
+    };
+}
+
+/// Prints the given warning on the standard error unless quiet is set
+macro_rules! warn {
+    ($quiet:expr, $($arg:tt)*) => {
+        if !$quiet {
+            eprintln!("Warning   : {}", format_args!($($arg)*));
+        }
+    };
+}
+
+/// Builds a schedule with the jobs read from the given input (numbered from 1), listing
+/// the jobs that can be scheduled next with their start before reading each one unless
+/// quiet is set, and returns the start of each job
+fn interactive(problem: &Rcpsp, input: impl BufRead, quiet: bool) -> Result<Vec<isize>, (i32, String)> {
+    let n_jobs = problem.instance.n_jobs;
+    let mut state = problem.initial_state();
+    let mut starts = vec![0; n_jobs];
+    let mut lines = input.lines();
+    while !state.is_complete(n_jobs) {
+        let next = problem.next_jobs(&state);
+        if next.is_empty() {
+            return Err((EXIT_INFEASIBLE, "no job can be scheduled anymore".to_string()));
+        }
+        let listed = next.iter().map(|(i, start)| format!("{}@{}", i + 1, start)).collect::<Vec<String>>();
+        info!(quiet, "Next      : {}", listed.join(" "));
+        if !quiet {
+            print!("> ");
+            let _ = std::io::stdout().flush();
+        }
+
+        let line = match lines.next() {
+            Some(Ok(line)) => line,
+            _ => return Err((1, "the input ended before the schedule was complete".to_string())),
+        };
+        let chosen = line.trim().parse::<usize>().ok()
+            .and_then(|job| next.iter().find(|(i, _)| *i + 1 == job).copied());
+        match chosen {
+            Some((job, start)) => {
+                state = problem.step(&state, job).0;
+                starts[job] = start;
+                info!(quiet, "Scheduled : job {} at {}", job + 1, start);
+            },
+            None => warn!(quiet, "{} is not a job that can be scheduled next", line.trim()),
+        }
+    }
+
+    if !problem.is_feasible(&starts) {
+        return Err((1, "the schedule built is not feasible".to_string()));
+    }
+    Ok(starts)
+}
+
+/// Computes the absolute makespan lower bound given by each bound provider for
+/// some states sampled along a random schedule (seeded with the given seed),
+/// and returns the names of the bounds with one row per sampled depth
+fn sample_bounds(problem: &Rcpsp, seed: u64) -> (Vec<&'static str>, Vec<(usize, Vec<isize>)>) {
+    let bounds: Vec<(&str, Box<dyn BoundProvider>)> = vec![
+        ("critical-path", Box::new(CriticalPathBound::new(problem))),
+        ("energy", Box::new(EnergyBound::new(problem))),
+    ];
+
+    let decisions = serial_sgs(problem, &priority_order(PriorityRule::Random, problem, seed));
+    let samples = 10.min(decisions.len()).max(1);
+    let step = (decisions.len() / samples).max(1);
+
+    let mut rows = vec![];
+    let mut state = problem.initial_state();
+    for (depth, decision) in decisions.iter().enumerate() {
+        if depth % step == 0 {
+            rows.push((depth, bounds.iter().map(|(_, bound)| state.origin + bound.lower_bound(&state, &problem.instance)).collect()));
+        }
+        state = problem.combined_transition(&state, *decision).0;
+    }
+
+    (bounds.iter().map(|(name, _)| *name).collect(), rows)
+}
+
+/// Prints the bounds sampled along a random schedule, one row per depth,
+/// without the header if quiet is set
+fn compare_bounds(problem: &Rcpsp, seed: u64, quiet: bool) {
+    let (names, rows) = sample_bounds(problem, seed);
+
+    if !quiet {
+        print!("{:>6}", "depth");
+        names.iter().for_each(|name| print!(" {:>14}", name));
+        println!();
+    }
+
+    for (depth, bounds) in rows {
+        print!("{:>6}", depth);
+        bounds.iter().for_each(|bound| print!(" {:>14}", bound));
+        println!();
+    }
+}
+
+/// Formats a value found on an instance read with the given scale, along with
+/// its value in the original units when the instance was scaled
+fn scaled_value(value: isize, scale: isize) -> String {
+    if scale == 1 {
+        value.to_string()
+    } else {
+        format!("{} ({} scaled)", value as f64 / scale as f64, value)
+    }
+}
+
+/// Ratio of the given value to the baseline of the instance, or None if the baseline
+/// is not positive
+fn normalized_value(problem: &Rcpsp, value: isize, normalize: Normalize) -> Option<f64> {
+    let baseline = match normalize {
+        Normalize::CriticalPath => problem.initial.origin + CriticalPathBound::new(problem).lower_bound(&problem.initial, &problem.instance),
+        Normalize::SumDurations => problem.instance.duration.iter().sum::<isize>(),
+    };
+    if baseline > 0 {
+        Some(value as f64 / baseline as f64)
+    } else {
+        None
+    }
+}
+
+/// Jobs of the given solution in the order in which they were scheduled
+fn scheduled_order(problem: &Rcpsp, mut solution: Vec<Decision>) -> Vec<usize> {
+    solution.sort_unstable_by_key(|d| d.variable.id());
+    solution.iter().map(|d| problem.decode(d.value).0).collect()
+}
+
+/// Reads the instance at the given path and prints its warnings unless quiet is set
+fn load_instance(path: &str, scale: isize, quiet: bool) -> Result<RcpspInstance, String> {
+    let file = File::open(path).map_err(|e| format!("cannot open {}: {}", path, e))?;
+    let instance = RcpspInstance::from_scaled(BufReader::new(file).lines(), scale)
+        .map_err(|e| format!("cannot parse {}: {}", path, e))?;
+    for warning in instance.warnings.iter() {
+        warn!(quiet, "{}", warning);
+    }
+    Ok(instance)
+}
+
+fn convert(from: Format, to: Format, input: &str, output: &str) {
+    let result = read_instance(from, input)
+        .and_then(|instance| {
+            instance.warnings.iter().for_each(|warning| eprintln!("Warning   : {}", warning));
+            write_instance(to, &instance, output)
+        });
+    if let Err(e) = result {
+        eprintln!("Error     : {}", e);
+        exit(EXIT_INVALID);
+    }
+}
+
+/// Solves the given instance with the default settings and returns the best value,
+/// whether it is optimal, the time elapsed and the number of transitions computed
+fn solve_default(path: &str, width: Option<usize>, duration: Option<u64>, threads: Option<usize>) -> Result<(isize, bool, f64, usize), String> {
+    let file = File::open(path).map_err(|e| e.to_string())?;
+    let problem = Rcpsp::try_new(RcpspInstance::try_from(file).map_err(|e| e.to_string())?)?;
+    let config = SolverConfig {
+        width: width.map_or(WidthChoice::NbUnassigned, WidthChoice::Fixed),
+        duration,
+        threads,
+        ..SolverConfig::default()
+    };
+
+    let time = Instant::now();
+    let (Completion{is_exact, best_value}, _) = solve_problem(&problem, &config);
+    let elapsed = time.elapsed().as_secs_f64();
+    let best = best_value.map_or(isize::MIN, |value| problem.objective.value(value));
+
+    Ok((best, is_exact, elapsed, problem.transitions.load(Ordering::Relaxed)))
+}
+
+/// Solves each instance in turn, writing its row as soon as it is solved, so that
+/// an error on one instance is recorded without aborting the others
+fn batch(out: &str, instances: &[String], width: Option<usize>, duration: Option<u64>, threads: Option<usize>) -> std::io::Result<()> {
+    let mut csv = File::create(out)?;
+    writeln!(csv, "instance,value,optimal,elapsed,nodes,error")?;
+
+    for path in instances.iter() {
+        match solve_default(path, width, duration, threads) {
+            Ok((value, optimal, elapsed, nodes)) => writeln!(csv, "{},{},{},{},{},", path, value, optimal, elapsed, nodes)?,
+            Err(e) => writeln!(csv, "{},,,,,\"{}\"", path, e.replace('"', "\"\""))?,
+        }
+        csv.flush()?;
+    }
+
+    Ok(())
+}
+
+/// Solves the instance once per width and returns the width along with the results
+/// of solve_default for each of them
+fn sweep_rows(instance: &str, widths: &[usize], duration: Option<u64>, threads: Option<usize>) -> Result<Vec<(usize, (isize, bool, f64, usize))>, String> {
+    widths.iter().copied()
+        .map(|width| solve_default(instance, Some(width), duration, threads).map(|result| (width, result)))
+        .collect()
+}
+
+/// Solves the instance once per width and prints the results as a table
+fn sweep(instance: &str, widths: &[usize], duration: Option<u64>, threads: Option<usize>) -> Result<(), String> {
+    let rows = sweep_rows(instance, widths, duration, threads)?;
+
+    println!("{:>8} {:>10} {:>8} {:>10} {:>12}", "width", "value", "optimal", "elapsed", "nodes");
+    for (width, (value, optimal, elapsed, nodes)) in rows {
+        println!("{:>8} {:>10} {:>8} {:>10.3} {:>12}", width, value, optimal, elapsed, nodes);
+    }
+    Ok(())
+}
+
+/// Settings of the solver selected by the command line arguments
+fn solver_config(args: &Args) -> SolverConfig {
+    SolverConfig {
+        width: if let Some(w) = args.width {
+            WidthChoice::Fixed(w)
+        } else if args.no_relax {
+            WidthChoice::Unbounded
+        } else if let Some(base) = args.adaptive_width {
+            WidthChoice::Adaptive(base)
+        } else if let Some(factor) = args.width_factor {
+            WidthChoice::Scaled(factor)
+        } else {
+            WidthChoice::NbUnassigned
+        },
+        duration: args.duration,
+        threads: args.threads,
+        max_mem_mb: args.max_mem_mb,
+        ranking: args.ranking,
+        merge_looseness: args.merge_looseness,
+    }
+}
+
+/// Solves the instance once per number of threads and returns the number of threads along
+/// with the value, the elapsed time, the speedup and the efficiency relative to the first one
+fn scale_rows(instance: &str, threads: &[usize], width: Option<usize>, duration: Option<u64>) -> Result<Vec<(usize, isize, f64, f64, f64)>, String> {
+    let mut rows = vec![];
+    let mut reference = None;
+    for n in threads.iter().copied() {
+        let (value, _, elapsed, _) = solve_default(instance, width, duration, Some(n))?;
+        let reference = *reference.get_or_insert(elapsed);
+        let speedup = if elapsed > 0.0 { reference / elapsed } else { f64::NAN };
+        let efficiency = speedup * threads[0] as f64 / n as f64;
+        rows.push((n, value, elapsed, speedup, efficiency));
+    }
+    Ok(rows)
+}
+
+/// Solves the instance once per number of threads and prints the results as a table
+fn scale(instance: &str, threads: &[usize], width: Option<usize>, duration: Option<u64>) -> Result<(), String> {
+    let rows = scale_rows(instance, threads, width, duration)?;
+
+    println!("{:>8} {:>10} {:>10} {:>10} {:>10}", "threads", "value", "elapsed", "speedup", "efficiency");
+    for (n, value, elapsed, speedup, efficiency) in rows {
+        println!("{:>8} {:>10} {:>10.3} {:>10.2} {:>10.2}", n, value, elapsed, speedup, efficiency);
+    }
+    Ok(())
+}
+
+/// Runs the given subcommand, exiting with an error code if it fails
+fn run_command(command: &Command) {
+    match command {
+        Command::Convert { from, to, input, output } => convert(*from, *to, input, output),
+        Command::Batch { out, width, duration, threads, instances } => {
+            if let Err(e) = batch(out, instances, *width, *duration, *threads) {
+                eprintln!("Error     : {}", e);
+                exit(1);
+            }
+        },
+        Command::Sweep { widths, duration, threads, instance } => {
+            if let Err(e) = sweep(instance, widths, *duration, *threads) {
+                eprintln!("Error     : {}", e);
+                exit(EXIT_INVALID);
+            }
+        },
+        Command::Scale { threads, width, duration, instance } => {
+            if let Err(e) = scale(instance, threads, *width, *duration) {
+                eprintln!("Error     : {}", e);
+                exit(EXIT_INVALID);
+            }
+        },
+    }
+}
+
+/// Reads the instance and builds the problem described by the command line arguments,
+/// or returns the exit code and the message of the error that prevents it
+fn build_problem(args: &Args) -> Result<Rcpsp, (i32, String)> {
+    let invalid = |e: String| (EXIT_INVALID, e);
+
+    let mut instance = load_instance(args.instance.as_deref().unwrap_or_default(), args.scale, args.quiet).map_err(invalid)?;
+    if !args.project.is_empty() {
+        let mut projects = vec![instance];
+        for path in args.project.iter() {
+            projects.push(load_instance(path, args.scale, args.quiet).map_err(invalid)?);
+        }
+        instance = RcpspInstance::combine(&projects).map_err(invalid)?;
+    }
+    if args.energy_mode {
+        let reported = instance.warnings.len();
+        instance.energy_to_rate();
+        for warning in instance.warnings[reported..].iter() {
+            warn!(args.quiet, "{}", warning);
+        }
+    }
+    let exceeding = (0..instance.n_jobs)
+        .flat_map(|i| (0..instance.n_resources).map(move |r| (i, r)))
+        .find(|(i, r)| instance.consumption[*i][*r] > instance.capacity[*r]);
+    if let Some((i, r)) = exceeding {
+        return Err((EXIT_INFEASIBLE, format!("job {} exceeds the capacity of resource {}, no schedule is feasible", i + 1, r + 1)));
+    }
+    if args.reduce {
+        info!(args.quiet, "Reduced   : {} edges", instance.transitive_reduction());
+    }
+    if let Some(max_parallel) = args.max_parallel {
+        instance.add_parallel_limit(max_parallel);
+    }
+    if args.bucket > 1 {
+        instance.coarsen(args.bucket).map_err(invalid)?;
+    }
+
+    let mut problem = Rcpsp::try_new(instance).map_err(invalid)?;
+    problem.full_propagation = args.full_propagation;
+    if args.trace {
+        problem.trace = Some(LogSink::stderr());
+    }
+    problem.non_delay = args.non_delay;
+    problem.beam = args.beam;
+    problem.objective = args.objective;
+    problem.deadline = args.deadline.map(|d| (d * problem.instance.scale).div_euclid(args.bucket)).or(problem.deadline);
+    if let Some(factor) = args.deadline_factor {
+        let deadline = problem.deadline_for_factor(factor);
+        problem.deadline = Some(deadline);
+        info!(args.quiet, "Deadline  : {}", deadline + problem.instance.duration[problem.sink]);
+    }
+    for (i, j, r) in problem.forced_conflicts() {
+        warn!(args.quiet, "jobs {} and {} must overlap to meet the deadline but exceed the capacity of resource {}", i + 1, j + 1, r + 1);
+    }
+    problem.fix_starts(&args.fix).map_err(invalid)?;
+    Ok(problem)
+}
+
+/// Writes the times at which the usage of each resource changes in the given schedule,
+/// rendered by the given function, followed by the usage from then on
+fn write_profiles(problem: &Rcpsp, starts: &[isize], path: &str, time: &dyn Fn(isize) -> String) -> std::io::Result<()> {
+    let mut file = File::create(path)?;
+    for r in 0..problem.instance.n_resources {
+        writeln!(file, "resource {} capacity {}", r + 1, problem.instance.capacity[r])?;
+        for (at, usage) in problem.usage_breakpoints(starts, r) {
+            writeln!(file, "{} {}", time(at), usage)?;
+        }
+    }
+    Ok(())
+}
+
+/// Lines describing the given solution as requested by the command line arguments, after
+/// writing its profiles to the file given by --profile-out, if any
+fn solution_report(problem: &Rcpsp, args: &Args, solution: Vec<Decision>) -> Result<Vec<String>, String> {
+    let mut lines = vec![];
+    let starts = problem.schedule(&solution);
+
+    if !problem.instance.optional.is_clear() {
+        let dropped = problem.dropped(&solution).iter().map(|i| (i + 1).to_string()).collect::<Vec<String>>();
+        lines.push(format!("Dropped   : {}", dropped.join(" ")));
+    }
+
+    if args.critical_path {
+        let slacks = problem.slacks(&starts);
+        let mut critical = (0..problem.instance.n_jobs).filter(|i| slacks[*i] == 0).collect::<Vec<usize>>();
+        critical.sort_by_key(|i| starts[*i]);
+        let critical = critical.iter().map(|i| (i + 1).to_string()).collect::<Vec<String>>();
+        lines.push(format!("Critical  : {}", critical.join(" ")));
+    }
+
+    if args.left_shift {
+        let mut shifted = starts.clone();
+        problem.left_shift(&mut shifted);
+        let makespan = (0..problem.instance.n_jobs).map(|i| shifted[i] + problem.instance.duration[i]).max().unwrap_or(0);
+        lines.push(format!("Shifted   : {}", makespan * args.bucket));
+    }
+
+    if args.sparkline {
+        for (r, line) in sparklines(problem, &starts).iter().enumerate() {
+            lines.push(format!("R{:<9}: |{}|", r + 1, line));
+        }
+    }
+
+    if args.cost {
+        lines.push(format!("Cost      : {}", problem.resource_cost(&starts)));
+    }
+
+    let calendar = args.start_date.map(|start| Calendar::new(start, args.unit));
+    let render_time = |t: isize| calendar.map_or_else(|| t.to_string(), |c| c.render(t));
+
+    if let Some(path) = &args.msproject {
+        File::create(path)
+            .and_then(|mut file| write_msproject(problem, &starts, &mut file, &render_time))
+            .map_err(|e| format!("cannot write {}: {}", path, e))?;
+    }
+
+    if let Some(path) = &args.profile_out {
+        write_profiles(problem, &starts, path, &render_time).map_err(|e| format!("cannot write {}: {}", path, e))?;
+    }
+
+    if args.emit == Some(Emit::Order) {
+        let order = scheduled_order(problem, solution);
+        if !problem.is_topological_order(&order) {
+            return Err("the order of the best solution violates the precedences".to_string());
+        }
+        let order = order.iter().map(|i| (i + 1).to_string()).collect::<Vec<String>>();
+        lines.push(format!("Order     : {}", order.join(" ")));
+    }
+
+    Ok(lines)
+}
+
+/// Lines comparing the given best value to the best value found with the start times
+/// given by --what-if, and to the makespan without the limit of each resource with
+/// --bottleneck, the new solves using the given configuration
+fn sensitivity_report(problem: &Rcpsp, args: &Args, config: &SolverConfig, best: isize) -> Vec<String> {
+    let mut lines = vec![];
+
+    if !args.what_if.is_empty() {
+        match problem.evaluate_with_fixed(&args.what_if, config, config.time_budget().as_ref()) {
+            Some(value) => lines.push(format!("What-if   : {} ({:+})", value * args.bucket, value * args.bucket - best)),
+            None => lines.push("What-if   : infeasible".to_string()),
+        }
+    }
+
+    if args.bottleneck {
+        for r in 0..problem.instance.n_resources {
+            match problem.makespan_without_resource(r, config, config.time_budget().as_ref()) {
+                Some(makespan) => lines.push(format!("R{:<9}: {} without its limit ({} less)", r + 1, makespan * args.bucket, best - makespan * args.bucket)),
+                None => lines.push(format!("R{:<9}: no schedule found without its limit", r + 1)),
+            }
+        }
+    }
+
+    lines
 }
 
 fn main() {
     let args = Args::parse();
 
-    let instance = RcpspInstance::from(File::open(&args.instance).unwrap());
-    let problem = Rcpsp::new(instance);
-    let relaxation = RcpspRelax{pb: &problem};
-    let ranking = RcpspRanking;
+    if let Some(command) = &args.command {
+        run_command(command);
+        return;
+    }
 
-    let width: Box<dyn WidthHeuristic<_> + Send + Sync> = if let Some(w) = args.width {
-        Box::new(FixedWidth(w))
-    } else {
-        Box::new(NbUnassignedWitdh(problem.nb_variables()))
+    // the search only reports the makespan of the schedules it completes, the value of the
+    // other objectives depending on the whole path
+    if args.stagnation_secs.is_some() && args.objective != Objective::Makespan {
+        eprintln!("Error     : --stagnation-secs is only supported with the makespan objective");
+        exit(EXIT_INVALID);
+    }
+    if args.target.is_some() && args.objective != Objective::Makespan {
+        eprintln!("Error     : --target is only supported with the makespan objective");
+        exit(EXIT_INVALID);
+    }
+
+    let instance_path = args.instance.clone().unwrap();
+    let mut problem = match build_problem(&args) {
+        Ok(problem) => problem,
+        Err((code, e)) => {
+            eprintln!("Error     : {}", e);
+            exit(code);
+        },
     };
-    let cutoff: Box<dyn Cutoff + Send + Sync> = if let Some(d) = args.duration {
-        Box::new(TimeBudget::new(Duration::from_secs(d)))
+    let incumbent = Arc::new(Incumbent::default());
+    if args.stagnation_secs.is_some() || args.target.is_some() {
+        problem.incumbent = Some(incumbent.clone());
+    }
+    if let Some(path) = &args.dot {
+        let result = File::create(path)
+            .and_then(|mut file| problem.instance.to_dot(&mut file, &problem.critical_edges()));
+        if let Err(e) = result {
+            eprintln!("Error     : cannot write {}: {}", path, e);
+            exit(1);
+        }
+    }
+    if args.earliest {
+        let earliest = problem.job_earliest_starts().iter().map(|e| e.to_string()).collect::<Vec<String>>();
+        info!(args.quiet, "Earliest  : {}", earliest.join(" "));
+    }
+    if args.root_bound {
+        info!(args.quiet, "Bound     : {}", problem.lower_bound() * args.bucket);
+    }
+    if args.contention {
+        for line in contention_report(&problem) {
+            info!(args.quiet, "{}", line);
+        }
+    }
+    if args.compare_bounds {
+        compare_bounds(&problem, args.seed, args.quiet);
+        return;
+    }
+    if args.interactive {
+        match interactive(&problem, std::io::stdin().lock(), args.quiet) {
+            Ok(starts) if args.quiet => println!("{}", starts[problem.sink]),
+            Ok(starts) => println!("Makespan  : {}", starts[problem.sink]),
+            Err((code, e)) => {
+                eprintln!("Error     : {}", e);
+                exit(code);
+            },
+        }
+        return;
+    }
+    if args.heuristic_only {
+        let time = Instant::now();
+        let best = best_heuristic(&problem, args.seed);
+        let elapsed = time.elapsed();
+        match best {
+            Some((value, rule)) => {
+                print_best(problem.objective.value(value) * args.bucket, problem.instance.scale, args.quiet);
+                info!(args.quiet, "Heuristic : {:?}", rule);
+                info!(args.quiet, "Optimal   : false");
+                info!(args.quiet, "Elapsed   : {}", elapsed.as_secs_f64());
+                exit(EXIT_FEASIBLE);
+            },
+            None => {
+                info!(args.quiet, "Optimal   : false");
+                exit(EXIT_INFEASIBLE);
+            },
+        }
+    }
+
+    match args.bound {
+        Bound::CriticalPath => {},
+        Bound::Energy => problem.bound = Arc::new(EnergyBound::new(&problem)),
+    }
+    let mut relaxation = RcpspRelax::new(&problem);
+    relaxation.merge_earliest = args.merge_earliest;
+    relaxation.cap_bucket = args.cap_bucket;
+    if args.explain {
+        relaxation.explain = Some(LogSink::stderr());
+    }
+    if args.explain_profiles {
+        relaxation.explain_profiles = Some(LogSink::stderr());
+    }
+
+    let config = solver_config(&args);
+    let ranking = config.ranking(&problem);
+    let width = config.width_heuristic(problem.nb_variables());
+    let cutoff: Box<dyn Cutoff + Send + Sync> = if let Some(s) = args.stagnation_secs {
+        Box::new(StagnationCutoff::new(incumbent.clone(), Duration::from_secs(s), args.duration.map(Duration::from_secs)))
     } else {
-        Box::new(NoCutoff)
+        config.time_budget()
+    };
+    let cutoff: Box<dyn Cutoff + Send + Sync> = match args.target {
+        Some(target) => Box::new(TargetCutoff::new(incumbent.clone(), (target * problem.instance.scale).div_euclid(args.bucket), cutoff)),
+        None => cutoff,
     };
+    // the first interrupt stops the search at the next check of the cutoff, the second one
+    // exits immediately in case the solver does not check it for a long time
+    let interrupted = Arc::new(AtomicBool::new(false));
+    let flag = interrupted.clone();
+    if let Err(e) = ctrlc::set_handler(move || if flag.swap(true, Ordering::Relaxed) { exit(130) }) {
+        warn!(args.quiet, "cannot handle interrupts: {}", e);
+    }
+    let cutoff: Box<dyn Cutoff + Send + Sync> = Box::new(InterruptCutoff::new(interrupted.clone(), cutoff));
 
-    let mut fringe = NoDupFringe::new(MaxUB::new(&ranking));
+    let mut fringe = CheckpointFringe::new(config.fringe(&ranking));
+    let mut resumed_best = None;
+    if let Some(path) = &args.resume {
+        match Checkpoint::read(path) {
+            Ok(checkpoint) if checkpoint.n_jobs != problem.instance.n_jobs => {
+                eprintln!("Error     : {} was written for an instance with {} jobs", path, checkpoint.n_jobs);
+                exit(EXIT_INVALID);
+            },
+            Ok(checkpoint) => {
+                info!(args.quiet, "Resumed   : {} open nodes", checkpoint.nodes.len());
+                if !checkpoint.nodes.is_empty() {
+                    fringe.resume(checkpoint.nodes);
+                }
+                resumed_best = checkpoint.best;
+            },
+            Err(e) => {
+                eprintln!("Error     : cannot read {}: {}", path, e);
+                exit(EXIT_INVALID);
+            },
+        }
+    }
     let mut solver = ParBarrierSolverFc::new(
         &problem, 
         &relaxation, 
@@ -57,16 +1067,456 @@ fn main() {
         cutoff.as_ref(), 
         &mut fringe);
 
-    if let Some(threads) = args.threads {
-        solver = solver.with_nb_threads(threads);
+    if let Some((value, decisions)) = resumed_best {
+        info!(args.quiet, "Checkpoint: {}", problem.objective.value(value) * args.bucket);
+        solver.set_primal(value, decisions);
+    }
+
+    if let Some(rule) = args.priority_rule {
+        let decisions = serial_sgs(&problem, &priority_order(rule, &problem, args.seed));
+        if let Some(value) = heuristic_value(&problem, &decisions) {
+            info!(args.quiet, "Heuristic : {}", problem.objective.value(value));
+            incumbent.report_schedule(problem.objective.value(value), &problem.schedule(&decisions));
+            solver.set_primal(value, decisions);
+        }
     }
+
+    if let Some((value, decisions)) = best_restart(&problem, args.restarts, args.seed) {
+        info!(args.quiet, "Restarts  : {}", problem.objective.value(value));
+        incumbent.report_schedule(problem.objective.value(value), &problem.schedule(&decisions));
+        solver.set_primal(value, decisions);
+    }
+
+    let threads = config.nb_threads();
+    solver = solver.with_nb_threads(threads);
+    info!(args.quiet, "Threads   : {}", threads);
     
     let time = Instant::now();
     let Completion{is_exact, best_value} = solver.maximize();
+    let completed = is_exact;
+    let is_exact = is_exact && !interrupted.load(Ordering::Relaxed) && !args.non_delay && args.beam.is_none() && args.merge_earliest == MergeEarliest::Min && args.bucket == 1;
     let duration = time.elapsed();
-    let best = best_value.map_or(isize::MIN, |value| - value);
+    let mut best = best_value.map_or(isize::MIN, |value| problem.objective.value(value));
+    // the target cutoff may stop the solver before it records the schedule that reached
+    // the target, whose makespan is then only known to the incumbent
+    let from_incumbent = args.target.is_some() && incumbent.best() != isize::MAX
+        && (best_value.is_none() || incumbent.best() < best);
+    if from_incumbent {
+        best = incumbent.best();
+    }
+    // the values above are in buckets, the reported one is in time units again
+    let coarse = best;
+    let best = best.saturating_mul(args.bucket);
+
+    print_best(best, problem.instance.scale, args.quiet);
+    if let Some(normalize) = args.normalize.filter(|_| best_value.is_some() || from_incumbent) {
+        if let Some(ratio) = normalized_value(&problem, coarse, normalize) {
+            info!(args.quiet, "Normalized: {:.4}", ratio);
+        }
+    }
+    info!(args.quiet, "Optimal   : {}", is_exact);
+    if interrupted.load(Ordering::Relaxed) {
+        info!(args.quiet, "Interrupt : best solution found before the interrupt");
+    }
+    info!(args.quiet, "Elapsed   : {}", duration.as_secs_f64());
+    info!(args.quiet, "Nodes     : {}", problem.transitions.load(Ordering::Relaxed));
+    if args.stats {
+        for (depth, count) in problem.transitions_per_depth.iter().enumerate() {
+            let count = count.load(Ordering::Relaxed);
+            if count > 0 {
+                info!(args.quiet, "D{:<9}: {}", depth, count);
+            }
+        }
+    }
+
+    if let Some(solution) = solver.best_solution().filter(|_| !from_incumbent) {
+        match solution_report(&problem, &args, solution) {
+            Ok(lines) => lines.iter().for_each(|line| info!(args.quiet, "{}", line)),
+            Err(e) => {
+                eprintln!("Error     : {}", e);
+                exit(1);
+            },
+        }
+    }
+
+    if best_value.is_some() {
+        for line in sensitivity_report(&problem, &args, &config, best) {
+            info!(args.quiet, "{}", line);
+        }
+    }
+
+    if args.all_optima && is_exact && args.objective == Objective::Makespan && problem.instance.optional.is_clear() {
+        let optima = problem.all_optima(best, args.max_optima);
+        info!(args.quiet, "Optima    : {}", optima.len());
+        for starts in optima.iter() {
+            let starts = starts.iter().map(|s| s.to_string()).collect::<Vec<String>>();
+            info!(args.quiet, "  {}", starts.join(" "));
+        }
+    }
+
+    if args.expect_optimum && is_exact {
+        let path = Path::new(&instance_path);
+        let expected = read_known_optimum(path.with_extension("opt"))
+            .or_else(|| read_known_optimum(path.with_extension("bub")));
+        match expected {
+            Some(expected) if expected != best => {
+                eprintln!("Mismatch  : expected {} but found {}", expected, best);
+                exit(1);
+            },
+            Some(_) => info!(args.quiet, "Expected  : ok"),
+            None => {
+                eprintln!("No known optimum found for {}", instance_path);
+                exit(1);
+            },
+        }
+    }
+
+    if let (Some(path), false) = (&args.checkpoint, completed) {
+        let best = best_value.zip(solver.best_solution());
+        drop(solver);
+        let checkpoint = Checkpoint { n_jobs: problem.instance.n_jobs, best, nodes: fringe.drain_open() };
+        match checkpoint.write(path) {
+            Ok(()) => info!(args.quiet, "Saved     : {} open nodes to {}", checkpoint.nodes.len(), path),
+            Err(e) => {
+                eprintln!("Error     : cannot write {}: {}", path, e);
+                exit(1);
+            },
+        }
+    }
+
+    if best_value.is_none() && !from_incumbent {
+        exit(EXIT_INFEASIBLE);
+    } else if !is_exact {
+        exit(EXIT_FEASIBLE);
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use std::collections::HashSet;
+
+    use ddo::{MaxUB, NoDupFringe, NbUnassignedWitdh, NoCutoff};
+
+    use crate::{model::tests::solve, heuristics::RcpspRanking};
 
-    println!("Best value: {}", best);
-    println!("Optimal   : {}", is_exact);
-    println!("Elapsed   : {}", duration.as_secs_f64());
+    use super::*;
+
+    #[test]
+    fn sampled_bounds_never_exceed_the_makespan() {
+        let content = "7 2\n2 1\n0 0 0 3 2 3 4\n2 1 1 1 5\n3 1 0 1 6\n2 0 1 1 6\n1 1 1 1 7\n2 2 0 1 7\n0 0 0 0\n";
+        let problem = Rcpsp::new(RcpspInstance::try_from(std::io::BufReader::new(content.as_bytes())).unwrap());
+        let optimum = solve(&problem);
+
+        for seed in 0..5 {
+            let decisions = serial_sgs(&problem, &priority_order(PriorityRule::Random, &problem, seed));
+            let makespan = problem.objective.value(problem.objective.schedule_value(&problem.instance, &problem.schedule(&decisions)));
+
+            let (names, rows) = sample_bounds(&problem, seed);
+            assert_eq!(names, vec!["critical-path", "energy"]);
+            assert_eq!(rows[0].0, 0);
+            assert!(rows[0].1.iter().all(|bound| *bound <= optimum));
+            // the sampled states lie on the random schedule
+            assert!(rows.iter().all(|(_, bounds)| bounds.iter().all(|bound| *bound <= makespan)));
+        }
+    }
+
+    #[test]
+    fn fractional_instance_is_solved_at_scale() {
+        let content = "4 1\n1\n0 0 2 2 3\n1.5 1 1 4\n2.25 1 1 4\n0 0 0\n";
+        let instance = RcpspInstance::from_scaled(content.as_bytes().lines(), 100).unwrap();
+        assert_eq!(instance.duration, vec![0, 150, 225, 0]);
+        assert_eq!(instance.capacity, vec![100]);
+        assert_eq!(instance.consumption[1], vec![100]);
+
+        let problem = Rcpsp::new(instance);
+        let makespan = solve(&problem);
+        assert_eq!(makespan, 375);
+        assert_eq!(scaled_value(makespan, problem.instance.scale), "3.75 (375 scaled)");
+        assert_eq!(scaled_value(7, 1), "7");
+    }
+
+    #[test]
+    fn batch_writes_one_row_per_instance() {
+        let dir = std::env::temp_dir().join(format!("rcpsp_batch_{}", std::process::id()));
+        std::fs::create_dir_all(&dir).unwrap();
+        let serial = dir.join("serial.rcp");
+        let parallel = dir.join("parallel.rcp");
+        std::fs::write(&serial, "4 1\n1\n0 0 2 2 3\n1 1 1 4\n2 1 1 4\n0 0 0\n").unwrap();
+        std::fs::write(&parallel, "4 1\n2\n0 0 2 2 3\n1 1 1 4\n2 1 1 4\n0 0 0\n").unwrap();
+        let missing = dir.join("missing.rcp");
+        let invalid = dir.join("invalid.rcp");
+        std::fs::write(&invalid, "2 1\n1\n0 0 1 3\n0 0 0\n").unwrap();
+        let out = dir.join("results.csv");
+
+        let instances = [&serial, &parallel, &missing, &invalid].iter().map(|p| p.to_str().unwrap().to_string()).collect::<Vec<String>>();
+        batch(out.to_str().unwrap(), &instances, None, None, Some(1)).unwrap();
+
+        let csv = std::fs::read_to_string(&out).unwrap();
+        let rows = csv.lines().map(|l| l.splitn(6, ',').collect::<Vec<&str>>()).collect::<Vec<_>>();
+        assert_eq!(rows.len(), 5);
+        assert_eq!(rows[0], vec!["instance", "value", "optimal", "elapsed", "nodes", "error"]);
+        assert_eq!((rows[1][0], rows[1][1], rows[1][2], rows[1][5]), (instances[0].as_str(), "3", "true", ""));
+        assert_eq!((rows[2][0], rows[2][1], rows[2][2], rows[2][5]), (instances[1].as_str(), "2", "true", ""));
+        assert!(rows[1][4].parse::<usize>().unwrap() > 0);
+        assert_eq!(&rows[3][..5], &[instances[2].as_str(), "", "", "", ""]);
+        assert!(rows[3][5].starts_with('"') && rows[3][5].len() > 2);
+        assert_eq!(&rows[4][..5], &[instances[3].as_str(), "", "", "", ""]);
+        assert_eq!(rows[4][5], "\"line 3: job 3 does not exist\"");
+
+        std::fs::remove_dir_all(&dir).unwrap();
+    }
+
+    #[test]
+    fn sweep_gives_one_row_per_width() {
+        let path = std::env::temp_dir().join(format!("rcpsp_sweep_{}.rcp", std::process::id()));
+        std::fs::write(&path, "5 1\n2\n0 0 3 2 3 4\n3 1 1 5\n2 1 1 5\n2 1 1 5\n0 0 0\n").unwrap();
+
+        let rows = sweep_rows(path.to_str().unwrap(), &[1, 10], None, Some(1)).unwrap();
+        assert_eq!(rows.iter().map(|(width, (value, optimal, _, _))| (*width, *value, *optimal)).collect::<Vec<_>>(), vec![(1, 4, true), (10, 4, true)]);
+        assert!(sweep_rows("missing.rcp", &[1, 10], None, Some(1)).is_err());
+
+        std::fs::remove_file(&path).unwrap();
+    }
+
+    #[test]
+    fn scale_gives_one_row_per_number_of_threads() {
+        let path = std::env::temp_dir().join(format!("rcpsp_scale_{}.rcp", std::process::id()));
+        std::fs::write(&path, "5 1\n2\n0 0 3 2 3 4\n3 1 1 5\n2 1 1 5\n2 1 1 5\n0 0 0\n").unwrap();
+
+        let rows = scale_rows(path.to_str().unwrap(), &[1, 2], None, None).unwrap();
+        assert_eq!(rows.iter().map(|(n, value, _, _, _)| (*n, *value)).collect::<Vec<_>>(), vec![(1, 4), (2, 4)]);
+        assert!(rows[0].3.is_nan() || rows[0].3 == 1.0);
+
+        std::fs::remove_file(&path).unwrap();
+    }
+
+    #[test]
+    fn emitted_order_respects_the_precedences() {
+        let problem = Rcpsp::new(RcpspInstance::try_from(BufReader::new("7 2\n2 1\n0 0 0 3 2 3 4\n2 1 1 1 5\n3 1 0 1 6\n2 0 1 1 6\n1 1 1 1 7\n2 2 0 1 7\n0 0 0 0\n".as_bytes())).unwrap());
+        let relaxation = RcpspRelax::new(&problem);
+        let ranking = RcpspRanking::default();
+        let width = NbUnassignedWitdh(problem.nb_variables());
+        let mut fringe = NoDupFringe::new(MaxUB::new(&ranking));
+        let mut solver = ParBarrierSolverFc::new(&problem, &relaxation, &ranking, &width, &NoCutoff, &mut fringe);
+        solver.maximize();
+
+        let order = scheduled_order(&problem, solver.best_solution().unwrap());
+        assert!(problem.is_topological_order(&order));
+        for (position, job) in order.iter().enumerate() {
+            assert!(problem.instance.predecessors_set[*job].iter().all(|p| order[..position].contains(p)));
+        }
+
+        assert!(!problem.is_topological_order(&[1, 0, 2, 3, 4, 5, 6]));
+        assert!(!problem.is_topological_order(&[0, 1, 2, 3, 4, 5]));
+        assert!(!problem.is_topological_order(&[0, 1, 1, 3, 4, 5, 6]));
+    }
+
+    #[test]
+    fn target_below_the_optimum_runs_to_optimality() {
+        let content = "7 2\n2 1\n0 0 0 3 2 3 4\n2 1 1 1 5\n3 1 0 1 6\n2 0 1 1 6\n1 1 1 1 7\n2 2 0 1 7\n0 0 0 0\n";
+        let optimum = solve(&Rcpsp::new(RcpspInstance::try_from(BufReader::new(content.as_bytes())).unwrap()));
+
+        for target in [100, optimum - 1] {
+            let incumbent = Arc::new(Incumbent::default());
+            let mut problem = Rcpsp::new(RcpspInstance::try_from(BufReader::new(content.as_bytes())).unwrap());
+            problem.incumbent = Some(incumbent.clone());
+            let relaxation = RcpspRelax::new(&problem);
+            let ranking = RcpspRanking::default();
+            let width = NbUnassignedWitdh(problem.nb_variables());
+            let cutoff = TargetCutoff::new(incumbent.clone(), target, Box::new(NoCutoff));
+            let mut fringe = NoDupFringe::new(MaxUB::new(&ranking));
+            let mut solver = ParBarrierSolverFc::new(&problem, &relaxation, &ranking, &width, &cutoff, &mut fringe);
+
+            let Completion { is_exact, best_value } = solver.maximize();
+            if target < optimum {
+                assert!(is_exact);
+                assert_eq!(best_value.map(|value| problem.objective.value(value)), Some(optimum));
+            } else {
+                assert!(incumbent.best() <= target);
+            }
+        }
+    }
+
+    #[test]
+    fn sparklines_give_one_row_per_resource_with_one_block_per_bucket() {
+        let content = "4 2\n2 1\n0 0 0 2 2 3\n1 2 0 1 4\n2 1 1 1 4\n0 0 0 0\n";
+        let problem = Rcpsp::new(RcpspInstance::try_from(std::io::BufReader::new(content.as_bytes())).unwrap());
+        assert_eq!(sparklines(&problem, &[0, 0, 1, 3]), vec!["█▄▄", " ██"]);
+
+        let content = "3 1\n1\n0 0 1 2\n100 1 1 3\n0 0 0\n";
+        let problem = Rcpsp::new(RcpspInstance::try_from(std::io::BufReader::new(content.as_bytes())).unwrap());
+        let lines = sparklines(&problem, &[0, 0, 100]);
+        assert_eq!(lines.len(), 1);
+        assert_eq!(lines[0].chars().count(), SPARKLINE_BUCKETS as usize);
+        assert!(lines[0].chars().all(|c| c == '█'));
+    }
+
+    #[test]
+    fn normalized_value_is_the_ratio_to_the_baseline() {
+        // the energy of the jobs fits in 3 periods but they cannot overlap
+        let content = "4 1\n2\n0 0 2 2 3\n2 1 1 4\n2 2 1 4\n0 0 0\n";
+        let problem = Rcpsp::new(RcpspInstance::try_from(std::io::BufReader::new(content.as_bytes())).unwrap());
+        let optimum = solve(&problem);
+        assert_eq!(optimum, 4);
+        assert_eq!(normalized_value(&problem, optimum, Normalize::CriticalPath), Some(4.0 / 3.0));
+        assert_eq!(normalized_value(&problem, optimum, Normalize::SumDurations), Some(1.0));
+
+        let content = "2 1\n1\n0 0 1 2\n0 0 0\n";
+        let problem = Rcpsp::new(RcpspInstance::try_from(std::io::BufReader::new(content.as_bytes())).unwrap());
+        assert_eq!(normalized_value(&problem, 0, Normalize::SumDurations), None);
+    }
+
+    #[test]
+    fn no_relax_finds_the_same_optimum_as_the_relaxed_solve() {
+        let problem = Rcpsp::new(RcpspInstance::try_from(BufReader::new("7 2\n2 1\n0 0 0 3 2 3 4\n2 1 1 1 5\n3 1 0 1 6\n2 0 1 1 6\n1 1 1 1 7\n2 2 0 1 7\n0 0 0 0\n".as_bytes())).unwrap());
+        let optimum = |arguments: &[&str]| {
+            let args = Args::try_parse_from(arguments).unwrap();
+            let (completion, _) = solve_problem(&problem, &solver_config(&args));
+            assert!(completion.is_exact);
+            completion.best_value
+        };
+
+        assert_eq!(optimum(&["rcpsp", "--no-relax", "instance.rcp"]), optimum(&["rcpsp", "--width", "2", "instance.rcp"]));
+        assert!(Args::try_parse_from(["rcpsp", "--no-relax", "--width", "2", "instance.rcp"]).is_err());
+    }
+
+    #[test]
+    fn adaptive_width_still_gives_a_feasible_schedule() {
+        let problem = Rcpsp::new(RcpspInstance::try_from(BufReader::new("7 2\n2 1\n0 0 0 3 2 3 4\n2 1 1 1 5\n3 1 0 1 6\n2 0 1 1 6\n1 1 1 1 7\n2 2 0 1 7\n0 0 0 0\n".as_bytes())).unwrap());
+        let args = Args::try_parse_from(["rcpsp", "--adaptive-width", "2", "instance.rcp"]).unwrap();
+        assert_eq!(solver_config(&args).width, WidthChoice::Adaptive(2));
+        let (_, starts) = solve_problem(&problem, &solver_config(&args));
+        assert!(problem.is_feasible(&starts));
+    }
+
+    #[test]
+    fn build_problem_applies_the_arguments() {
+        let path = std::env::temp_dir().join(format!("rcpsp_build_{}.rcp", std::process::id()));
+        std::fs::write(&path, "4 1\n1\n0 0 2 2 3\n1 1 1 4\n2 1 1 4\n0 0 0\n").unwrap();
+        let path = path.to_str().unwrap();
+
+        let args = Args::try_parse_from(["rcpsp", "--quiet", "--deadline", "7", "--fix", "3:0", path]).unwrap();
+        let problem = build_problem(&args).unwrap();
+        assert_eq!(problem.deadline, Some(7));
+        assert_eq!(problem.fixed[2], Some(0));
+
+        let args = Args::try_parse_from(["rcpsp", "--quiet", "--fix", "3:0,2:0", path]).unwrap();
+        assert_eq!(build_problem(&args).unwrap_err().0, EXIT_INVALID);
+        let args = Args::try_parse_from(["rcpsp", "--quiet", "missing.rcp"]).unwrap();
+        assert_eq!(build_problem(&args).unwrap_err().0, EXIT_INVALID);
+
+        std::fs::write(path, "3 1\n1\n0 0 1 2\n2 2 1 3\n0 0 0\n").unwrap();
+        let args = Args::try_parse_from(["rcpsp", "--quiet", path]).unwrap();
+        assert_eq!(build_problem(&args).unwrap_err(), (EXIT_INFEASIBLE, "job 2 exceeds the capacity of resource 1, no schedule is feasible".to_string()));
+
+        std::fs::remove_file(path).unwrap();
+    }
+
+    #[test]
+    fn solution_report_gives_one_line_per_requested_detail() {
+        let problem = Rcpsp::new(RcpspInstance::try_from(BufReader::new("4 1\n1\n0 0 2 2 3\n1 1 1 4\n2 1 1 4\n0 0 0\n".as_bytes())).unwrap());
+        let relaxation = RcpspRelax::new(&problem);
+        let ranking = RcpspRanking::default();
+        let width = NbUnassignedWitdh(problem.nb_variables());
+        let mut fringe = NoDupFringe::new(MaxUB::new(&ranking));
+        let mut solver = ParBarrierSolverFc::new(&problem, &relaxation, &ranking, &width, &NoCutoff, &mut fringe);
+        solver.maximize();
+        let solution = solver.best_solution().unwrap();
+
+        let args = Args::try_parse_from(["rcpsp", "instance.rcp"]).unwrap();
+        assert!(solution_report(&problem, &args, solution.clone()).unwrap().is_empty());
+
+        let args = Args::try_parse_from(["rcpsp", "--left-shift", "--sparkline", "--emit", "order", "instance.rcp"]).unwrap();
+        let lines = solution_report(&problem, &args, solution).unwrap();
+        assert_eq!(lines.len(), 3);
+        assert_eq!(lines[0], "Shifted   : 3");
+        assert_eq!(lines[1], "R1        : |███|");
+        assert!(lines[2] == "Order     : 1 2 3 4" || lines[2] == "Order     : 1 3 2 4");
+    }
+
+    #[test]
+    fn overloaded_windows_are_reported_first() {
+        // jobs 2 and 3 both start at 0 on a resource that fits only one of them
+        let content = "5 2\n1 2\n0 0 0 3 2 3 4\n2 1 0 1 5\n3 1 1 1 5\n1 0 1 1 5\n0 0 0 0\n";
+        let problem = Rcpsp::new(RcpspInstance::try_from(BufReader::new(content.as_bytes())).unwrap());
+        assert_eq!(problem.root_contention(), vec![
+            vec![(0, 1, 2.0), (1, 2, 2.0), (2, 3, 1.0)],
+            vec![(0, 1, 1.0), (1, 2, 0.5), (2, 3, 0.5)],
+        ]);
+        assert_eq!(contention_report(&problem), vec![
+            "R1        : [0, 1) 2.00, [1, 2) 2.00, [2, 3) 1.00",
+            "R2        : [0, 1) 1.00, [1, 2) 0.50, [2, 3) 0.50",
+        ]);
+    }
+
+    #[test]
+    fn msproject_rows_list_the_direct_predecessors() {
+        let content = "7 2\n2 1\n0 0 0 3 2 3 4\n2 1 1 1 5\n3 1 0 1 6\n2 0 1 1 6\n1 1 1 1 7\n2 2 0 1 7\n0 0 0 0\n";
+        let problem = Rcpsp::new(RcpspInstance::try_from(BufReader::new(content.as_bytes())).unwrap());
+        let starts = vec![0, 0, 0, 2, 3, 4, 6];
+        let mut csv = vec![];
+        write_msproject(&problem, &starts, &mut csv, &|t| t.to_string()).unwrap();
+
+        let csv = String::from_utf8(csv).unwrap();
+        let mut rows = csv.lines();
+        assert_eq!(rows.next(), Some("ID,Name,Duration,Start,Finish,Predecessors"));
+        for (i, row) in rows.enumerate() {
+            let (fields, predecessors) = row.split_once(",\"").unwrap();
+            let fields = fields.split(',').collect::<Vec<&str>>();
+            assert_eq!(fields, vec![(i + 1).to_string(), format!("Job {}", i + 1), problem.instance.duration[i].to_string(), starts[i].to_string(), (starts[i] + problem.instance.duration[i]).to_string()]);
+            let predecessors = predecessors.trim_end_matches('"').split(',')
+                .filter(|p| !p.is_empty())
+                .map(|p| p.parse::<usize>().unwrap() - 1)
+                .collect::<HashSet<usize>>();
+            assert_eq!(predecessors, problem.instance.predecessors_set[i]);
+        }
+    }
+
+    #[test]
+    fn heuristic_only_gives_a_feasible_schedule_quickly_on_a_large_instance() {
+        let problem = Rcpsp::new(load_instance("resources/j120/j120_10_1.rcp", 1, true).unwrap());
+        let time = Instant::now();
+        let (value, rule) = best_heuristic(&problem, 7).unwrap();
+        assert!(time.elapsed().as_secs() < 5);
+
+        let starts = problem.schedule(&serial_sgs(&problem, &priority_order(rule, &problem, 7)));
+        assert!(problem.is_feasible(&starts));
+        assert_eq!(problem.objective.schedule_value(&problem.instance, &starts), value);
+        assert!(starts[problem.sink] >= problem.critical_path_length());
+    }
+
+    #[test]
+    fn restarts_are_reproducible_from_their_seed() {
+        let problem = Rcpsp::new(load_instance("resources/tests/two_resources.rcp", 1, true).unwrap());
+        let (value, decisions) = best_restart(&problem, 10, 3).unwrap();
+        assert_eq!(best_restart(&problem, 10, 3), Some((value, decisions.clone())));
+        assert!(problem.is_feasible(&problem.schedule(&decisions)));
+
+        // each random order is sampled among the topological orders, and the best one is kept
+        for k in 1..=10 {
+            let decisions = serial_sgs(&problem, &priority_order(PriorityRule::Random, &problem, 3 + k));
+            let order = decisions.iter().map(|d| problem.decode(d.value).0).collect::<Vec<usize>>();
+            assert!(problem.is_topological_order(&order));
+            assert!(heuristic_value(&problem, &decisions).unwrap() <= value);
+        }
+        assert!(best_restart(&problem, 0, 3).is_none());
+    }
+
+    #[test]
+    fn interactive_choices_are_read_until_the_schedule_is_complete() {
+        let problem = Rcpsp::new(RcpspInstance::try_from(BufReader::new("5 1\n2\n0 0 3 2 3 4\n3 1 1 5\n2 1 1 5\n2 2 1 5\n0 0 0\n".as_bytes())).unwrap());
+        // the choices that cannot be scheduled next are skipped
+        let starts = interactive(&problem, "1\n5\n4\nx\n2\n3\n5\n".as_bytes(), true).unwrap();
+        assert_eq!(starts, vec![0, 2, 2, 0, 5]);
+        assert!(problem.is_feasible(&starts));
+
+        assert_eq!(interactive(&problem, "1\n4\n".as_bytes(), true).unwrap_err().0, 1);
+    }
+
+    #[test]
+    fn absurd_thread_counts_are_rejected() {
+        assert!(Args::try_parse_from(["rcpsp", "--threads", "4", "instance.rcp"]).is_ok());
+        assert!(Args::try_parse_from(["rcpsp", "--threads", "-1", "instance.rcp"]).is_err());
+        assert!(Args::try_parse_from(["rcpsp", "--threads", "100000", "instance.rcp"]).is_err());
+    }
 }
@@ -1,9 +1,9 @@
-use std::{vec, collections::VecDeque};
+use std::{vec, borrow::Cow, collections::{VecDeque, HashSet}, sync::{Arc, atomic::{AtomicUsize, Ordering}}};
 
-use ddo::{Problem, Variable, Decision, DecisionCallback};
+use ddo::{Problem, Variable, Decision, DecisionCallback, Completion, Cutoff};
 use fixedbitset::FixedBitSet;
 
-use crate::{instance::RcpspInstance, state::{State, ConsumptionProfile, ConsumptionStep}};
+use crate::{instance::{RcpspInstance, ConsumptionSegment}, state::{State, ConsumptionProfile, ConsumptionStep}, bounds::{BoundProvider, CriticalPathBound, EnergyBound}, objective::Objective, sink::LogSink, cutoff::Incumbent, config::{SolverConfig, solve_with_cutoff}};
 
 
 /// This is the structure encapsulating the Rcpsp problem.
@@ -12,25 +12,315 @@ pub struct Rcpsp {
     pub instance: RcpspInstance,
     pub initial : State,
     pub topo_order: Vec<usize>,
+    /// Successors of each job along with the minimum delay between their start times
+    pub lags: Vec<Vec<(usize, isize)>>,
+    /// Lags used by the propagation, without those of the optional jobs, which only
+    /// delay their successors once they are scheduled
+    pub propagated_lags: Vec<Vec<(usize, isize)>>,
+    /// Jobs that transitively precede each job
+    pub ancestors: Vec<FixedBitSet>,
+    /// Jobs that transitively succeed each job
+    pub descendants: Vec<FixedBitSet>,
+    /// Jobs sharing at least one resource with each job
+    pub competitors: Vec<FixedBitSet>,
+    /// Whether to propagate the whole schedule after each transition
+    pub full_propagation: bool,
+    /// Lower bound used to prune the nodes, shared by the clones of the problem
+    pub bound: Arc<dyn BoundProvider + Send + Sync>,
+    /// Objective to minimize
+    pub objective: Objective,
+    /// Where to log each transition, if anywhere
+    pub trace: Option<LogSink>,
+    /// Receives the makespan of each complete schedule found
+    pub incumbent: Option<Arc<Incumbent>>,
+    /// Start time imposed to some of the jobs
+    pub fixed: Vec<Option<isize>>,
+    /// Number of transitions computed so far
+    pub transitions: Arc<AtomicUsize>,
+    /// Number of transitions computed so far from the states of each depth
+    pub transitions_per_depth: Arc<Vec<AtomicUsize>>,
+    /// Upper bound on the makespan of any schedule built by the model, used as
+    /// the end of the consumption profiles
+    pub horizon: isize,
+    /// Whether to only schedule the eligible jobs that can start the earliest, which
+    /// restricts the search to non-delay schedules and may miss the optimal ones
+    pub non_delay: bool,
+    /// Time by which the sink must have started, if any
+    pub deadline: Option<isize>,
+    /// The unique job without successors, whose start is the makespan
+    pub sink: usize,
+    /// Consumption of each job seen by the search, which is zero for the fixed jobs
+    /// since their consumption is already in the initial profiles
+    pub consumption: Vec<Vec<isize>>,
+    /// Consumption segments of each job seen by the search, none for the fixed jobs
+    pub segments: Vec<Option<Vec<ConsumptionSegment>>>,
+    /// Energy (consumption over the whole duration) of each job on each resource seen
+    /// by the search, which is zero for the fixed jobs
+    pub energy: Vec<Vec<isize>>,
+    /// Time before which each job cannot start given the energy of its ancestors
+    pub release: Vec<isize>,
+    /// Max number of jobs offered from each state, those that can start the earliest
+    /// being preferred, which may exclude all the optimal schedules
+    pub beam: Option<usize>,
+    /// Longest path from the start of the project to the start of each job
+    pub head: Vec<isize>,
+    /// Longest path from the start of each job to the end of the project
+    pub tail: Vec<isize>,
 }
 impl Rcpsp {
     pub fn new(inst: RcpspInstance) -> Self {
-        let mut consumption = vec![];
-        for i in 0..inst.n_resources {
-            let mut steps = VecDeque::new();
-            steps.push_back(ConsumptionStep { start: 0, end: isize::MAX, rem_capacity: inst.capacity[i]});
-            consumption.push(ConsumptionProfile { steps });
+        Self::try_new(inst).unwrap_or_else(|e| panic!("{}", e))
+    }
+
+    /// Creates the problem, failing if some jobs cannot be ordered because they belong
+    /// to or follow a precedence cycle
+    pub fn try_new(inst: RcpspInstance) -> Result<Self, String> {
+        inst.validate()?;
+        let inst = inst.with_virtual_terminals();
+        let order = inst.topological_order();
+        if order.len() != inst.n_jobs {
+            let mut ordered = FixedBitSet::with_capacity(inst.n_jobs);
+            ordered.extend(order.iter().copied());
+            // numbered as in the original instance, without the virtual source and sink
+            let shift = inst.virtual_jobs.contains(0) as usize;
+            let missing = (0..inst.n_jobs)
+                .filter(|i| !ordered.contains(*i) && !inst.virtual_jobs.contains(*i))
+                .map(|i| (i + 1 - shift).to_string())
+                .collect::<Vec<String>>();
+            return Err(format!("jobs {} are part of or follow a precedence cycle", missing.join(" ")));
         }
+
+        let sink = (0..inst.n_jobs).find(|i| inst.successors_set[*i].is_empty()).unwrap_or(0);
+        if inst.optional.contains(sink) {
+            return Err(format!("job {} is the last job and cannot be optional", sink + 1));
+        }
+
+        let horizon = inst.horizon.map_or_else(|| Self::horizon(&inst), |h| h.max(1));
         let mut state = State {
             done: FixedBitSet::with_capacity(inst.n_jobs),
             maybe_done: None,
-            profile: consumption,
+            profile: Self::initial_profiles(&inst, horizon),
             earliest: vec![0; inst.n_jobs],
-            depth : 0
+            depth : 0,
+            origin: 0,
+            dead_end: false,
+        };
+        let lags = inst.lagged_successors();
+        let propagated_lags = (0..inst.n_jobs)
+            .map(|i| if inst.optional.contains(i) { vec![] } else { lags[i].clone() })
+            .collect::<Vec<Vec<(usize, isize)>>>();
+        let competitors = Self::competitors(&inst);
+        let descendants = inst.descendants();
+        let mut ancestors = vec![FixedBitSet::with_capacity(inst.n_jobs); inst.n_jobs];
+        for (i, desc) in descendants.iter().enumerate() {
+            desc.ones().for_each(|j| ancestors[j].insert(i));
+        }
+        let release = Self::energy_release(&inst, &ancestors);
+        state.earliest = release.clone();
+        state.propagate(&order, &propagated_lags, &inst.duration, &inst.consumption, &inst.segments);
+        let n_jobs = inst.n_jobs;
+        let consumption = inst.consumption.clone();
+        let segments = inst.segments.clone();
+        let energy = (0..n_jobs).map(|i| (0..inst.n_resources).map(|r| inst.energy(i, r)).collect()).collect();
+        let deadline = inst.horizon;
+        let (head, tail) = Self::longest_paths(&inst, &order, &lags);
+        let mut pb = Self { instance: inst, initial: state, topo_order: order, lags, propagated_lags, ancestors, descendants, competitors, full_propagation: false, bound: Arc::new(CriticalPathBound::default()), objective: Objective::Makespan, trace: None, incumbent: None, fixed: vec![None; n_jobs], transitions: Arc::new(AtomicUsize::new(0)), transitions_per_depth: Arc::new((0..n_jobs).map(|_| AtomicUsize::new(0)).collect()), horizon, non_delay: false, deadline, sink, consumption, segments, energy, release, beam: None, head, tail };
+        // the bound needs the closure of the precedences computed above
+        pb.bound = Arc::new(CriticalPathBound::new(&pb));
+        Ok(pb)
+    }
+
+    /// Earliest start of each job in the initial state, given by its predecessors, the energy
+    /// of its ancestors and the initial profiles. The fixed jobs start at their imposed time.
+    pub fn job_earliest_starts(&self) -> Vec<isize> {
+        (0..self.instance.n_jobs)
+            .map(|i| self.fixed[i].unwrap_or(self.initial.origin + self.initial.earliest[i]))
+            .collect()
+    }
+
+    /// Estimates the contention of each resource over time if every job started at its
+    /// earliest start in the initial state, as the intervals (start, end, ratio) where the
+    /// ratio is the total consumption of the jobs running divided by the capacity
+    pub fn root_contention(&self) -> Vec<Vec<(isize, isize, f64)>> {
+        let inst = &self.instance;
+        let starts = self.job_earliest_starts();
+        let mut times = (0..inst.n_jobs)
+            .flat_map(|i| [starts[i], starts[i] + inst.duration[i]])
+            .collect::<Vec<isize>>();
+        times.sort_unstable();
+        times.dedup();
+
+        (0..inst.n_resources).map(|r| {
+            times.windows(2).filter_map(|w| {
+                let demand = (0..inst.n_jobs)
+                    .filter(|i| !self.initial.done.contains(*i) && starts[*i] <= w[0] && starts[*i] + inst.duration[*i] >= w[1])
+                    .map(|i| inst.consumption[i][r])
+                    .sum::<isize>();
+                if demand == 0 {
+                    return None;
+                }
+                let ratio = if inst.capacity[r] > 0 { demand as f64 / inst.capacity[r] as f64 } else { f64::INFINITY };
+                Some((w[0], w[1], ratio))
+            }).collect()
+        }).collect()
+    }
+
+    /// Length of the longest path in the precedence network, ignoring the resources
+    pub fn critical_path_length(&self) -> isize {
+        self.head[self.sink] + self.instance.duration[self.sink]
+    }
+
+    /// Deadline on the start of the sink such that the makespan is at most the given
+    /// multiple of the length of the critical path, rounded up
+    pub fn deadline_for_factor(&self, factor: f64) -> isize {
+        (factor * self.critical_path_length() as f64).ceil() as isize - self.instance.duration[self.sink]
+    }
+
+    /// Lower bound on the makespan computed on the initial state without any search,
+    /// as the best of the critical path and energy bounds
+    pub fn lower_bound(&self) -> isize {
+        let bound = self.initial.origin + EnergyBound::new(self).lower_bound(&self.initial, &self.instance);
+        bound.max(self.total_energy_bound())
+    }
+
+    /// Lower bound on the makespan given by the total energy of the mandatory jobs on
+    /// each resource divided by its capacity
+    pub fn total_energy_bound(&self) -> isize {
+        let inst = &self.instance;
+        (0..inst.n_resources)
+            .filter(|r| inst.capacity[*r] > 0)
+            .map(|r| {
+                let energy = (0..inst.n_jobs).filter(|i| !inst.optional.contains(*i)).map(|i| self.energy[i][r]).sum::<isize>();
+                (energy + inst.capacity[r] - 1).div_euclid(inst.capacity[r])
+            })
+            .max()
+            .unwrap_or(0)
+    }
+
+    /// Precedence edges that belong to a longest path in the precedence network
+    pub fn critical_edges(&self) -> Vec<(usize, usize)> {
+        let length = self.critical_path_length();
+        (0..self.instance.n_jobs)
+            .flat_map(|i| self.lags[i].iter().map(move |(j, lag)| (i, *j, *lag)))
+            .filter(|(i, j, lag)| self.head[*i] + lag + self.tail[*j] == length && self.head[*i] + self.tail[*i] == length)
+            .map(|(i, j, _)| (i, j))
+            .collect()
+    }
+
+    /// Pairs of jobs unrelated by the precedences whose compulsory parts overlap, along with
+    /// a resource whose capacity they exceed together. The compulsory part of a job is the
+    /// time during which it runs whenever it starts between its longest path from the start
+    /// and the latest start meeting the deadline, so any such pair makes the instance
+    /// infeasible. Only detects a few infeasible instances, and none without a deadline.
+    pub fn forced_conflicts(&self) -> Vec<(usize, usize, usize)> {
+        let inst = &self.instance;
+        let deadline = match self.deadline {
+            Some(deadline) if inst.optional.is_clear() => deadline,
+            _ => return vec![],
         };
-        let order = Self::toposort(&inst);
-        state.propagate(&order, &inst.successors_set, &inst.duration, &inst.consumption);
-        Self { instance: inst, initial: state, topo_order: order }
+
+        let end = deadline + inst.duration[self.sink];
+        let compulsory = (0..inst.n_jobs).map(|i| {
+            let from = end - self.tail[i]; // latest start
+            let to = self.head[i] + inst.duration[i];
+            (from < to && inst.segments[i].is_none()).then_some((from, to))
+        }).collect::<Vec<Option<(isize, isize)>>>();
+
+        let mut conflicts = vec![];
+        for i in 0..inst.n_jobs {
+            for j in (i + 1)..inst.n_jobs {
+                if self.ancestors[i].contains(j) || self.descendants[i].contains(j) {
+                    continue;
+                }
+                if let (Some((from_i, to_i)), Some((from_j, to_j))) = (compulsory[i], compulsory[j]) {
+                    if from_i.max(from_j) < to_i.min(to_j) {
+                        if let Some(r) = (0..inst.n_resources).find(|r| inst.shifts[*r].is_none() && inst.consumption[i][*r] + inst.consumption[j][*r] > inst.capacity[*r]) {
+                            conflicts.push((i, j, r));
+                        }
+                    }
+                }
+            }
+        }
+        conflicts
+    }
+
+    /// Imposes the start time of the given jobs. Their consumption is added to the initial
+    /// profiles and then ignored by the propagation, so that their earliest start can only
+    /// be delayed by their predecessors, which makes the partial schedule infeasible.
+    pub fn fix_starts(&mut self, fixed: &[(usize, isize)]) -> Result<(), String> {
+        // the other jobs can always be scheduled after the fixed ones
+        let fixed_end = fixed.iter()
+            .filter(|(job, _)| *job < self.instance.n_jobs)
+            .map(|(job, start)| start + self.instance.duration[*job])
+            .max()
+            .unwrap_or(0);
+        self.horizon += fixed_end.max(0);
+
+        let mut state = self.initial.clone();
+        state.earliest = self.release.clone();
+        state.profile = Self::initial_profiles(&self.instance, self.horizon);
+        for (job, start) in fixed.iter().copied() {
+            if job >= self.instance.n_jobs {
+                return Err(format!("job {} does not exist", job + 1));
+            }
+            if self.instance.flexible[job].is_some() {
+                return Err(format!("job {} has a flexible requirement and cannot be fixed", job + 1));
+            }
+            if self.instance.optional.contains(job) {
+                return Err(format!("job {} is optional and cannot be fixed", job + 1));
+            }
+            if start < 0 {
+                return Err(format!("job {} cannot start before time 0", job + 1));
+            }
+
+            state.add_job_consumption(start, self.instance.duration[job], &self.instance.consumption[job], &self.instance.segments[job]);
+            state.earliest[job] = start;
+            self.fixed[job] = Some(start);
+            self.consumption[job] = vec![0; self.instance.n_resources];
+            self.segments[job] = None;
+            self.energy[job] = vec![0; self.instance.n_resources];
+        }
+
+        for (r, profile) in state.profile.iter().enumerate() {
+            if let Some(step) = profile.steps.iter().find(|s| s.rem_capacity < 0) {
+                return Err(format!("fixed jobs exceed the capacity of resource {} at time {}", r + 1, step.start));
+            }
+        }
+
+        state.propagate(&self.topo_order, &self.propagated_lags, &self.instance.duration, &self.consumption, &self.segments);
+        if state.dead_end {
+            return Err("some jobs cannot fit in the profiles around the fixed jobs".to_string());
+        }
+        for (job, start) in fixed.iter().copied() {
+            if state.earliest[job] > start {
+                return Err(format!("job {} cannot start before {} due to its predecessors", job + 1, state.earliest[job]));
+            }
+        }
+
+        self.initial = state;
+        self.competitors = Self::competitors(&self.instance);
+        Ok(())
+    }
+
+    /// Checks whether all the fixed jobs start at their imposed time in the given schedule
+    pub fn respects_fixed(&self, starts: &[isize]) -> bool {
+        self.fixed.iter().enumerate().all(|(i, start)| start.map_or(true, |start| starts[i] == start))
+    }
+
+    /// Checks whether a fixed job has been delayed beyond its start time in an exact state
+    fn violates_fixed(&self, state: &State) -> bool {
+        state.maybe_done.is_none() && self.fixed.iter().enumerate().any(|(i, start)| match start {
+            Some(start) => !state.done.contains(i) && state.origin + state.earliest[i] > *start,
+            None => false,
+        })
+    }
+
+    /// Checks whether the sink cannot meet the deadline anymore in an exact state
+    fn misses_deadline(&self, state: &State) -> bool {
+        match self.deadline {
+            Some(deadline) => state.maybe_done.is_none() && state.origin + state.earliest[self.sink] > deadline,
+            None => false,
+        }
     }
 }
 
@@ -38,7 +328,7 @@ impl Problem for Rcpsp {
     type State = State;
 
     fn nb_variables(&self) -> usize {
-        self.instance.n_jobs
+        self.instance.n_jobs - self.initial.depth
     }
 
     fn initial_state(&self) -> State {
@@ -46,43 +336,85 @@ impl Problem for Rcpsp {
     }
 
     fn initial_value(&self) -> isize {
-        - self.initial.earliest[self.instance.n_jobs - 1]
+        self.objective.initial(&self.instance, self.sink, &self.initial)
     }
 
     fn for_each_in_domain(&self, variable: Variable, state: &Self::State, f: &mut dyn DecisionCallback)
     {
-        if state.done.count_ones(..) == state.depth { // must only schedule jobs that are not done
-            for i in 0..self.instance.n_jobs {
-                if !state.done.contains(i) && &self.instance.predecessors[i] & &state.done == self.instance.predecessors[i] {
-                    f.apply(Decision { variable, value: i as isize })
-                }
+        for i in self.feasible_decisions(state) {
+            for choice in 0..self.instance.nb_choices(i) {
+                f.apply(Decision { variable, value: self.encode(i, choice) })
             }
-        } else if let Some(maybe) = &state.maybe_done { // can schedule jobs that are maybe done
-            let maybe_done = &state.done | &maybe;
-            for i in 0..self.instance.n_jobs {
-                if !state.done.contains(i) && &self.instance.predecessors[i] & &maybe_done == self.instance.predecessors[i] {
-                    f.apply(Decision { variable, value: i as isize })
-                }
+            if self.instance.optional.contains(i) {
+                f.apply(Decision { variable, value: self.encode_drop(i) })
             }
         }
     }
 
     fn combined_transition(&self, state: &State, d: Decision) -> (State, isize) {
-        let d = d.value as usize;
+        let (d, choice) = self.decode(d.value);
+        self.transitions.fetch_add(1, Ordering::Relaxed);
+        if let Some(count) = self.transitions_per_depth.get(state.depth) {
+            count.fetch_add(1, Ordering::Relaxed);
+        }
 
         let mut successor = state.clone();
         successor.depth = state.depth + 1;
         successor.done.insert(d);
-        successor.add_consumption(state.earliest[d], self.instance.duration[d], &self.instance.consumption[d]);
-        successor.propagate(&self.topo_order, &self.instance.successors_set, &self.instance.duration, &self.instance.consumption);
 
-        let delta = successor.earliest[self.instance.n_jobs - 1] - state.earliest[self.instance.n_jobs - 1];
+        let choice = match choice {
+            Some(choice) => choice,
+            None => { // dropped, the job consumes nothing and does not delay its successors
+                successor.earliest[d] = 0;
+                successor.forward_to_earliest();
+                let cost = self.objective.drop_cost(&self.instance, d)
+                    + self.objective.forward_cost(&self.instance, &successor, successor.origin - state.origin);
+                if let Some(trace) = &self.trace {
+                    trace.log(&format!("[trace] depth {}: job {} is dropped (delta {})", state.depth, d + 1, self.objective.value(cost)));
+                }
+                return (successor, cost);
+            },
+        };
+
+        let start = match self.start_with_choice(state, d, choice) {
+            Some(start) => start,
+            None => {
+                successor.dead_end = true;
+                return (successor, 0);
+            },
+        };
+        successor.add_job_consumption(start, self.instance.duration[d], &self.chosen_consumption(d, choice), &self.segments[d]);
+        for (j, lag) in self.lags[d].iter().copied() {
+            if !successor.done.contains(j) {
+                successor.earliest[j] = successor.earliest[j].max(start + lag);
+            }
+        }
+        // merged states are not fully propagated, and the lags of the optional jobs are not propagated
+        if self.full_propagation || state.maybe_done.is_some() || self.instance.optional.contains(d) {
+            successor.propagate(&self.topo_order, &self.propagated_lags, &self.instance.duration, &self.consumption, &self.segments);
+        } else {
+            successor.propagate_from(d, &self.topo_order, &self.propagated_lags, &self.competitors[d], &self.instance.duration, &self.consumption, &self.segments);
+        }
+
+        let mut cost = self.objective.node_cost(&self.instance, self.sink, state, &successor, d, start);
+
+        // the value of the other objectives depends on the whole path, which is not known here
+        if let (Some(incumbent), Objective::Makespan) = (&self.incumbent, self.objective) {
+            if successor.is_complete(self.instance.n_jobs) && successor.maybe_done.is_none() {
+                incumbent.report(state.origin + start);
+            }
+        }
 
         successor.earliest[d] = 0; // clear estimation of the job scheduled
 
         successor.forward_to_earliest();
+        cost += self.objective.forward_cost(&self.instance, &successor, successor.origin - state.origin);
+
+        if let Some(trace) = &self.trace {
+            trace.log(&format!("[trace] depth {}: job {} starts at {} (delta {})", state.depth, d + 1, start, self.objective.value(cost)));
+        }
 
-        (successor, - delta)
+        (successor, cost)
     }
 
     fn next_variable(&self, depth: usize, _: &mut dyn Iterator<Item = &Self::State>)
@@ -104,26 +436,1292 @@ impl Problem for Rcpsp {
 }
 
 impl Rcpsp {
-    fn toposort(instance: &RcpspInstance) -> Vec<usize> {
-        let mut predecessors = vec![];
+    /// Computes the start time of each job when scheduling them in the order given by the decisions.
+    /// The jobs dropped are given the earliest start they had when they were dropped.
+    pub fn schedule(&self, decisions: &[Decision]) -> Vec<isize> {
+        let mut decisions = decisions.to_vec();
+        decisions.sort_unstable_by_key(|d| d.variable.id());
+
+        let mut state = self.initial.clone();
+        let mut starts = vec![0; self.instance.n_jobs];
+        for d in decisions.iter() {
+            match self.decode(d.value) {
+                (d, Some(choice)) => starts[d] = self.schedule_job(&mut state, d, choice),
+                (d, None) => {
+                    state.done.insert(d);
+                    starts[d] = state.earliest[d];
+                },
+            }
+        }
+        starts
+    }
+
+    /// Optional jobs dropped by the given decisions
+    pub fn dropped(&self, decisions: &[Decision]) -> Vec<usize> {
+        let mut dropped = decisions.iter()
+            .map(|d| self.decode(d.value))
+            .filter(|(_, choice)| choice.is_none())
+            .map(|(job, _)| job)
+            .collect::<Vec<usize>>();
+        dropped.sort_unstable();
+        dropped
+    }
+
+    /// Choice of resources that lets the given job start the earliest in the given state,
+    /// or None if no choice lets it start before the horizon
+    pub fn earliest_choice(&self, state: &State, job: usize) -> Option<usize> {
+        (0..self.instance.nb_choices(job))
+            .filter_map(|choice| self.start_with_choice(state, job, choice).map(|start| (start, choice)))
+            .min()
+            .map(|(_, choice)| choice)
+    }
+
+    /// Schedules the given job at its earliest start in a state that is not moved
+    /// forward in time, so that its earliest times remain absolute, and returns its start
+    pub fn schedule_job(&self, state: &mut State, job: usize, choice: usize) -> isize {
+        let start = match self.start_with_choice(state, job, choice) {
+            Some(start) => start,
+            None => {
+                state.dead_end = true;
+                return state.earliest[job];
+            },
+        };
+        state.done.insert(job);
+        state.add_job_consumption(start, self.instance.duration[job], &self.chosen_consumption(job, choice), &self.segments[job]);
+        for (j, lag) in self.lags[job].iter().copied() {
+            if !state.done.contains(j) {
+                state.earliest[j] = state.earliest[j].max(start + lag);
+            }
+        }
+        state.propagate(&self.topo_order, &self.propagated_lags, &self.instance.duration, &self.consumption, &self.segments);
+        start
+    }
+
+    /// Value of the decision scheduling the given job with the given choice of resources
+    pub fn encode(&self, job: usize, choice: usize) -> isize {
+        (job + choice * self.instance.n_jobs) as isize
+    }
+
+    /// Value of the decision dropping the given optional job, which is negative so that
+    /// it cannot be confused with the choices of resources
+    pub fn encode_drop(&self, job: usize) -> isize {
+        - (job as isize) - 1
+    }
+
+    /// Job and choice of resources of the given decision value, the choice being None
+    /// when the job is dropped
+    pub fn decode(&self, value: isize) -> (usize, Option<usize>) {
+        if value < 0 {
+            return ((- value - 1) as usize, None);
+        }
+        let value = value as usize;
+        (value % self.instance.n_jobs, Some(value / self.instance.n_jobs))
+    }
+
+    /// Start of the given job in the given state. The earliest times only account for the
+    /// fixed part of the consumption of the jobs, so the start of a job with a flexible
+    /// requirement depends on the resource chosen, and may not exist in the profiles.
+    fn start_with_choice(&self, state: &State, job: usize, choice: usize) -> Option<isize> {
+        match &self.instance.flexible[job] {
+            Some(_) => state.get_job_earliest_start(state.earliest[job], self.instance.duration[job], &self.chosen_consumption(job, choice), &None),
+            None => Some(state.earliest[job]),
+        }
+    }
+
+    /// Consumption of the given job with the given choice of resources, which is zero for
+    /// the fixed jobs since their consumption is already in the initial profiles
+    fn chosen_consumption(&self, job: usize, choice: usize) -> Cow<'_, Vec<isize>> {
+        match &self.instance.flexible[job] {
+            Some(_) => Cow::Owned(self.instance.chosen_consumption(job, choice)),
+            None => Cow::Borrowed(&self.consumption[job]),
+        }
+    }
+
+    /// Enumerates the distinct schedules with the given makespan that can be built by scheduling
+    /// the jobs at their earliest start, stopping after max_count schedules have been found
+    pub fn all_optima(&self, makespan: isize, max_count: usize) -> Vec<Vec<isize>> {
+        let mut search = OptimaSearch {
+            pb: self,
+            makespan,
+            max_count,
+            starts: vec![0; self.instance.n_jobs],
+            found: HashSet::new(),
+        };
+        search.explore(&self.initial, 0);
+
+        let mut optima = search.found.into_iter().collect::<Vec<Vec<isize>>>();
+        optima.sort_unstable();
+        optima
+    }
+
+    /// Solves the subproblem of scheduling the remaining jobs from the given exact state,
+    /// with the given configuration and cutoff. The value found is relative to the origin
+    /// of the state, and the decisions are numbered from 0.
+    pub fn solve_from(&self, start: State, config: &SolverConfig, cutoff: &(dyn Cutoff + Send + Sync)) -> Result<Completion, String> {
+        self.check_state(&start)?;
+
+        let mut sub = self.clone();
+        sub.initial = start;
+        sub.initial.propagate(&sub.topo_order, &sub.propagated_lags, &sub.instance.duration, &sub.consumption, &sub.segments);
+
+        Ok(solve_with_cutoff(&sub, config, cutoff).0)
+    }
+
+    /// Rebuilds the initial state after the instance has been modified, computing the
+    /// horizon, the energy release, the profiles and the bound again before imposing the
+    /// fixed starts
+    fn rebuild_initial(&mut self) -> Result<(), String> {
+        let fixed = (0..self.instance.n_jobs)
+            .filter_map(|i| self.fixed[i].map(|start| (i, start)))
+            .collect::<Vec<(usize, isize)>>();
+        self.horizon = self.instance.horizon.map_or_else(|| Self::horizon(&self.instance), |h| h.max(1));
+        self.release = Self::energy_release(&self.instance, &self.ancestors);
+        self.fix_starts(&fixed)?;
+        self.bound = Arc::new(CriticalPathBound::new(self));
+        Ok(())
+    }
+
+    /// Solves the problem with the given configuration and cutoff after imposing the start
+    /// time of the given jobs, and returns the best value found, or None if no schedule was
+    /// found or if the placements are inconsistent
+    pub fn evaluate_with_fixed(&self, fixed: &[(usize, isize)], config: &SolverConfig, cutoff: &(dyn Cutoff + Send + Sync)) -> Option<isize> {
+        let mut constrained = self.clone();
+        constrained.fix_starts(fixed).ok()?;
+
+        let start = constrained.initial.clone();
+        let Completion { best_value, .. } = constrained.solve_from(start, config, cutoff).ok()?;
+        best_value.map(|value| constrained.objective.value(value))
+    }
+
+    /// Solves the problem with the given configuration and cutoff after removing the limit on
+    /// the capacity of the given resource, whose capacity is raised to the total consumption
+    /// of all the jobs. Returns the best makespan found, if any.
+    pub fn makespan_without_resource(&self, r: usize, config: &SolverConfig, cutoff: &(dyn Cutoff + Send + Sync)) -> Option<isize> {
+        let inst = &self.instance;
+        let total = (0..inst.n_jobs)
+            .map(|i| inst.consumption[i][r] + inst.flexible[i].as_ref().map_or(0, |f| f.amount))
+            .sum::<isize>()
+            .max(1);
+
+        let mut relaxed = self.clone();
+        relaxed.objective = Objective::Makespan;
+        relaxed.instance.capacity[r] = total;
+        relaxed.instance.shifts[r] = None;
+        relaxed.instance.available_from[r] = 0;
+        relaxed.rebuild_initial().ok()?;
+
+        let start = relaxed.initial.clone();
+        let Completion { best_value, .. } = relaxed.solve_from(start, config, cutoff).ok()?;
+        best_value.map(|value| relaxed.initial.origin + relaxed.objective.value(value))
+    }
+
+    /// Checks that the given state is an exact state of this problem
+    fn check_state(&self, state: &State) -> Result<(), String> {
+        let n_jobs = self.instance.n_jobs;
+        if state.done.len() != n_jobs || state.earliest.len() != n_jobs {
+            return Err(format!("state has {} jobs instead of {}", state.done.len(), n_jobs));
+        }
+        if state.profile.len() != self.instance.n_resources {
+            return Err(format!("state has {} resources instead of {}", state.profile.len(), self.instance.n_resources));
+        }
+        if state.maybe_done.is_some() {
+            return Err("state is the result of a merge".to_string());
+        }
+        if state.dead_end {
+            return Err("state has no successor".to_string());
+        }
+        if state.done.count_ones(..) != state.depth {
+            return Err(format!("state has {} jobs done at depth {}", state.done.count_ones(..), state.depth));
+        }
+        if let Some(i) = state.done.ones().find(|i| !self.ancestors[*i].is_subset(&state.done)) {
+            return Err(format!("job {} is done before some of its predecessors", i + 1));
+        }
+        for (r, profile) in state.profile.iter().enumerate() {
+            profile.check_invariants().map_err(|e| format!("resource {}: {}", r + 1, e))?;
+        }
+        Ok(())
+    }
+
+    /// Moves each job of the given schedule to its earliest start given its predecessors and
+    /// the consumption of all the other jobs, until no job can be moved. Since the jobs are
+    /// only moved earlier, the precedences with their successors remain satisfied. The
+    /// schedule is left unchanged when some jobs have a flexible requirement or are optional,
+    /// since the resources chosen and the jobs dropped are not known.
+    pub fn left_shift(&self, starts: &mut [isize]) {
+        let inst = &self.instance;
+        if inst.flexible.iter().any(|f| f.is_some()) || !inst.optional.is_clear() {
+            return;
+        }
+
+        let mut moved = true;
+        while moved {
+            moved = false;
+            for i in self.topo_order.iter().copied() {
+                if self.fixed[i].is_some() {
+                    continue;
+                }
+
+                // the initial profiles already hold the consumption of the fixed jobs
+                let mut state = self.initial.clone();
+                for j in (0..inst.n_jobs).filter(|j| *j != i) {
+                    state.add_job_consumption(starts[j], inst.duration[j], &self.consumption[j], &self.segments[j]);
+                }
+                let current = &*starts;
+                let ready = self.ancestors[i].ones()
+                    .flat_map(|p| self.lags[p].iter().filter(|(j, _)| *j == i).map(move |(_, lag)| current[p] + lag))
+                    .max()
+                    .unwrap_or(0)
+                    .max(0);
+
+                if let Some(start) = state.get_job_earliest_start(ready, inst.duration[i], &self.consumption[i], &self.segments[i]) {
+                    if start < starts[i] {
+                        starts[i] = start;
+                        moved = true;
+                    }
+                }
+            }
+        }
+
+        debug_assert!(self.is_feasible(starts), "left shift produced an infeasible schedule");
+    }
+
+    /// Checks whether the given schedule satisfies the precedences and the capacities
+    pub fn is_feasible(&self, starts: &[isize]) -> bool {
+        let inst = &self.instance;
+        let precedences = (0..inst.n_jobs).all(|i| self.lags[i].iter().all(|(j, lag)| starts[*j] >= starts[i] + lag));
+
+        let mut state = self.initial.clone();
+        for i in 0..inst.n_jobs {
+            state.add_job_consumption(starts[i], inst.duration[i], &self.consumption[i], &self.segments[i]);
+        }
+        let capacities = state.profile.iter().all(|p| p.steps.iter().all(|s| s.rem_capacity >= 0));
+
+        precedences && capacities && starts.iter().all(|s| *s >= 0)
+    }
+
+    /// Returns the jobs that can be scheduled next from the given state
+    pub fn feasible_decisions(&self, state: &State) -> Vec<usize> {
+        if state.dead_end || self.violates_fixed(state) || self.misses_deadline(state) { // dead end
+            return vec![];
+        }
+
+        if state.done.count_ones(..) == state.depth { // must only schedule jobs that are not done
+            let eligible = (0..self.instance.n_jobs)
+                .filter(|i| !state.done.contains(*i) && &self.instance.predecessors[*i] & &state.done == self.instance.predecessors[*i]);
+            let frontier = if self.non_delay {
+                eligible.clone().map(|i| state.earliest[i]).min().unwrap_or(0)
+            } else {
+                isize::MIN
+            };
+            let mut decisions = eligible.filter(|i| !self.non_delay || state.earliest[*i] == frontier).collect::<Vec<usize>>();
+            if let Some(beam) = self.beam {
+                decisions.sort_by_key(|i| (state.earliest[*i], *i));
+                decisions.truncate(beam);
+            }
+            decisions
+        } else if let Some(maybe) = &state.maybe_done { // can schedule jobs that are maybe done
+            let maybe_done = &state.done | maybe;
+            (0..self.instance.n_jobs)
+                .filter(|i| !state.done.contains(*i) && &self.instance.predecessors[*i] & &maybe_done == self.instance.predecessors[*i])
+                .collect()
+        } else {
+            vec![]
+        }
+    }
+
+    /// Jobs that can be scheduled next from the given state, with the time at which each
+    /// one would start with the choice of resources that lets it start the earliest
+    pub fn next_jobs(&self, state: &State) -> Vec<(usize, isize)> {
+        self.feasible_decisions(state).into_iter()
+            .filter_map(|i| {
+                let choice = self.earliest_choice(state, i)?;
+                self.start_with_choice(state, i, choice).map(|start| (i, state.origin + start))
+            })
+            .collect()
+    }
+
+    /// Schedules the given job from the given state with the choice of resources that lets
+    /// it start the earliest, and returns the next state along with the cost of the transition
+    pub fn step(&self, state: &State, job: usize) -> (State, isize) {
+        let choice = self.earliest_choice(state, job).unwrap_or(0);
+        self.combined_transition(state, Decision { variable: Variable(state.depth), value: self.encode(job, choice) })
+    }
+
+    /// Checks whether the given permutation of the jobs puts each job after all its predecessors
+    pub fn is_topological_order(&self, order: &[usize]) -> bool {
+        if order.len() != self.instance.n_jobs {
+            return false;
+        }
+
+        let mut position = vec![usize::MAX; self.instance.n_jobs];
+        for (p, i) in order.iter().copied().enumerate() {
+            if i >= self.instance.n_jobs || position[i] != usize::MAX {
+                return false;
+            }
+            position[i] = p;
+        }
+
+        (0..self.instance.n_jobs).all(|i| self.instance.successors_set[i].iter().all(|j| position[i] < position[*j]))
+    }
+
+    /// Computes the total consumption of the given resource at the given time in a schedule
+    pub fn profile_at(&self, starts: &[isize], resource: usize, time: isize) -> isize {
+        (0..self.instance.n_jobs).map(|i| {
+            if time < starts[i] || time >= starts[i] + self.instance.duration[i] {
+                return 0;
+            }
+
+            match &self.instance.segments[i] {
+                Some(segments) => {
+                    let mut end = starts[i];
+                    for segment in segments.iter() {
+                        end += segment.duration;
+                        if time < end {
+                            return segment.consumption[resource];
+                        }
+                    }
+                    0
+                },
+                None => self.instance.consumption[i][resource],
+            }
+        }).sum()
+    }
+
+    /// Times at which the total consumption of the given resource changes in a schedule,
+    /// along with the consumption from that time on, ending with a zero consumption
+    pub fn usage_breakpoints(&self, starts: &[isize], resource: usize) -> Vec<(isize, isize)> {
+        let mut times = vec![];
+        for i in 0..self.instance.n_jobs {
+            times.push(starts[i]);
+            times.push(starts[i] + self.instance.duration[i]);
+            if let Some(segments) = &self.instance.segments[i] {
+                let mut end = starts[i];
+                for segment in segments.iter() {
+                    end += segment.duration;
+                    times.push(end);
+                }
+            }
+        }
+        times.sort_unstable();
+        times.dedup();
+
+        let mut breakpoints: Vec<(isize, isize)> = vec![];
+        for time in times {
+            let usage = self.profile_at(starts, resource, time);
+            if breakpoints.last().map_or(true, |(_, last)| *last != usage) {
+                breakpoints.push((time, usage));
+            }
+        }
+        breakpoints
+    }
+
+    /// Total cost of the resources used by a schedule, i.e. the area under the usage of each
+    /// resource over time multiplied by its cost
+    pub fn resource_cost(&self, starts: &[isize]) -> isize {
+        (0..self.instance.n_resources)
+            .filter(|r| self.instance.resource_cost[*r] != 0)
+            .map(|r| {
+                let breakpoints = self.usage_breakpoints(starts, r);
+                let area = breakpoints.windows(2).map(|w| (w[1].0 - w[0].0) * w[0].1).sum::<isize>();
+                area * self.instance.resource_cost[r]
+            })
+            .sum()
+    }
+
+    /// Computes the total float of each job in the given schedule, i.e. the delay
+    /// that can be applied to its start without increasing the makespan when only
+    /// the precedence constraints are considered
+    pub fn slacks(&self, starts: &[isize]) -> Vec<isize> {
+        let makespan = (0..self.instance.n_jobs)
+            .map(|i| starts[i] + self.instance.duration[i])
+            .max()
+            .unwrap_or(0);
+
+        let mut latest = vec![0; self.instance.n_jobs];
+        for i in self.topo_order.iter().copied().rev() {
+            latest[i] = makespan - self.instance.duration[i];
+            for (j, lag) in self.lags[i].iter().copied() {
+                latest[i] = latest[i].min(latest[j] - lag);
+            }
+        }
+
+        (0..self.instance.n_jobs).map(|i| latest[i] - starts[i]).collect()
+    }
+
+    /// Sum of the time needed by each job before its successors can start, so that
+    /// scheduling the jobs one after the other always fits in the horizon. With periodic
+    /// capacities, each job may additionally wait for a full cycle of all the patterns.
+    /// The horizon is positive so that the profiles are never empty.
+    fn horizon(instance: &RcpspInstance) -> isize {
+        let sequential = (0..instance.n_jobs).map(|i| {
+            let lag = instance.time_lags.iter()
+                .filter(|((j, _), _)| *j == i)
+                .map(|(_, lag)| *lag)
+                .max()
+                .unwrap_or(0);
+            instance.duration[i].max(lag)
+        }).sum::<isize>();
+
+        let cycle = instance.shifts.iter().flatten()
+            .map(|s| s.cycle())
+            .fold(None, |lcm: Option<isize>, c| Some(lcm.map_or(c, |l| l / gcd(l, c) * c)));
+        let ready = instance.available_from.iter().copied().max().unwrap_or(0);
+        let horizon = match cycle {
+            Some(cycle) => ready + sequential + instance.n_jobs as isize * cycle,
+            None => ready + sequential,
+        };
+        horizon.max(1)
+    }
+
+    /// Profiles of the resources before any job is scheduled, expanding the periodic
+    /// capacities up to the horizon and without capacity before each resource is available
+    fn initial_profiles(instance: &RcpspInstance, horizon: isize) -> Vec<ConsumptionProfile> {
+        let mut profiles = vec![];
+        for r in 0..instance.n_resources {
+            let mut steps = VecDeque::new();
+            match &instance.shifts[r] {
+                Some(shift) => {
+                    let mut start = 0;
+                    for rem_capacity in shift.capacity.iter().copied().cycle() {
+                        if start >= horizon && !steps.is_empty() {
+                            break;
+                        }
+                        let end = (start + shift.period).min(horizon);
+                        match steps.back_mut() {
+                            Some(ConsumptionStep { end: last_end, rem_capacity: last, .. }) if *last == rem_capacity => *last_end = end,
+                            _ => steps.push_back(ConsumptionStep { start, end, rem_capacity }),
+                        }
+                        start = end;
+                    }
+                },
+                None => steps.push_back(ConsumptionStep { start: 0, end: horizon, rem_capacity: instance.capacity[r] }),
+            }
+
+            let ready = instance.available_from[r].min(horizon);
+            if ready > 0 {
+                while steps.front().map_or(false, |s| s.end <= ready) {
+                    steps.pop_front();
+                }
+                if let Some(first) = steps.front_mut() {
+                    first.start = ready;
+                }
+                steps.push_front(ConsumptionStep { start: 0, end: ready, rem_capacity: 0 });
+            }
+
+            let mut profile = ConsumptionProfile { steps, capacity: instance.capacity[r] };
+            profile.compact();
+            profiles.push(profile);
+        }
+        profiles
+    }
+
+    /// Lower bound on the start of each job given by the energy of its ancestors, which must
+    /// all be done before it starts and cannot use more than the capacity of each resource.
+    /// It is only valid when the jobs start after the end of their predecessors and cannot
+    /// be dropped, so the bound is zero otherwise.
+    fn energy_release(instance: &RcpspInstance, ancestors: &[FixedBitSet]) -> Vec<isize> {
+        let mut release = vec![0; instance.n_jobs];
+        if !instance.time_lags.is_empty() || !instance.optional.is_clear() {
+            return release;
+        }
+
+        for r in 0..instance.n_resources {
+            let capacity = match &instance.shifts[r] {
+                Some(shift) => shift.capacity.iter().copied().max().unwrap_or(0).max(instance.capacity[r]),
+                None => instance.capacity[r],
+            };
+            if capacity <= 0 {
+                continue;
+            }
+            for j in 0..instance.n_jobs {
+                let energy = ancestors[j].ones().map(|i| instance.energy(i, r)).sum::<isize>();
+                release[j] = release[j].max((energy + capacity - 1) / capacity);
+            }
+        }
+        release
+    }
+
+    fn longest_paths(instance: &RcpspInstance, order: &[usize], lags: &[Vec<(usize, isize)>]) -> (Vec<isize>, Vec<isize>) {
+        let mut head = vec![0; instance.n_jobs];
+        for i in order.iter().copied() {
+            for (j, lag) in lags[i].iter().copied() {
+                head[j] = head[j].max(head[i] + lag);
+            }
+        }
+
+        let mut tail = instance.duration.clone();
+        for i in order.iter().copied().rev() {
+            for (j, lag) in lags[i].iter().copied() {
+                tail[i] = tail[i].max(lag + tail[j]);
+            }
+        }
+
+        (head, tail)
+    }
+
+    fn competitors(instance: &RcpspInstance) -> Vec<FixedBitSet> {
+        let mut competitors = vec![FixedBitSet::with_capacity(instance.n_jobs); instance.n_jobs];
         for i in 0..instance.n_jobs {
-            predecessors.push(instance.predecessors_set[i].clone());
+            for j in 0..instance.n_jobs {
+                let uses = |k: usize, r: usize| instance.consumption[k][r] > 0
+                    || instance.flexible[k].as_ref().map_or(false, |f| f.resources.contains(&r));
+                if i != j && instance.duration[i] > 0 && instance.duration[j] > 0
+                    && (0..instance.n_resources).any(|r| uses(i, r) && uses(j, r)) {
+                    competitors[i].insert(j);
+                }
+            }
+        }
+        competitors
+    }
+}
+
+struct OptimaSearch<'a> {
+    pb: &'a Rcpsp,
+    makespan: isize,
+    max_count: usize,
+    starts: Vec<isize>,
+    found: HashSet<Vec<isize>>,
+}
+
+impl OptimaSearch<'_> {
+    /// Explores the schedules where the jobs are scheduled in non-decreasing order of start time,
+    /// which is enough to build any schedule in which each job starts as early as possible
+    fn explore(&mut self, state: &State, last_start: isize) {
+        let n_jobs = self.pb.instance.n_jobs;
+        if self.found.len() >= self.max_count {
+            return;
+        }
+        if state.dead_end {
+            return;
+        }
+        if state.done.count_ones(..) == n_jobs {
+            self.found.insert(self.starts.clone());
+            return;
+        }
+        if self.pb.bound.lower_bound(state, &self.pb.instance) > self.makespan {
+            return;
+        }
+
+        for i in 0..n_jobs {
+            if state.done.contains(i) || state.earliest[i] < last_start
+                || &self.pb.instance.predecessors[i] & &state.done != self.pb.instance.predecessors[i] {
+                continue;
+            }
+
+            for choice in 0..self.pb.instance.nb_choices(i) {
+                let mut successor = state.clone();
+                self.starts[i] = self.pb.schedule_job(&mut successor, i, choice);
+                self.explore(&successor, self.starts[i]);
+            }
+        }
+    }
+}
+
+fn gcd(a: isize, b: isize) -> isize {
+    if b == 0 { a } else { gcd(b, a % b) }
+}
+
+#[cfg(test)]
+pub mod tests {
+    use std::{fs::{File, read_dir}, io::BufReader, path::Path, sync::Mutex, time::Instant};
+
+    use ddo::{Relaxation, Solver, MaxUB, NoDupFringe, ParBarrierSolverFc, NbUnassignedWitdh, NoCutoff};
+
+    use crate::{relax::RcpspRelax, heuristics::RcpspRanking, bounds::EnergyBound, config::{WidthChoice, solve_problem}};
+
+    use super::*;
+
+    /// Optimal makespan of the given problem, proven by the solver
+    pub fn solve(problem: &Rcpsp) -> isize {
+        let (completion, _) = solve_problem(problem, &SolverConfig::default());
+        assert!(completion.is_exact);
+        - completion.best_value.unwrap()
+    }
+
+    /// Configuration of the sub-solves, narrow and single-threaded
+    fn narrow() -> SolverConfig {
+        SolverConfig { width: WidthChoice::Fixed(10), threads: Some(1), ..SolverConfig::default() }
+    }
+
+    fn parse(content: &str) -> RcpspInstance {
+        RcpspInstance::try_from(BufReader::new(content.as_bytes())).unwrap()
+    }
+
+    fn domain(problem: &Rcpsp, state: &State) -> Vec<Decision> {
+        let mut decisions = vec![];
+        problem.for_each_in_domain(Variable(state.depth), state, &mut |d: Decision| decisions.push(d));
+        decisions
+    }
+
+    #[test]
+    fn incremental_propagation_matches_the_full_propagation() {
+        // the jobs share the two resources in different ways, so that each transition delays some others
+        let instance = parse("7 2\n2 1\n0 0 0 3 2 3 4\n2 1 1 1 5\n3 1 0 1 6\n2 0 1 1 6\n1 1 1 1 7\n2 2 0 1 7\n0 0 0 0\n");
+        let incremental = Rcpsp::new(instance.clone());
+        let mut full = Rcpsp::new(instance);
+        full.full_propagation = true;
+
+        // every order of the jobs is explored, comparing the states after each transition
+        let mut stack = vec![(incremental.initial_state(), full.initial_state())];
+        while let Some((a, b)) = stack.pop() {
+            assert_eq!(a, b);
+            for d in domain(&incremental, &a) {
+                stack.push((incremental.combined_transition(&a, d).0, full.combined_transition(&b, d).0));
+            }
+        }
+    }
+
+    #[test]
+    fn every_job_of_a_chain_is_critical() {
+        let problem = Rcpsp::new(parse("4 1\n1\n0 0 1 2\n2 1 1 3\n3 1 1 4\n0 0 0\n"));
+        assert_eq!(problem.slacks(&[0, 0, 2, 5]), vec![0, 0, 0, 0]);
+    }
+
+    #[test]
+    fn shorter_parallel_job_has_slack() {
+        // jobs 2 and 3 run in parallel between the source and the sink
+        let problem = Rcpsp::new(parse("4 1\n2\n0 0 2 2 3\n3 1 1 4\n1 1 1 4\n0 0 0\n"));
+        assert_eq!(problem.slacks(&[0, 0, 0, 3]), vec![0, 0, 2, 0]);
+        // delaying the shorter job consumes its slack
+        assert_eq!(problem.slacks(&[0, 0, 1, 3]), vec![0, 0, 1, 0]);
+    }
+
+    #[test]
+    fn traced_jobs_follow_the_precedences() {
+        let lines = Arc::new(Mutex::new(vec![]));
+        let captured = lines.clone();
+        let mut problem = Rcpsp::new(parse("7 2\n2 1\n0 0 0 3 2 3 4\n2 1 1 1 5\n3 1 0 1 6\n2 0 1 1 6\n1 1 1 1 7\n2 2 0 1 7\n0 0 0 0\n"));
+        problem.trace = Some(LogSink(Arc::new(move |line: &str| captured.lock().unwrap().push(line.to_string()))));
+
+        let mut state = problem.initial_state();
+        while state.depth < problem.nb_variables() {
+            let decision = domain(&problem, &state)[0];
+            state = problem.combined_transition(&state, decision).0;
+        }
+
+        let jobs = lines.lock().unwrap().iter()
+            .map(|line| line.split("job ").nth(1).unwrap().split(' ').next().unwrap().parse::<usize>().unwrap() - 1)
+            .collect::<Vec<usize>>();
+        assert_eq!(jobs.len(), problem.instance.n_jobs);
+        for (position, job) in jobs.iter().enumerate() {
+            for predecessor in problem.instance.predecessors_set[*job].iter() {
+                assert!(jobs[..position].contains(predecessor), "job {} traced before its predecessor {}", job + 1, predecessor + 1);
+            }
+        }
+    }
+
+    #[test]
+    fn symmetric_jobs_give_one_optimum_per_order() {
+        // three identical jobs competing for a single unit of resource
+        let problem = Rcpsp::new(parse("5 1\n1\n0 0 3 2 3 4\n2 1 1 5\n2 1 1 5\n2 1 1 5\n0 0 0\n"));
+        let makespan = solve(&problem);
+        assert_eq!(makespan, 6);
+
+        let optima = problem.all_optima(makespan, 1000);
+        assert_eq!(optima.len(), 6);
+        for starts in optima.iter() {
+            let mut middle = starts[1..4].to_vec();
+            middle.sort_unstable();
+            assert_eq!(middle, vec![0, 2, 4]);
+            assert_eq!(starts[4], 6);
+        }
+
+        assert_eq!(problem.all_optima(makespan, 4).len(), 4);
+        assert!(problem.all_optima(makespan - 1, 1000).is_empty());
+    }
+
+    #[test]
+    fn fixed_jobs_keep_their_consumption_in_the_instance() {
+        let inst = RcpspInstance::new(vec![2, 3], vec![vec![1], vec![1]], vec![1], vec![]);
+        let job = 2; // shifted by the virtual source
+        for (start, makespan) in [(0, 5), (1, 6)] {
+            let mut problem = Rcpsp::new(inst.clone());
+            problem.fix_starts(&[(job, start)]).unwrap();
+            assert_eq!(problem.instance.consumption[job], vec![1]);
+            assert_eq!(solve(&problem), makespan);
+
+            // the energy of the fixed job is already in the profiles
+            problem.bound = Arc::new(EnergyBound::new(&problem));
+            assert_eq!(solve(&problem), makespan);
+        }
+    }
+
+    #[test]
+    fn closure_contains_the_transitive_precedences() {
+        // 1 -> 2 -> 4 -> 5 and 1 -> 3 -> 5, jobs 2 and 3 being unrelated
+        let problem = Rcpsp::new(parse("5 1\n1\n0 0 2 2 3\n1 1 1 4\n1 1 1 5\n1 1 1 5\n0 0 0\n"));
+        let ones = |sets: &Vec<FixedBitSet>, i: usize| sets[i].ones().collect::<Vec<usize>>();
+        assert_eq!(ones(&problem.descendants, 0), vec![1, 2, 3, 4]);
+        assert_eq!(ones(&problem.descendants, 1), vec![3, 4]);
+        assert_eq!(ones(&problem.descendants, 2), vec![4]);
+        assert_eq!(ones(&problem.descendants, 4), Vec::<usize>::new());
+        assert_eq!(ones(&problem.ancestors, 4), vec![0, 1, 2, 3]);
+        assert_eq!(ones(&problem.ancestors, 3), vec![0, 1]);
+        assert_eq!(ones(&problem.ancestors, 0), Vec::<usize>::new());
+        for i in 0..5 {
+            assert!(!problem.descendants[i].contains(i));
+            for j in problem.descendants[i].ones() {
+                assert!(problem.ancestors[j].contains(i));
+            }
         }
+    }
+
+    #[test]
+    fn isolated_job_is_ordered_and_competes_for_the_resource() {
+        // job 4 has neither predecessors nor successors but consumes the resource
+        let instance = parse("4 1\n1\n0 0 1 2\n2 1 1 3\n0 0 0\n3 1 0\n");
+        let mut sorted = instance.topological_order();
+        sorted.sort_unstable();
+        assert_eq!(sorted, vec![0, 1, 2, 3]);
+
+        assert_eq!(solve(&Rcpsp::new(instance)), 5);
+    }
+
+    #[test]
+    fn negative_lag_overlaps_the_jobs_within_the_capacity() {
+        // job 3 may start one period before job 2, which precedes it
+        let content = "4 1\n2\n0 0 1 2\n3 1 2 3 4\n2 1 1 4\n0 0 0\nlag 2 3 -1\n";
+        assert_eq!(solve(&Rcpsp::new(parse(content))), 3);
+        assert_eq!(solve(&Rcpsp::new(parse(&content.replacen("\n2\n", "\n1\n", 1)))), 5);
+    }
+
+    #[test]
+    fn chain_is_scheduled_up_to_the_end_of_the_profiles() {
+        // every schedule is serial and ends with the profiles
+        let problem = Rcpsp::new(RcpspInstance::new(vec![2, 3, 4, 0], vec![vec![1], vec![1], vec![1], vec![0]], vec![1], vec![(0, 1), (1, 2), (2, 3)]));
+        assert_eq!(problem.horizon, 9);
+
+        let mut state = problem.initial_state();
+        while let Some(decision) = domain(&problem, &state).first().copied() {
+            state = problem.combined_transition(&state, decision).0;
+            state.profile.iter().for_each(|p| assert_eq!(p.check_invariants(), Ok(())));
+        }
+        assert_eq!(state.depth, 4);
+        assert_eq!(solve(&problem), 9);
+    }
+
+    #[test]
+    fn non_delay_search_misses_the_schedule_that_waits() {
+        // starting job 2 at time 0 delays the chain 1 -> 3 -> 4, which it could run along
+        let mut problem = Rcpsp::new(parse("4 1\n2\n1 1 1 3\n3 1 0\n2 2 1 4\n3 1 0\n"));
+        assert_eq!(solve(&problem), 6);
+
+        problem.non_delay = true;
+        assert_eq!(solve(&problem), 8);
+    }
+
+    #[test]
+    fn profile_sums_the_jobs_running_at_each_time() {
+        // job 3 consumes 1 unit during its first period and 2 units during the next two
+        let problem = Rcpsp::new(parse("4 1\n4\n0 0 2 2 3\n2 2 1 4\n3 2 1 4\n0 0 0\nramp 3 2 1 1 2 2\n"));
+        let starts = [0, 0, 0, 3];
+        let profile = (-1..5).map(|t| problem.profile_at(&starts, 0, t)).collect::<Vec<isize>>();
+        assert_eq!(profile, vec![0, 3, 4, 2, 0, 0]);
+    }
+
+    #[test]
+    fn off_shift_periods_delay_the_jobs() {
+        // the resource is available during 3 periods out of 6, so the second job cannot
+        // run right after the first one
+        let content = "4 1\n1\n0 0 1 2\n2 1 1 3\n2 1 1 4\n0 0 0\n";
+        assert_eq!(solve(&Rcpsp::new(parse(content))), 4);
+        assert_eq!(solve(&Rcpsp::new(parse(&format!("{}shift 1 3 2 1 0\n", content)))), 8);
+    }
+
+    #[test]
+    fn deadline_below_the_optimum_makes_the_instance_infeasible() {
+        let mut problem = Rcpsp::new(parse("4 1\n1\n0 0 2 2 3\n1 1 1 4\n2 1 1 4\n0 0 0\n"));
+        problem.deadline = Some(3);
+        assert_eq!(solve(&problem), 3);
+
+        problem.deadline = Some(2);
+        let relaxation = RcpspRelax::new(&problem);
+        let ranking = RcpspRanking::default();
+        let width = NbUnassignedWitdh(problem.nb_variables());
+        let mut fringe = NoDupFringe::new(MaxUB::new(&ranking));
+        let mut solver = ParBarrierSolverFc::new(&problem, &relaxation, &ranking, &width, &NoCutoff, &mut fringe);
+        let completion = solver.maximize();
+        assert!(completion.is_exact);
+        assert_eq!(completion.best_value, None);
+    }
+
+    #[test]
+    fn parallel_limit_of_one_serialises_the_jobs() {
+        let content = "5 1\n3\n0 0 3 2 3 4\n1 1 1 5\n2 1 1 5\n3 1 1 5\n0 0 0\n";
+        assert_eq!(solve(&Rcpsp::new(parse(content))), 3);
+
+        let mut instance = parse(content);
+        instance.add_parallel_limit(2);
+        // the first job runs after the second one, along the third one
+        assert_eq!(solve(&Rcpsp::new(instance)), 3);
+
+        let mut instance = parse(content);
+        instance.add_parallel_limit(1);
+        assert_eq!(instance.consumption, vec![vec![0, 1], vec![1, 1], vec![1, 1], vec![1, 1], vec![0, 1]]);
+        assert_eq!(solve(&Rcpsp::new(instance)), 6);
+    }
 
-        let mut order = vec![];
-        let mut open = vec![0];
+    #[test]
+    fn prefix_cost_and_sub_solve_give_the_optimum() {
+        let problem = Rcpsp::new(parse("7 2\n2 1\n0 0 0 3 2 3 4\n2 1 1 1 5\n3 1 0 1 6\n2 0 1 1 6\n1 1 1 1 7\n2 2 0 1 7\n0 0 0 0\n"));
+        let relaxation = RcpspRelax::new(&problem);
+        let ranking = RcpspRanking::default();
+        let width = NbUnassignedWitdh(problem.nb_variables());
+        let mut fringe = NoDupFringe::new(MaxUB::new(&ranking));
+        let mut solver = ParBarrierSolverFc::new(&problem, &relaxation, &ranking, &width, &NoCutoff, &mut fringe);
+        let optimum = solver.maximize().best_value.unwrap();
+        let mut solution = solver.best_solution().unwrap();
+        solution.sort_unstable_by_key(|d| d.variable.id());
 
-        while !open.is_empty() {
-            let i = open.pop().unwrap();
-            order.push(i);
+        // the value of the sub-solve is relative to the origin, which is the time already
+        // elapsed in the prefix
+        let mut state = problem.initial_state();
+        let mut value = problem.initial_value();
+        for decision in solution.iter() {
+            let completion = problem.solve_from(state.clone(), &narrow(), &NoCutoff).unwrap();
+            assert!(completion.is_exact);
+            assert_eq!(completion.best_value.unwrap() - state.origin, optimum);
 
-            for j in instance.successors_set[i].iter() {
-                if predecessors[*j].remove(&i) && predecessors[*j].is_empty() {
-                    open.push(*j);
+            let (next, cost) = problem.combined_transition(&state, *decision);
+            state = next;
+            value += cost;
+        }
+        assert_eq!(value, optimum);
+
+        let mut merged = problem.initial_state();
+        merged.maybe_done = Some(FixedBitSet::with_capacity(problem.instance.n_jobs));
+        assert!(problem.solve_from(merged, &narrow(), &NoCutoff).is_err());
+    }
+
+    #[test]
+    fn sink_is_found_when_it_is_not_the_last_job() {
+        // job 2 is the sink, jobs 3 and 4 compete for the resource before it
+        let problem = Rcpsp::new(parse("4 1\n1\n0 0 2 3 4\n0 0 0\n2 1 1 2\n3 1 1 2\n"));
+        assert_eq!(problem.instance.n_jobs, 4);
+        assert_eq!(problem.sink, 1);
+        assert_eq!(solve(&problem), 5);
+    }
+
+    #[test]
+    fn sink_with_a_duration_is_followed_by_a_virtual_sink() {
+        let inst = RcpspInstance::new(vec![2, 3, 4], vec![vec![1], vec![1], vec![1]], vec![1], vec![(0, 1), (1, 2)]);
+        let pb = Rcpsp::new(inst);
+        assert_eq!(pb.instance.n_jobs, 4);
+        assert_eq!(pb.sink, 3);
+        assert_eq!(pb.instance.duration[pb.sink], 0);
+
+        assert_eq!(solve(&pb), 9);
+        assert!(pb.initial.origin + pb.bound.lower_bound(&pb.initial, &pb.instance) <= 9);
+    }
+
+    #[test]
+    fn job_longer_than_the_shifts_is_a_dead_end() {
+        // the resource is only available 3 periods in a row, less than the duration of job 2
+        let problem = Rcpsp::new(parse("3 1\n1\n0 0 1 2\n4 1 1 3\n0 0 0\nshift 1 3 2 1 0\n"));
+        assert!(problem.initial.dead_end);
+
+        let relaxation = RcpspRelax::new(&problem);
+        let ranking = RcpspRanking::default();
+        let width = NbUnassignedWitdh(problem.nb_variables());
+        let mut fringe = NoDupFringe::new(MaxUB::new(&ranking));
+        let mut solver = ParBarrierSolverFc::new(&problem, &relaxation, &ranking, &width, &NoCutoff, &mut fringe);
+        assert_eq!(solver.maximize().best_value, None);
+    }
+
+    #[test]
+    fn delayed_resource_pushes_the_makespan() {
+        let content = "4 1\n1\n0 0 1 2\n2 1 1 3\n2 1 1 4\n0 0 0\n";
+        assert_eq!(solve(&Rcpsp::new(parse(content))), 4);
+
+        let problem = Rcpsp::new(parse(&format!("{}ready 1 3\n", content)));
+        assert_eq!(problem.initial.profile[0].steps[0], ConsumptionStep { start: 0, end: 3, rem_capacity: 0 });
+        assert_eq!(problem.initial.profile[0].steps[1].rem_capacity, 1);
+        assert_eq!(solve(&problem), 7);
+
+        assert!(RcpspInstance::try_from(BufReader::new(format!("{}ready 1 -1\n", content).as_bytes())).is_err());
+    }
+
+    #[test]
+    fn feasible_decisions_match_the_domain() {
+        let mut problem = Rcpsp::new(parse("7 2\n2 1\n0 0 0 3 2 3 4\n2 1 1 1 5\n3 1 0 1 6\n2 0 1 1 6\n1 1 1 1 7\n2 2 0 1 7\n0 0 0 0\n"));
+        let jobs = |decisions: Vec<Decision>| decisions.iter().map(|d| d.value as usize).collect::<Vec<usize>>();
+
+        for non_delay in [false, true] {
+            problem.non_delay = non_delay;
+            let mut layer = vec![problem.initial_state()];
+            let mut count = 0;
+            while !layer.is_empty() {
+                let mut next = vec![];
+                for state in layer.iter() {
+                    assert_eq!(problem.feasible_decisions(state), jobs(domain(&problem, state)));
+                    next.extend(domain(&problem, state).into_iter().map(|d| problem.combined_transition(state, d).0));
+                    count += 1;
+                }
+                if layer.len() > 1 {
+                    let merged = RcpspRelax::new(&problem).merge(&mut layer.iter());
+                    assert_eq!(problem.feasible_decisions(&merged), jobs(domain(&problem, &merged)));
                 }
+                layer = next;
             }
+            assert!(count > problem.instance.n_jobs);
+        }
+    }
+
+    #[test]
+    fn left_shift_removes_the_padding_and_keeps_a_tight_schedule() {
+        let problem = Rcpsp::new(parse("4 1\n1\n0 0 2 2 3\n1 1 1 4\n2 1 1 4\n0 0 0\n"));
+
+        let mut padded = vec![0, 2, 5, 9];
+        assert!(problem.is_feasible(&padded));
+        problem.left_shift(&mut padded);
+        assert_eq!(padded[problem.sink], 3);
+        assert!(problem.is_feasible(&padded));
+
+        let mut tight = vec![0, 2, 0, 3];
+        problem.left_shift(&mut tight);
+        assert_eq!(tight, vec![0, 2, 0, 3]);
+    }
+
+    #[test]
+    fn bucketed_schedule_is_feasible_once_rescaled_and_within_the_rounding_bound() {
+        let content = "5 1\n2\n0 0 2 2 3\n3 1 1 4\n1 2 1 5\n3 1 1 5\n0 0 0\n";
+        let problem = Rcpsp::new(parse(content));
+        let optimum = solve(&problem);
+        assert_eq!(optimum, 7);
+
+        let bucket = 2;
+        let mut instance = parse(content);
+        instance.coarsen(bucket).unwrap();
+        let coarse = Rcpsp::new(instance);
+        let relaxation = RcpspRelax::new(&coarse);
+        let ranking = RcpspRanking::default();
+        let width = NbUnassignedWitdh(coarse.nb_variables());
+        let mut fringe = NoDupFringe::new(MaxUB::new(&ranking));
+        let mut solver = ParBarrierSolverFc::new(&coarse, &relaxation, &ranking, &width, &NoCutoff, &mut fringe);
+        let makespan = - solver.maximize().best_value.unwrap() * bucket;
+        assert!(makespan >= optimum);
+        assert!(makespan <= optimum + bucket * problem.instance.n_jobs as isize);
+
+        let starts = coarse.schedule(&solver.best_solution().unwrap()).iter().map(|s| s * bucket).collect::<Vec<isize>>();
+        assert!(problem.is_feasible(&starts));
+        assert_eq!(starts[problem.sink], makespan);
+    }
+
+    #[test]
+    fn flexible_requirement_goes_to_the_less_loaded_resource() {
+        let content = "4 2\n1 1\n0 0 0 2 2 3\n3 1 0 1 4\n3 0 0 1 4\n0 0 0 0\n";
+        assert_eq!(solve(&Rcpsp::new(parse(&format!("{}flex 3 1 1 1\n", content)))), 6);
+
+        let problem = Rcpsp::new(parse(&format!("{}flex 3 1 2 1 2\n", content)));
+        let relaxation = RcpspRelax::new(&problem);
+        let ranking = RcpspRanking::default();
+        let width = NbUnassignedWitdh(problem.nb_variables());
+        let mut fringe = NoDupFringe::new(MaxUB::new(&ranking));
+        let mut solver = ParBarrierSolverFc::new(&problem, &relaxation, &ranking, &width, &NoCutoff, &mut fringe);
+        assert_eq!(solver.maximize().best_value, Some(-3));
+
+        let solution = solver.best_solution().unwrap();
+        let flexible = solution.iter().map(|d| problem.decode(d.value)).find(|(job, _)| *job == 2);
+        assert_eq!(flexible, Some((2, Some(1))));
+        assert_eq!(problem.schedule(&solution)[2], 0);
+    }
+
+    #[test]
+    fn beam_search_gives_feasible_schedules_no_better_than_the_optimum() {
+        let content = "7 2\n2 1\n0 0 0 3 2 3 4\n2 1 1 1 5\n3 1 0 1 6\n2 0 1 1 6\n1 1 1 1 7\n2 2 0 1 7\n0 0 0 0\n";
+        let optimum = solve(&Rcpsp::new(parse(content)));
+
+        for beam in 1..=3 {
+            let mut problem = Rcpsp::new(parse(content));
+            problem.beam = Some(beam);
+            let relaxation = RcpspRelax::new(&problem);
+            let ranking = RcpspRanking::default();
+            let width = NbUnassignedWitdh(problem.nb_variables());
+            let mut fringe = NoDupFringe::new(MaxUB::new(&ranking));
+            let mut solver = ParBarrierSolverFc::new(&problem, &relaxation, &ranking, &width, &NoCutoff, &mut fringe);
+            let makespan = - solver.maximize().best_value.unwrap();
+            assert!(makespan >= optimum);
+
+            let starts = problem.schedule(&solver.best_solution().unwrap());
+            assert!(problem.is_feasible(&starts));
+            assert_eq!(starts[problem.sink], makespan);
         }
+    }
+
+    #[test]
+    fn removing_a_resource_limit_relaxes_the_earliest_starts() {
+        // jobs 2 and 3 compete for the resource before job 4, which delays it initially
+        let problem = Rcpsp::new(parse("4 1\n1\n0 0 2 2 3\n2 1 1 4\n2 1 1 4\n1 1 0\n"));
+        assert_eq!(solve(&problem), 5);
+        assert_eq!(problem.makespan_without_resource(0, &narrow(), &NoCutoff), Some(3));
+    }
+
+    #[test]
+    fn instances_without_jobs_or_resources_are_solved() {
+        let empty = Rcpsp::new(RcpspInstance::new(vec![], vec![], vec![], vec![]));
+        assert_eq!(solve(&empty), 0);
+
+        let unconstrained = Rcpsp::new(RcpspInstance::new(vec![2, 3, 4], vec![vec![], vec![], vec![]], vec![], vec![(0, 1)]));
+        assert_eq!(solve(&unconstrained), 5);
+    }
+
+    #[test]
+    fn imposed_placements_are_compared_with_the_unconstrained_optimum() {
+        // job 3 uses the whole resource and cannot overlap the chain of jobs 2 and 4
+        let problem = Rcpsp::new(parse("5 1\n2\n0 0 2 2 3\n3 1 1 4\n1 2 1 5\n3 1 1 5\n0 0 0\n"));
+        let optimum = solve(&problem);
+        assert_eq!(optimum, 7);
+
+        assert_eq!(problem.evaluate_with_fixed(&[(2, 0)], &narrow(), &NoCutoff), Some(optimum));
+        assert_eq!(problem.evaluate_with_fixed(&[(2, 6)], &narrow(), &NoCutoff), Some(optimum));
+        assert_eq!(problem.evaluate_with_fixed(&[(2, 8)], &narrow(), &NoCutoff), Some(9));
+        // job 4 cannot start before its predecessor ends
+        assert_eq!(problem.evaluate_with_fixed(&[(3, 0)], &narrow(), &NoCutoff), None);
+    }
+
+    #[test]
+    fn jobs_left_out_by_a_cycle_are_listed() {
+        // jobs 2 and 3 precede each other, and jobs 4 and 5 follow them
+        let content = "5 1\n1\n0 0 1 2\n1 1 1 3\n1 1 2 2 4\n1 1 1 5\n0 0 0\n";
+        let error = Rcpsp::try_new(parse(content)).unwrap_err();
+        assert_eq!(error, "jobs 2 3 4 5 are part of or follow a precedence cycle");
+
+        // with two roots, the jobs are shifted by the virtual source but reported as in the file
+        let content = "3 1\n1\n1 1 1 2\n1 1 1 1\n1 1 0\n";
+        let error = Rcpsp::try_new(parse(content)).unwrap_err();
+        assert_eq!(error, "jobs 1 2 are part of or follow a precedence cycle");
+    }
+
+    #[test]
+    fn several_roots_and_leaves_end_with_the_latest_leaf() {
+        // jobs 1 -> 2 and 3 -> 4 are two independent chains, job 5 is isolated
+        let content = "5 2\n1 1\n2 1 0 1 2\n3 0 0 0\n1 0 1 1 4\n1 0 0 0\n4 1 1 0\n";
+        let problem = Rcpsp::new(parse(content));
+        assert_eq!(problem.instance.n_jobs, 7);
+        assert_eq!(problem.instance.successors_set[0].len(), 3);
+        assert_eq!(problem.instance.predecessors_set[problem.sink].len(), 3);
+
+        // the isolated job 5 shares a resource with each chain and starts once both released them
+        let optimum = solve(&problem);
+        assert_eq!(optimum, 6);
+        let relaxation = RcpspRelax::new(&problem);
+        let ranking = RcpspRanking::default();
+        let width = NbUnassignedWitdh(problem.nb_variables());
+        let mut fringe = NoDupFringe::new(MaxUB::new(&ranking));
+        let mut solver = ParBarrierSolverFc::new(&problem, &relaxation, &ranking, &width, &NoCutoff, &mut fringe);
+        solver.maximize();
+        let starts = problem.schedule(&solver.best_solution().unwrap());
+        let latest = [2, 4, 5].iter().map(|i| starts[*i] + problem.instance.duration[*i]).max();
+        assert_eq!(latest, Some(optimum));
+    }
 
-        order
+    #[test]
+    fn longest_paths_meet_at_the_critical_path_length() {
+        // the longest path goes through jobs 2 and 4
+        let problem = Rcpsp::new(parse("5 1\n2\n0 0 2 2 3\n3 1 1 4\n1 2 1 5\n3 1 1 5\n0 0 0\n"));
+        assert_eq!(problem.head, vec![0, 0, 0, 3, 6]);
+        assert_eq!(problem.tail, vec![6, 6, 1, 3, 0]);
+        assert_eq!(problem.critical_path_length(), 6);
+        assert_eq!(problem.head[problem.sink], problem.tail[0]);
     }
-}
\ No newline at end of file
+
+    #[test]
+    fn critical_edges_follow_the_longest_path() {
+        let problem = Rcpsp::new(parse("5 1\n2\n0 0 2 2 3\n3 1 1 4\n1 2 1 5\n3 1 1 5\n0 0 0\n"));
+        let mut edges = problem.critical_edges();
+        edges.sort_unstable();
+        assert_eq!(edges, vec![(0, 1), (1, 3), (3, 4)]);
+    }
+
+    #[test]
+    fn dropping_an_optional_job_pays_off_only_net_of_its_penalty() {
+        // job 2 alone delays the makespan from 3 to 8 on the single resource
+        let content = "4 1\n1\n0 0 2 2 3\n5 1 1 4\n3 1 1 4\n0 0 0\n";
+        let cheap = Rcpsp::new(parse(&format!("{}optional 2 2\n", content)));
+        assert_eq!(solve(&cheap), 5);
+        let expensive = Rcpsp::new(parse(&format!("{}optional 2 10\n", content)));
+        assert_eq!(solve(&expensive), 8);
+    }
+
+    #[test]
+    fn total_flow_time_charges_optional_jobs_their_completion_or_penalty() {
+        // the shortest jobs first complete at 1, 2, 4 and 7 on the single resource
+        let mut inst = RcpspInstance::new(vec![3, 1, 1, 2], vec![vec![1]; 4], vec![1], vec![]);
+        inst.optional.insert(3);
+        inst.drop_penalty[3] = 10;
+        let mut problem = Rcpsp::new(inst.clone());
+        problem.objective = Objective::TotalFlowTime;
+        assert_eq!(solve(&problem), 14);
+
+        inst.drop_penalty[3] = 3;
+        let mut problem = Rcpsp::new(inst);
+        problem.objective = Objective::TotalFlowTime;
+        assert_eq!(solve(&problem), 1 + 2 + 5 + 3);
+    }
+
+    #[test]
+    fn usage_breakpoints_reconstruct_the_profile_of_a_schedule() {
+        let problem = Rcpsp::new(parse("5 1\n2\n0 0 3 2 3 4\n3 1 1 5\n2 1 1 5\n2 2 1 5\n0 0 0\n"));
+        let starts = vec![0, 0, 0, 3, 5];
+        let breakpoints = problem.usage_breakpoints(&starts, 0);
+        assert_eq!(breakpoints, vec![(0, 2), (2, 1), (3, 2), (5, 0)]);
+
+        for time in 0..7 {
+            let (_, usage) = breakpoints.iter().rev().find(|(t, _)| *t <= time).unwrap();
+            assert_eq!(*usage, problem.profile_at(&starts, 0, time));
+            assert!(*usage <= problem.instance.capacity[0]);
+        }
+    }
+
+    #[test]
+    fn resource_cost_is_the_area_under_the_usage_times_the_cost() {
+        let content = "5 1\n2\n0 0 3 2 3 4\n3 1 1 5\n2 1 1 5\n2 2 1 5\n0 0 0\n";
+        let starts = vec![0, 0, 0, 3, 5];
+        assert_eq!(Rcpsp::new(parse(content)).resource_cost(&starts), 0);
+
+        // the usage is 2 on [0, 2), 1 on [2, 3) and 2 on [3, 5)
+        let problem = Rcpsp::new(parse(&format!("{}cost 1 3\n", content)));
+        assert_eq!(problem.instance.resource_cost, vec![3]);
+        assert_eq!(problem.resource_cost(&starts), 27);
+    }
+
+    #[test]
+    fn scripted_steps_build_a_feasible_schedule() {
+        let problem = Rcpsp::new(parse("5 1\n2\n0 0 3 2 3 4\n3 1 1 5\n2 1 1 5\n2 2 1 5\n0 0 0\n"));
+        let mut state = problem.initial_state();
+        let mut starts = vec![0; problem.instance.n_jobs];
+        for job in [0, 3, 1, 2, 4] {
+            let (_, start) = problem.next_jobs(&state).into_iter().find(|(i, _)| *i == job).unwrap();
+            starts[job] = start;
+            state = problem.step(&state, job).0;
+        }
+        assert!(state.is_complete(problem.instance.n_jobs));
+        assert!(problem.next_jobs(&state).is_empty());
+        assert_eq!(starts, vec![0, 2, 2, 0, 5]);
+        assert!(problem.is_feasible(&starts));
+    }
+
+    #[test]
+    fn scheduled_count_follows_the_decisions_of_a_search() {
+        let problem = Rcpsp::new(parse("5 1\n2\n0 0 3 2 3 4\n3 1 1 5\n2 1 1 5\n2 2 1 5\n0 0 0\n"));
+        let mut state = problem.initial_state();
+        while let Some(decision) = domain(&problem, &state).first().copied() {
+            assert!(!state.is_complete(problem.instance.n_jobs));
+            state = problem.combined_transition(&state, decision).0;
+            assert_eq!(state.scheduled_count(), state.depth);
+            assert_eq!(state.scheduled_count(), state.done.count_ones(..));
+        }
+        assert_eq!(state.scheduled_count(), 5);
+        assert!(state.is_complete(problem.instance.n_jobs));
+    }
+
+    #[test]
+    fn each_relation_type_constrains_its_own_ends() {
+        // job 3 starts at 4 and lasts 3, job 4 lasts 2 and follows it with the given relation
+        let content = "5 1\n1\n0 0 1 2\n4 0 1 3\n3 0 2 4 5\n2 0 1 5\n0 0 0\n";
+        for (tag, earliest, makespan) in [("FS", 7, 9), ("SS", 4, 7), ("FF", 5, 7), ("SF", 2, 7)] {
+            let problem = Rcpsp::new(parse(&format!("{}rel 3 4 {}\n", content, tag)));
+            assert_eq!(problem.initial.earliest[3], earliest, "relation {}", tag);
+            assert_eq!(solve(&problem), makespan, "relation {}", tag);
+        }
+    }
+
+    #[test]
+    fn energy_of_the_ancestors_tightens_the_root_bound() {
+        // jobs 1 and 2 compete for the resource before job 3, which is followed by jobs 4 and 5
+        let edges = vec![(0, 2), (1, 2), (2, 3), (2, 4)];
+        let inst = RcpspInstance::new(vec![2, 2, 1, 2, 2], vec![vec![1], vec![1], vec![0], vec![1], vec![1]], vec![1], edges);
+        let mut pb = Rcpsp::new(inst);
+        let job = 3; // shifted by the virtual source
+        assert_eq!(pb.release[job], 4);
+        assert_eq!(pb.initial.earliest[job], 4);
+
+        // the precedences alone only delay job 3 until 2
+        let mut precedences_only = pb.initial.clone();
+        precedences_only.earliest = vec![0; pb.instance.n_jobs];
+        precedences_only.propagate(&pb.topo_order, &pb.propagated_lags, &pb.instance.duration, &pb.consumption, &pb.segments);
+        assert_eq!(precedences_only.earliest[job], 2);
+        let loose = pb.bound.lower_bound(&precedences_only, &pb.instance);
+        let tight = pb.bound.lower_bound(&pb.initial, &pb.instance);
+        assert!(loose < tight);
+        assert_eq!(tight, solve(&pb));
+
+        pb.fix_starts(&[]).unwrap();
+        assert_eq!(pb.initial.earliest[job], 4);
+    }
+
+
+    #[test]
+    fn jobs_forced_to_overlap_beyond_the_capacity_are_flagged() {
+        // jobs 2 and 3 are unrelated, and both must run during [0, 3) to end by 3
+        let mut problem = Rcpsp::new(parse("4 1\n1\n0 0 2 2 3\n3 1 1 4\n3 1 1 4\n0 0 0\n"));
+        assert_eq!(problem.forced_conflicts(), vec![]);
+
+        problem.deadline = Some(3);
+        assert_eq!(problem.forced_conflicts(), vec![(1, 2, 0)]);
+
+        // both jobs may run one after the other by 6
+        problem.deadline = Some(6);
+        assert_eq!(problem.forced_conflicts(), vec![]);
+        assert_eq!(solve(&problem), 6);
+    }
+
+    #[test]
+    fn earliest_starts_follow_a_chain() {
+        let mut problem = Rcpsp::new(parse("5 1\n2\n0 0 1 2\n2 1 1 3\n3 1 1 4\n1 1 1 5\n0 0 0\n"));
+        assert_eq!(problem.job_earliest_starts(), vec![0, 0, 2, 5, 6]);
+
+        problem.fixed[2] = Some(4);
+        assert_eq!(problem.job_earliest_starts()[2], 4);
+    }
+
+    #[test]
+    fn transitions_per_depth_sum_to_the_total() {
+        let problem = Rcpsp::new(parse("5 1\n2\n0 0 3 2 3 4\n3 1 1 5\n2 1 1 5\n2 2 1 5\n0 0 0\n"));
+        assert_eq!(solve(&problem), 5);
+
+        let per_depth = problem.transitions_per_depth.iter().map(|count| count.load(Ordering::Relaxed)).collect::<Vec<usize>>();
+        assert_eq!(per_depth.iter().sum::<usize>(), problem.transitions.load(Ordering::Relaxed));
+    }
+
+    #[test]
+    fn deadline_factor_rounds_up_the_multiple_of_the_critical_path() {
+        let mut problem = Rcpsp::new(parse("5 1\n1\n0 0 1 2\n2 1 1 3\n3 1 1 4\n1 1 1 5\n0 0 0\n"));
+        assert_eq!(problem.critical_path_length(), 6);
+        assert_eq!(problem.deadline_for_factor(1.1), 7);
+        assert_eq!(problem.deadline_for_factor(1.5), 9);
+
+        problem.deadline = Some(problem.deadline_for_factor(1.0));
+        assert_eq!(solve(&problem), 6);
+    }
+
+    /// Times both propagations on the transitions from the states of a descent in the first
+    /// instances of j60 and j90:
+    /// cargo test --release propagation_benchmark -- --ignored --nocapture
+    #[test]
+    #[ignore]
+    fn propagation_benchmark() {
+        for set in ["j60", "j90"] {
+            let dir = Path::new(env!("CARGO_MANIFEST_DIR")).join("resources").join(set);
+            let mut paths = read_dir(dir).unwrap().map(|entry| entry.unwrap().path()).collect::<Vec<_>>();
+            paths.sort();
+
+            for full_propagation in [false, true] {
+                let time = Instant::now();
+                for path in paths.iter().take(20) {
+                    let mut problem = Rcpsp::new(RcpspInstance::try_from(File::open(path).unwrap()).unwrap());
+                    problem.full_propagation = full_propagation;
+
+                    let mut state = problem.initial_state();
+                    while state.depth < problem.nb_variables() {
+                        let successors = domain(&problem, &state).into_iter()
+                            .map(|d| problem.combined_transition(&state, d).0)
+                            .collect::<Vec<State>>();
+                        state = successors.into_iter().next().unwrap();
+                    }
+                }
+                println!("{} (full propagation: {}): {:?}", set, full_propagation, time.elapsed());
+            }
+        }
+    }
+}
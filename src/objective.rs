@@ -0,0 +1,193 @@
+use crate::{instance::RcpspInstance, state::State};
+
+/// The objectives that can be minimized. Since the solver maximizes, the costs
+/// returned here are the opposite of the objective and `value` converts them back.
+#[derive(Debug, Clone, Copy, PartialEq, Eq, clap::ValueEnum)]
+pub enum Objective {
+    /// Completion time of the last job
+    Makespan,
+    /// Sum of the completion times of the jobs of the project, without the virtual source
+    /// and sink. Each mandatory job is charged its completion time relative to the state
+    /// where it is scheduled, and each move forward in time is charged once for each
+    /// mandatory job remaining, so that the costs do not depend on the origin of the states.
+    /// Since an optional job may be dropped, it is charged its completion time in absolute
+    /// time when it is scheduled instead.
+    TotalFlowTime,
+    /// Sum of the delays of the jobs past their due date, weighted by the weight of each job.
+    /// Each job of the project is charged its tardiness in absolute time when it is
+    /// scheduled, and each optional job at most its penalty if dropped.
+    WeightedTardiness,
+}
+
+impl Objective {
+    /// Value of the initial state
+    pub fn initial(&self, _inst: &RcpspInstance, sink: usize, state: &State) -> isize {
+        match self {
+            Objective::Makespan => - state.earliest[sink],
+            Objective::TotalFlowTime | Objective::WeightedTardiness => 0,
+        }
+    }
+
+    /// Cost of the transition from the given state to its successor, where the given
+    /// job starts at the given time, computed before the successor is moved forward in time
+    pub fn node_cost(&self, inst: &RcpspInstance, sink: usize, state: &State, successor: &State, job: usize, start: isize) -> isize {
+        match self {
+            Objective::Makespan => - (successor.earliest[sink] - state.earliest[sink]),
+            Objective::TotalFlowTime if inst.virtual_jobs.contains(job) => 0,
+            Objective::TotalFlowTime if inst.optional.contains(job) => - (state.origin + start + inst.duration[job]),
+            Objective::TotalFlowTime => - (start + inst.duration[job]),
+            Objective::WeightedTardiness if inst.virtual_jobs.contains(job) => 0,
+            Objective::WeightedTardiness => - Self::tardiness(inst, job, state.origin + start + inst.duration[job]),
+        }
+    }
+
+    /// Cost of dropping the given optional job
+    pub fn drop_cost(&self, inst: &RcpspInstance, job: usize) -> isize {
+        - inst.drop_penalty[job]
+    }
+
+    /// Additional cost of moving the given successor forward by the given delay
+    pub fn forward_cost(&self, inst: &RcpspInstance, successor: &State, delay: isize) -> isize {
+        match self {
+            Objective::Makespan | Objective::WeightedTardiness => 0,
+            Objective::TotalFlowTime => - (Self::remaining_jobs(inst, successor) as isize * delay),
+        }
+    }
+
+    /// Upper bound on the value that can still be obtained from the given state, given a
+    /// lower bound on its makespan. Each optional job costs at most its completion time
+    /// or its penalty.
+    pub fn upper_bound(&self, inst: &RcpspInstance, sink: usize, state: &State, makespan: isize) -> isize {
+        match self {
+            Objective::Makespan => - (makespan.max(state.earliest[sink]) - state.earliest[sink]),
+            Objective::TotalFlowTime => - (0..inst.n_jobs)
+                .filter(|i| !state.done.contains(*i) && !inst.virtual_jobs.contains(*i))
+                .map(|i| match inst.optional.contains(i) {
+                    true => (state.origin + state.earliest[i] + inst.duration[i]).min(inst.drop_penalty[i]),
+                    false => state.earliest[i] + inst.duration[i],
+                })
+                .sum::<isize>(),
+            Objective::WeightedTardiness => - (0..inst.n_jobs)
+                .filter(|i| !state.done.contains(*i) && !inst.virtual_jobs.contains(*i))
+                .map(|i| match inst.optional.contains(i) {
+                    true => Self::tardiness(inst, i, state.origin + state.earliest[i] + inst.duration[i]).min(inst.drop_penalty[i]),
+                    false => Self::tardiness(inst, i, state.origin + state.earliest[i] + inst.duration[i]),
+                })
+                .sum::<isize>(),
+        }
+    }
+
+    /// Value given by the solver to the complete schedule with the given start times
+    pub fn schedule_value(&self, inst: &RcpspInstance, starts: &[isize]) -> isize {
+        match self {
+            Objective::Makespan => - (0..inst.n_jobs).map(|i| starts[i] + inst.duration[i]).max().unwrap_or(0),
+            Objective::TotalFlowTime => - (0..inst.n_jobs)
+                .filter(|i| !inst.virtual_jobs.contains(*i))
+                .map(|i| starts[i] + inst.duration[i])
+                .sum::<isize>(),
+            Objective::WeightedTardiness => - (0..inst.n_jobs)
+                .filter(|i| !inst.virtual_jobs.contains(*i))
+                .map(|i| Self::tardiness(inst, i, starts[i] + inst.duration[i]))
+                .sum::<isize>(),
+        }
+    }
+
+    /// Number of mandatory jobs of the project that remain to be scheduled from the given
+    /// state, the jobs maybe done in a merged state being left out so that its costs stay
+    /// optimistic
+    fn remaining_jobs(inst: &RcpspInstance, state: &State) -> usize {
+        let maybe_done = |i: usize| state.maybe_done.as_ref().map_or(false, |maybe| maybe.contains(i));
+        (0..inst.n_jobs)
+            .filter(|i| !state.done.contains(*i) && !maybe_done(*i) && !inst.optional.contains(*i) && !inst.virtual_jobs.contains(*i))
+            .count()
+    }
+
+    /// Weighted delay of the given job past its due date when it completes at the given time
+    fn tardiness(inst: &RcpspInstance, job: usize, completion: isize) -> isize {
+        inst.job_weight[job] * (completion - inst.due_date(job)).max(0)
+    }
+
+    /// Converts a value found by the solver to the value of the objective
+    pub fn value(&self, solver_value: isize) -> isize {
+        - solver_value
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use std::io::BufReader;
+
+    use ddo::{Problem, Solver, ParBarrierSolverFc, NoDupFringe, MaxUB, NbUnassignedWitdh, NoCutoff};
+
+    use crate::{model::Rcpsp, relax::RcpspRelax, heuristics::RcpspRanking};
+
+    use super::*;
+
+    #[test]
+    fn value_is_the_makespan_of_the_best_schedule() {
+        let content = "5 1\n2\n0 0 3 2 3 4\n3 1 1 5\n2 1 1 5\n2 2 1 5\n0 0 0\n";
+        let problem = Rcpsp::new(RcpspInstance::try_from(BufReader::new(content.as_bytes())).unwrap());
+        let relaxation = RcpspRelax::new(&problem);
+        let ranking = RcpspRanking::default();
+        let width = NbUnassignedWitdh(problem.nb_variables());
+        let mut fringe = NoDupFringe::new(MaxUB::new(&ranking));
+        let mut solver = ParBarrierSolverFc::new(&problem, &relaxation, &ranking, &width, &NoCutoff, &mut fringe);
+
+        let completion = solver.maximize();
+        let starts = problem.schedule(&solver.best_solution().unwrap());
+        let makespan = (0..problem.instance.n_jobs).map(|i| starts[i] + problem.instance.duration[i]).max().unwrap();
+        assert!(completion.best_value.unwrap() < 0);
+        assert_eq!(problem.objective.value(completion.best_value.unwrap()), makespan);
+        assert_eq!(makespan, 5);
+    }
+
+    #[test]
+    fn total_flow_time_optimum_differs_from_the_makespan_optimum() {
+        // job 1 must come first to finish job 2 early, job 3 must come first to finish early itself
+        let content = "3 1\n1\n3 1 1 2\n3 0 0\n1 1 0\n";
+        let mut values = vec![];
+        for objective in [Objective::Makespan, Objective::TotalFlowTime] {
+            let mut problem = Rcpsp::new(RcpspInstance::try_from(BufReader::new(content.as_bytes())).unwrap());
+            problem.objective = objective;
+            let relaxation = RcpspRelax::new(&problem);
+            let ranking = RcpspRanking::default();
+            let width = NbUnassignedWitdh(problem.nb_variables());
+            let mut fringe = NoDupFringe::new(MaxUB::new(&ranking));
+            let mut solver = ParBarrierSolverFc::new(&problem, &relaxation, &ranking, &width, &NoCutoff, &mut fringe);
+
+            let completion = solver.maximize();
+            assert!(completion.is_exact);
+            let starts = problem.schedule(&solver.best_solution().unwrap());
+            assert_eq!(objective.schedule_value(&problem.instance, &starts), completion.best_value.unwrap());
+            values.push((objective.value(completion.best_value.unwrap()), starts[problem.sink]));
+        }
+        // the sum of the completion times without the virtual source and sink
+        assert_eq!(values, vec![(6, 6), (12, 7)]);
+    }
+
+    #[test]
+    fn makespan_cap_trades_tardiness_for_a_shorter_schedule() {
+        // job 2 is late unless it precedes job 1, which delays the long job 3 that follows job 1
+        let content = "5 1\n1\n0 0 2 2 3\n1 1 1 4\n3 1 1 5\n5 0 1 5\n0 0 0\nDUE DATES\n3 3\n";
+        let mut values = vec![];
+        for deadline in [None, Some(6), Some(5)] {
+            let mut problem = Rcpsp::new(RcpspInstance::try_from(BufReader::new(content.as_bytes())).unwrap());
+            problem.objective = Objective::WeightedTardiness;
+            problem.deadline = deadline;
+            let relaxation = RcpspRelax::new(&problem);
+            let ranking = RcpspRanking::default();
+            let width = NbUnassignedWitdh(problem.nb_variables());
+            let mut fringe = NoDupFringe::new(MaxUB::new(&ranking));
+            let mut solver = ParBarrierSolverFc::new(&problem, &relaxation, &ranking, &width, &NoCutoff, &mut fringe);
+
+            let completion = solver.maximize();
+            assert!(completion.is_exact);
+            values.push(solver.best_solution().map(|decisions| {
+                let starts = problem.schedule(&decisions);
+                assert_eq!(Objective::WeightedTardiness.schedule_value(&problem.instance, &starts), completion.best_value.unwrap());
+                (Objective::WeightedTardiness.value(completion.best_value.unwrap()), starts[problem.sink])
+            }));
+        }
+        assert_eq!(values, vec![Some((0, 9)), Some((1, 6)), None]);
+    }
+}
@@ -0,0 +1,113 @@
+use crate::model::Rcpsp;
+
+/// Classic priority rules used to order the jobs
+#[derive(Debug, Clone, Copy, PartialEq, Eq, clap::ValueEnum)]
+pub enum PriorityRule {
+    /// Longest processing time first
+    Lpt,
+    /// Most total (transitive) successors first
+    Mts,
+    /// Smallest latest finish time first, i.e. the critical path length minus the
+    /// longest path from the end of the job to the end of the project
+    Lft,
+    /// Greatest rank positional weight first, i.e. the duration of the job and its successors
+    Grpw,
+    /// Random order
+    Random,
+}
+
+/// Orders the jobs by decreasing priority according to the given rule,
+/// ties being broken by job index
+pub fn priority_order(rule: PriorityRule, pb: &Rcpsp, seed: u64) -> Vec<usize> {
+    let inst = &pb.instance;
+    let mut order = (0..inst.n_jobs).collect::<Vec<usize>>();
+
+    match rule {
+        PriorityRule::Lpt => {
+            order.sort_by_key(|i| -inst.duration[*i]);
+        },
+        PriorityRule::Mts => {
+            order.sort_by_key(|i| -(pb.descendants[*i].count_ones(..) as isize));
+        },
+        PriorityRule::Lft => {
+            let horizon = pb.critical_path_length();
+            order.sort_by_key(|i| horizon - pb.tail[*i] + inst.duration[*i]);
+        },
+        PriorityRule::Grpw => {
+            order.sort_by_key(|i| -(inst.duration[*i] + pb.descendants[*i].ones().map(|j| inst.duration[j]).sum::<isize>()));
+        },
+        PriorityRule::Random => {
+            // xorshift64, so that the order is reproducible for a given seed
+            let mut state = seed.max(1);
+            for i in (1..order.len()).rev() {
+                state ^= state << 13;
+                state ^= state >> 7;
+                state ^= state << 17;
+                order.swap(i, (state % (i as u64 + 1)) as usize);
+            }
+        },
+    }
+
+    order
+}
+
+#[cfg(test)]
+mod tests {
+    use std::io::BufReader;
+
+    use crate::{instance::RcpspInstance, sgs::serial_sgs};
+
+    use super::*;
+
+    /// Jobs 2 and 3 start the project, 2 precedes 4 while 3 precedes both 4 and 5
+    fn problem() -> Rcpsp {
+        let content = "6 1\n2\n0 0 2 2 3\n3 1 1 4\n1 1 2 4 5\n2 1 1 6\n4 1 1 6\n0 0 0\n";
+        Rcpsp::new(RcpspInstance::try_from(BufReader::new(content.as_bytes())).unwrap())
+    }
+
+    #[test]
+    fn rules_order_the_jobs_by_priority() {
+        let pb = problem();
+        assert_eq!(priority_order(PriorityRule::Lpt, &pb, 0), vec![4, 1, 3, 2, 0, 5]);
+        assert_eq!(priority_order(PriorityRule::Mts, &pb, 0), vec![0, 2, 1, 3, 4, 5]);
+        assert_eq!(priority_order(PriorityRule::Lft, &pb, 0), vec![0, 2, 1, 3, 4, 5]);
+        assert_eq!(priority_order(PriorityRule::Grpw, &pb, 0), vec![0, 2, 1, 4, 3, 5]);
+    }
+
+    #[test]
+    fn random_order_is_a_permutation_reproducible_from_its_seed() {
+        let pb = problem();
+        let order = priority_order(PriorityRule::Random, &pb, 42);
+        assert_eq!(order, priority_order(PriorityRule::Random, &pb, 42));
+
+        let mut sorted = order.clone();
+        sorted.sort_unstable();
+        assert_eq!(sorted, (0..pb.instance.n_jobs).collect::<Vec<usize>>());
+    }
+
+    #[test]
+    fn serial_sgs_follows_the_precedences() {
+        let pb = problem();
+        for rule in [PriorityRule::Lpt, PriorityRule::Mts, PriorityRule::Lft, PriorityRule::Grpw, PriorityRule::Random] {
+            let decisions = serial_sgs(&pb, &priority_order(rule, &pb, 7));
+            let jobs = decisions.iter().map(|d| pb.decode(d.value).0).collect::<Vec<usize>>();
+            assert_eq!(jobs.len(), pb.instance.n_jobs);
+            for (position, job) in jobs.iter().enumerate() {
+                assert!(pb.instance.predecessors_set[*job].iter().all(|p| jobs[..position].contains(p)));
+            }
+        }
+    }
+    #[test]
+    fn serial_sgs_gives_flexible_jobs_their_earliest_choice() {
+        let content = "4 2\n1 1\n0 0 0 2 2 3\n3 1 0 1 4\n2 0 0 1 4\n0 0 0 0\nflex 3 1 2 1 2\n";
+        let pb = Rcpsp::new(RcpspInstance::try_from(BufReader::new(content.as_bytes())).unwrap());
+        let decisions = serial_sgs(&pb, &[0, 1, 2, 3]);
+        let flexible = decisions.iter().map(|d| pb.decode(d.value)).find(|(job, _)| *job == 2);
+        assert_eq!(flexible, Some((2, Some(1))));
+        assert_eq!(pb.schedule(&decisions)[2], 0);
+
+        for rule in [PriorityRule::Lpt, PriorityRule::Mts, PriorityRule::Lft, PriorityRule::Grpw, PriorityRule::Random] {
+            assert!(pb.is_feasible(&pb.schedule(&serial_sgs(&pb, &priority_order(rule, &pb, 7)))));
+        }
+    }
+}
@@ -1,11 +1,39 @@
 use ddo::{Relaxation, Decision};
 use fixedbitset::FixedBitSet;
 
-use crate::{model::Rcpsp, state::State};
+use crate::{model::Rcpsp, state::State, sink::LogSink};
+
+/// How the earliest times of the merged states are aggregated. Only the minimum
+/// guarantees that the merged state is a relaxation, so that the bounds remain valid,
+/// the others are only meant for heuristic experiments.
+#[derive(Debug, Clone, Copy, PartialEq, Eq, clap::ValueEnum)]
+pub enum MergeEarliest {
+    /// Smallest earliest time among the merged states
+    Min,
+    /// Largest earliest time among the merged states (not admissible)
+    Max,
+    /// Average earliest time of the merged states, rounded down (not admissible)
+    Avg,
+}
 
 #[derive(Clone)]
 pub struct RcpspRelax<'a> {
     pub pb: &'a Rcpsp,
+    /// Where to log each merge, if anywhere
+    pub explain: Option<LogSink>,
+    /// Aggregation of the earliest times of the merged states
+    pub merge_earliest: MergeEarliest,
+    /// Where to log the intervals where the profiles of each merged state differ from
+    /// the merged profiles, if anywhere
+    pub explain_profiles: Option<LogSink>,
+    /// Granularity to which the remaining capacities of the merged profiles are rounded up
+    pub cap_bucket: isize,
+}
+
+impl <'a> RcpspRelax<'a> {
+    pub fn new(pb: &'a Rcpsp) -> Self {
+        Self { pb, explain: None, merge_earliest: MergeEarliest::Min, explain_profiles: None, cap_bucket: 1 }
+    }
 }
 
 impl Relaxation for RcpspRelax<'_> {
@@ -15,12 +43,22 @@ impl Relaxation for RcpspRelax<'_> {
         let mut merged = self.pb.initial.clone();
         merged.done.toggle_range(..);
         merged.profile.iter_mut().for_each(|c| {
-            c.steps[0].rem_capacity = 0;
+            c.steps.iter_mut().for_each(|s| s.rem_capacity = 0);
         });
 
         let mut maybe_done = FixedBitSet::with_capacity(self.pb.instance.n_jobs);
+        let mut count = 0;
+        let n_jobs = self.pb.instance.n_jobs;
+        let mut max_earliest = vec![isize::MIN; n_jobs];
+        let mut sum_earliest = vec![0; n_jobs];
+        let mut nb_earliest = vec![0; n_jobs];
+        let mut children = vec![];
 
         for state in states {
+            count += 1;
+            if self.explain_profiles.is_some() {
+                children.push(state);
+            }
             merged.done &= &state.done;
             maybe_done |= &state.done;
 
@@ -33,6 +71,9 @@ impl Relaxation for RcpspRelax<'_> {
             for i in 0..self.pb.instance.n_jobs {
                 if !state.done.contains(i) {
                     merged.earliest[i] = merged.earliest[i].min(state.earliest[i]);
+                    max_earliest[i] = max_earliest[i].max(state.earliest[i]);
+                    sum_earliest[i] += state.earliest[i];
+                    nb_earliest[i] += 1;
                 }
             }
 
@@ -40,7 +81,38 @@ impl Relaxation for RcpspRelax<'_> {
         }
 
         maybe_done ^= &merged.done;
+        merged.profile.iter_mut().for_each(|p| p.round_up_capacity(self.cap_bucket));
+        for i in (0..n_jobs).filter(|i| nb_earliest[*i] > 0) {
+            match self.merge_earliest {
+                MergeEarliest::Min => {},
+                MergeEarliest::Max => merged.earliest[i] = max_earliest[i],
+                MergeEarliest::Avg => merged.earliest[i] = sum_earliest[i].div_euclid(nb_earliest[i]),
+            }
+        }
+        if let Some(explain) = &self.explain {
+            explain.log(&format!("[explain] depth {}: width exceeded, merged {} states ({} jobs done, {} maybe done)",
+                merged.depth, count, merged.done.count_ones(..), maybe_done.count_ones(..)));
+        }
         merged.maybe_done = Some(maybe_done);
+        if let Some(explain) = &self.explain_profiles {
+            for (k, child) in children.iter().enumerate() {
+                for (r, profile) in child.profile.iter().enumerate() {
+                    for (start, end, child_cap, merged_cap) in profile.diff(&merged.profile[r]) {
+                        explain.log(&format!("[explain] depth {}: state {} resource {} has {} instead of {} on [{}, {})",
+                            merged.depth, k + 1, r + 1, child_cap, merged_cap, start, end));
+                    }
+                }
+            }
+        }
+
+        // the pointwise min of the earliest times ignores the merged profiles, propagating
+        // them again remains a relaxation since the merged profiles have more capacity.
+        // The jobs enabled only through a maybe done predecessor keep the min over the
+        // merged states: the merged state may skip that predecessor, as the states where
+        // it is done do, so its lags are not propagated and the earliest start of such a
+        // job cannot be raised above its value in any of the merged states.
+        let inst = &self.pb.instance;
+        merged.propagate(&self.pb.topo_order, &self.pb.propagated_lags, &inst.duration, &self.pb.consumption, &self.pb.segments);
 
         merged
     }
@@ -48,4 +120,122 @@ impl Relaxation for RcpspRelax<'_> {
     fn relax(&self, _src: &Self::State, _dest: &Self::State, _merged: &Self::State, _d: Decision, cost: isize) -> isize {
         cost
     }
+
+    fn fast_upper_bound(&self, state: &Self::State) -> isize {
+        if state.maybe_done.is_some() { // earliest times of merged states are not comparable
+            return isize::MAX;
+        }
+
+        let bound = self.pb.bound.lower_bound(state, &self.pb.instance);
+        self.pb.objective.upper_bound(&self.pb.instance, self.pb.sink, state, bound)
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use std::{io::BufReader, sync::{Arc, Mutex}};
+
+    use ddo::{Problem, Solver, ParBarrierSolverFc, FixedWidth, NoCutoff, NoDupFringe, MaxUB};
+
+    use super::*;
+    use crate::{instance::RcpspInstance, heuristics::RcpspRanking, model::tests::solve};
+
+    #[test]
+    fn successor_of_a_maybe_done_job_keeps_a_lower_bound() {
+        // job 1 precedes job 3, job 2 precedes the sink, nothing consumes the resource
+        let inst = RcpspInstance::new(vec![0, 2, 3, 1, 0], vec![vec![0]; 5], vec![1], vec![(0, 1), (0, 2), (1, 3), (2, 4), (3, 4)]);
+        let pb = Rcpsp::new(inst);
+        let relax = RcpspRelax::new(&pb);
+
+        // job 1 is done and job 3 can start at 2
+        let mut a = pb.initial.clone();
+        a.done.insert(0);
+        a.done.insert(1);
+        a.depth = 2;
+        a.earliest = vec![0, 0, 0, 2, 3];
+        // job 2 is done and job 1 starts at 1 at the earliest, so that job 3 cannot start before 3.
+        // Job 3 is then enabled only through job 1 being maybe done, and raising it to 3 would
+        // exclude the schedules of a.
+        let mut b = pb.initial.clone();
+        b.done.insert(0);
+        b.done.insert(2);
+        b.depth = 2;
+        b.earliest = vec![0, 1, 0, 3, 4];
+
+        let merged = relax.merge(&mut [a.clone(), b.clone()].iter());
+        assert_eq!(merged.maybe_done.as_ref().map(|m| m.ones().collect::<Vec<usize>>()), Some(vec![1, 2]));
+        assert_eq!(merged.earliest[3], 2);
+        assert!(merged.earliest[3] <= a.earliest[3] && merged.earliest[3] <= b.earliest[3]);
+    }
+
+    #[test]
+    fn merges_are_explained() {
+        let lines = Arc::new(Mutex::new(vec![]));
+        let captured = lines.clone();
+        let pb = Rcpsp::new(RcpspInstance::new(vec![0, 2, 3, 0], vec![vec![0], vec![1], vec![1], vec![0]], vec![1], vec![(0, 1), (0, 2), (1, 3), (2, 3)]));
+        let mut relax = RcpspRelax::new(&pb);
+        relax.explain = Some(LogSink(Arc::new(move |line: &str| captured.lock().unwrap().push(line.to_string()))));
+
+        let mut a = pb.initial.clone();
+        a.done.insert(0);
+        a.done.insert(1);
+        a.depth = 2;
+        let mut b = pb.initial.clone();
+        b.done.insert(0);
+        b.done.insert(2);
+        b.depth = 2;
+        relax.merge(&mut [a, b].iter());
+
+        assert_eq!(*lines.lock().unwrap(), vec!["[explain] depth 2: width exceeded, merged 2 states (1 jobs done, 2 maybe done)".to_string()]);
+    }
+
+    #[test]
+    fn merged_earliest_times_follow_the_aggregation() {
+        let inst = RcpspInstance::new(vec![0, 2, 3, 1, 0], vec![vec![0]; 5], vec![1], vec![(0, 1), (0, 2), (1, 3), (2, 4), (3, 4)]);
+        let pb = Rcpsp::new(inst);
+        let mut a = pb.initial.clone();
+        a.done.insert(0);
+        a.done.insert(1);
+        a.depth = 2;
+        a.earliest = vec![0, 0, 0, 2, 3];
+        let mut b = pb.initial.clone();
+        b.done.insert(0);
+        b.done.insert(2);
+        b.depth = 2;
+        b.earliest = vec![0, 1, 0, 3, 4];
+
+        let mut relax = RcpspRelax::new(&pb);
+        let mut earliest = vec![];
+        for merge_earliest in [MergeEarliest::Min, MergeEarliest::Max, MergeEarliest::Avg] {
+            relax.merge_earliest = merge_earliest;
+            earliest.push(relax.merge(&mut [a.clone(), b.clone()].iter()).earliest[3]);
+        }
+        assert_eq!(earliest, vec![2, 3, 2]);
+    }
+
+    #[test]
+    fn only_min_keeps_the_optimum_but_all_aggregations_give_feasible_schedules() {
+        let content = "7 2\n2 1\n0 0 0 3 2 3 4\n2 1 1 1 5\n3 1 0 1 6\n2 0 1 1 6\n1 1 1 1 7\n2 2 0 1 7\n0 0 0 0\n";
+        let pb = Rcpsp::new(RcpspInstance::try_from(BufReader::new(content.as_bytes())).unwrap());
+        let optimum = solve(&pb);
+        let inst = &pb.instance;
+
+        for merge_earliest in [MergeEarliest::Min, MergeEarliest::Max, MergeEarliest::Avg] {
+            let mut relax = RcpspRelax::new(&pb);
+            relax.merge_earliest = merge_earliest;
+            let ranking = RcpspRanking::default();
+            let width = FixedWidth(2);
+            let mut fringe = NoDupFringe::new(MaxUB::new(&ranking));
+            let mut solver = ParBarrierSolverFc::new(&pb, &relax, &ranking, &width, &NoCutoff, &mut fringe);
+            let value = pb.objective.value(solver.maximize().best_value.unwrap());
+            if merge_earliest == MergeEarliest::Min {
+                assert_eq!(value, optimum);
+            }
+
+            let starts = pb.schedule(&solver.best_solution().unwrap());
+            assert_eq!(starts[pb.sink], value);
+            assert!((0..inst.n_jobs).all(|i| inst.successors_set[i].iter().all(|j| starts[i] + inst.duration[i] <= starts[*j])));
+            assert!((0..inst.n_resources).all(|r| (0..value).all(|t| pb.profile_at(&starts, r, t) <= inst.capacity[r])));
+        }
+    }
 }
@@ -0,0 +1,35 @@
+use ddo::{Decision, Variable};
+
+use crate::model::Rcpsp;
+
+/// Serial schedule generation scheme: repeatedly selects the eligible job (whose
+/// predecessors are all scheduled) that comes first in the priority order. Since the
+/// jobs are then scheduled at their earliest start, the sequence alone defines the schedule.
+/// Optional jobs are never dropped, and each job with a flexible requirement gets the
+/// choice of resources that lets it start the earliest.
+pub fn serial_sgs(pb: &Rcpsp, priority: &[usize]) -> Vec<Decision> {
+    let n_jobs = pb.instance.n_jobs;
+    let mut rank = vec![0; n_jobs];
+    for (r, i) in priority.iter().copied().enumerate() {
+        rank[i] = r;
+    }
+
+    let mut remaining = pb.instance.predecessors_set.iter().map(|p| p.len()).collect::<Vec<usize>>();
+    let mut scheduled = vec![false; n_jobs];
+    let mut state = pb.initial.clone();
+    let mut decisions = vec![];
+
+    while let Some(job) = (0..n_jobs)
+        .filter(|i| !scheduled[*i] && remaining[*i] == 0)
+        .min_by_key(|i| rank[*i]) {
+        scheduled[job] = true;
+        for j in pb.instance.successors_set[job].iter().copied() {
+            remaining[j] -= 1;
+        }
+        let choice = pb.earliest_choice(&state, job).unwrap_or(0);
+        pb.schedule_job(&mut state, job, choice);
+        decisions.push(Decision { variable: Variable(decisions.len()), value: pb.encode(job, choice) });
+    }
+
+    decisions
+}
@@ -0,0 +1,23 @@
+use std::{fmt::Debug, sync::Arc};
+
+/// Destination of the lines logged by the library while solving an instance
+/// (e.g. the transitions with --trace), so that callers can capture them.
+#[derive(Clone)]
+pub struct LogSink(pub Arc<dyn Fn(&str) + Send + Sync>);
+
+impl LogSink {
+    /// Writes each line on the standard error
+    pub fn stderr() -> Self {
+        Self(Arc::new(|line| eprintln!("{}", line)))
+    }
+
+    pub fn log(&self, line: &str) {
+        (self.0)(line)
+    }
+}
+
+impl Debug for LogSink {
+    fn fmt(&self, f: &mut std::fmt::Formatter<'_>) -> std::fmt::Result {
+        f.write_str("LogSink")
+    }
+}
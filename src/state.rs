@@ -1,8 +1,10 @@
-use std::{hash::Hash, collections::{VecDeque, HashSet}, fmt::Display, vec};
+use std::{hash::{Hash, Hasher}, collections::VecDeque, fmt::Display, vec, mem::size_of};
 
 use fixedbitset::FixedBitSet;
 
-#[derive(Debug, Clone, Hash, PartialEq, Eq)]
+use crate::instance::ConsumptionSegment;
+
+#[derive(Debug, Clone)]
 pub struct State {
     /// These are the jobs that have already been scheduled
     pub done: FixedBitSet,
@@ -14,20 +16,117 @@ pub struct State {
     pub earliest: Vec<isize>,
     /// This is the 'depth' in the schedule, the number of jobs that have already been scheduled
     pub depth: usize,
+    /// Absolute time corresponding to time 0 in this state, which is not part of its identity
+    pub origin: isize,
+    /// Whether some job cannot fit in the profiles anymore, so that the state has no successor
+    pub dead_end: bool,
+}
+
+// the earliest times of the jobs done are stale and never read, so they are not
+// part of the identity of the state
+impl PartialEq for State {
+    fn eq(&self, other: &Self) -> bool {
+        self.done == other.done
+            && self.maybe_done == other.maybe_done
+            && self.profile == other.profile
+            && self.relevant_earliest().eq(other.relevant_earliest())
+            && self.depth == other.depth
+            && self.dead_end == other.dead_end
+    }
+}
+
+impl Eq for State {}
+
+impl Hash for State {
+    fn hash<H: Hasher>(&self, state: &mut H) {
+        self.done.hash(state);
+        self.maybe_done.hash(state);
+        self.profile.hash(state);
+        self.relevant_earliest().for_each(|e| e.hash(state));
+        self.depth.hash(state);
+        self.dead_end.hash(state);
+    }
 }
 
 impl State {
+    /// Earliest time of each job, replaced by 0 for the jobs that are done
+    fn relevant_earliest(&self) -> impl Iterator<Item = isize> + '_ {
+        self.earliest.iter().enumerate().map(|(i, e)| if self.done.contains(i) { 0 } else { *e })
+    }
+
+    /// Arbitrary but fixed order on the jobs done and the earliest times of the states,
+    /// used to break the ties between states that are otherwise equivalent
+    pub fn content_cmp(&self, other: &Self) -> std::cmp::Ordering {
+        self.done.as_slice().cmp(other.done.as_slice())
+            .then_with(|| self.relevant_earliest().cmp(other.relevant_earliest()))
+            .then_with(|| self.origin.cmp(&other.origin))
+    }
+
     pub fn add_consumption(&mut self, start_time: isize, duration: isize, consumption: &Vec<isize>) {
         if duration > 0 {
             for (i, c) in consumption.iter().copied().enumerate() {
                 if c > 0 {
                     self.profile[i].add_consumption(start_time, duration, c);
+                    self.profile[i].compact();
+                    self.profile[i].debug_check();
+                }
+            }
+        }
+    }
+
+    /// Adds the consumption of a job, either constant over its duration or given by its segments
+    pub fn add_job_consumption(&mut self, start_time: isize, duration: isize, consumption: &Vec<isize>, segments: &Option<Vec<ConsumptionSegment>>) {
+        if let Some(segments) = segments {
+            let mut start_time = start_time;
+            for segment in segments.iter() {
+                self.add_consumption(start_time, segment.duration, &segment.consumption);
+                start_time += segment.duration;
+            }
+        } else {
+            self.add_consumption(start_time, duration, consumption);
+        }
+    }
+
+    /// Computes the earliest start of a job, either with constant consumption or given by its segments,
+    /// or None if the job does not fit in the profiles
+    pub fn get_job_earliest_start(&self, earliest: isize, duration: isize, consumption: &Vec<isize>, segments: &Option<Vec<ConsumptionSegment>>) -> Option<isize> {
+        let segments = match segments {
+            Some(segments) => segments,
+            None => return self.get_earliest_start(earliest, duration, consumption),
+        };
+
+        let mut earliest = earliest;
+        loop {
+            let mut moved_earliest = false;
+            let mut offset = 0;
+            for segment in segments.iter() {
+                let start = self.get_earliest_start(earliest + offset, segment.duration, &segment.consumption)? - offset;
+                offset += segment.duration;
+
+                // restart from the first segment if earliest has changed
+                if start > earliest {
+                    earliest = start;
+                    moved_earliest = true;
+                    break;
                 }
             }
+
+            // exit loop if all segments have agreed on a start time
+            if !moved_earliest {
+                break;
+            }
         }
+
+        Some(earliest)
     }
 
-    pub fn get_earliest_start(&self, earliest: isize, duration: isize, consumption: &Vec<isize>) -> isize {
+    /// Computes the earliest start of a job with constant consumption, or None if there is
+    /// no interval with enough capacity before the end of the profiles
+    pub fn get_earliest_start(&self, earliest: isize, duration: isize, consumption: &Vec<isize>) -> Option<isize> {
+        if duration == 0 { // the job occupies no space in the profiles
+            return Some(earliest);
+        }
+
         let mut index = vec![0; self.profile.len()];
         let mut earliest = earliest;
 
@@ -40,14 +139,14 @@ impl State {
 
                 loop {
                     // find first step after earliest that has enough capacity
-                    while profile.steps[index[i]].end <= earliest || profile.steps[index[i]].rem_capacity < consumption[i] {
+                    while profile.steps.get(index[i])?.end <= earliest || profile.steps[index[i]].rem_capacity < consumption[i] {
                         index[i] += 1;
                     }
 
                     // check if enough time with the step found and the next ones to insert the job
                     let mut cumul_time: isize = 0;
                     let mut j = index[i];
-                    loop {
+                    while j < profile.steps.len() {
                         if profile.steps[j].rem_capacity >= consumption[i] {
                             let time = profile.steps[j].end - profile.steps[j].start.max(earliest);
                             cumul_time = cumul_time.saturating_add(time);
@@ -84,26 +183,61 @@ impl State {
             }
         }
 
-        earliest
+        Some(earliest)
     }
 
-    pub fn propagate(&mut self, topo_order: &Vec<usize>, successors: &Vec<HashSet<usize>>, duration: &Vec<isize>, consumption: &Vec<Vec<isize>>) {
+    pub fn propagate(&mut self, topo_order: &Vec<usize>, successors: &Vec<Vec<(usize, isize)>>, duration: &Vec<isize>, consumption: &Vec<Vec<isize>>, segments: &Vec<Option<Vec<ConsumptionSegment>>>) {
         for i in topo_order.iter().copied() {
             if self.done.contains(i) { // propagation already done for this job
                 continue;
             }
 
-            self.earliest[i] = self.get_earliest_start(self.earliest[i], duration[i], &consumption[i]);
+            let maybe_done = self.maybe_done.as_ref().map_or(false, |maybe| maybe.contains(i));
+            match self.get_job_earliest_start(self.earliest[i], duration[i], &consumption[i], &segments[i]) {
+                Some(earliest) => self.earliest[i] = earliest,
+                None if maybe_done => {},
+                None => {
+                    self.dead_end = true;
+                    return;
+                },
+            }
+
+            if maybe_done { // propagation may have been done for those jobs
+                continue;
+            }
 
-            if let Some(maybe) = &self.maybe_done { // propagation may have been done for those jobs
-                if maybe.contains(i) {
-                    continue;
+            for (j, lag) in successors[i].iter().copied() {
+                if !self.done.contains(j) {
+                    self.earliest[j] = self.earliest[j].max(self.earliest[i] + lag);
                 }
             }
+        }
+    }
 
-            for j in successors[i].iter().copied() {
-                if !self.done.contains(j) {
-                    self.earliest[j] = self.earliest[j].max(self.earliest[i] + duration[i]);
+    /// Propagates only the jobs that may be affected by the scheduling of the given job:
+    /// the jobs competing with it for a resource and, transitively, the successors whose
+    /// earliest start is pushed. Requires the state to be fully propagated beforehand.
+    pub fn propagate_from(&mut self, job: usize, topo_order: &Vec<usize>, successors: &Vec<Vec<(usize, isize)>>, competitors: &FixedBitSet, duration: &Vec<isize>, consumption: &Vec<Vec<isize>>, segments: &Vec<Option<Vec<ConsumptionSegment>>>) {
+        let mut dirty = competitors.clone();
+        dirty.extend(successors[job].iter().map(|(j, _)| *j));
+
+        for i in topo_order.iter().copied() {
+            if self.done.contains(i) || !dirty.contains(i) {
+                continue;
+            }
+
+            match self.get_job_earliest_start(self.earliest[i], duration[i], &consumption[i], &segments[i]) {
+                Some(earliest) => self.earliest[i] = earliest,
+                None => {
+                    self.dead_end = true;
+                    return;
+                },
+            }
+
+            for (j, lag) in successors[i].iter().copied() {
+                if !self.done.contains(j) && self.earliest[i] + lag > self.earliest[j] {
+                    self.earliest[j] = self.earliest[i] + lag;
+                    dirty.insert(j);
                 }
             }
         }
@@ -122,6 +256,7 @@ impl State {
 
         if let Some(earliest) = earliest {
             if earliest > 0 {
+                self.origin += earliest;
                 for profile in self.profile.iter_mut() {
                     profile.forward_by(earliest);
                 }
@@ -134,6 +269,105 @@ impl State {
         }
     }
 
+    /// Number of decisions taken to reach this state, i.e. the number of jobs scheduled
+    /// or dropped, which may exceed the number of jobs surely done in a merged state
+    pub fn scheduled_count(&self) -> usize {
+        self.depth
+    }
+
+    /// Whether all the jobs of an instance with the given number of jobs have been decided
+    pub fn is_complete(&self, n_jobs: usize) -> bool {
+        self.depth >= n_jobs
+    }
+
+    /// Fraction of the jobs that are maybe done in this state
+    pub fn looseness(&self) -> f64 {
+        match &self.maybe_done {
+            Some(maybe) if !maybe.is_empty() => maybe.count_ones(..) as f64 / maybe.len() as f64,
+            _ => 0.0,
+        }
+    }
+
+    /// Estimates the number of bytes used to store this state
+    pub fn size_in_bytes(&self) -> usize {
+        let mut size = size_of::<Self>();
+        size += self.done.len() / 8;
+        size += self.maybe_done.as_ref().map_or(0, |maybe| maybe.len() / 8);
+        size += self.profile.iter()
+            .map(|p| size_of::<ConsumptionProfile>() + p.steps.len() * size_of::<ConsumptionStep>())
+            .sum::<usize>();
+        size += self.earliest.len() * size_of::<isize>();
+        size
+    }
+
+    /// Writes the state as whitespace-separated tokens, read back by deserialize
+    pub fn serialize(&self) -> String {
+        let bits = |set: &FixedBitSet| (0..set.len()).map(|i| if set.contains(i) { '1' } else { '0' }).collect::<String>();
+        let mut tokens = vec![
+            self.depth.to_string(),
+            self.origin.to_string(),
+            (self.dead_end as u8).to_string(),
+            self.earliest.len().to_string(),
+            bits(&self.done),
+            self.maybe_done.as_ref().map_or("-".to_string(), bits),
+        ];
+        tokens.extend(self.earliest.iter().map(|e| e.to_string()));
+        tokens.push(self.profile.len().to_string());
+        for profile in self.profile.iter() {
+            tokens.push(profile.capacity.to_string());
+            tokens.push(profile.steps.len().to_string());
+            for s in profile.steps.iter() {
+                tokens.extend([s.start.to_string(), s.end.to_string(), s.rem_capacity.to_string()]);
+            }
+        }
+        tokens.join(" ")
+    }
+
+    /// Reads a state written by serialize from the given tokens, or None if they are malformed
+    pub fn deserialize<'a>(tokens: &mut impl Iterator<Item = &'a str>) -> Option<State> {
+        fn bits(s: &str, n: usize) -> Option<FixedBitSet> {
+            if s.len() != n {
+                return None;
+            }
+            let mut set = FixedBitSet::with_capacity(n);
+            for (i, c) in s.chars().enumerate() {
+                match c {
+                    '1' => set.insert(i),
+                    '0' => {},
+                    _ => return None,
+                }
+            }
+            Some(set)
+        }
+
+        let depth = tokens.next()?.parse::<usize>().ok()?;
+        let origin = tokens.next()?.parse::<isize>().ok()?;
+        let dead_end = tokens.next()? == "1";
+        let n_jobs = tokens.next()?.parse::<usize>().ok()?;
+        let done = bits(tokens.next()?, n_jobs)?;
+        let maybe_done = match tokens.next()? {
+            "-" => None,
+            maybe => Some(bits(maybe, n_jobs)?),
+        };
+        let earliest = (0..n_jobs).map(|_| tokens.next()?.parse::<isize>().ok()).collect::<Option<Vec<isize>>>()?;
+        let n_resources = tokens.next()?.parse::<usize>().ok()?;
+        let mut profile = vec![];
+        for _ in 0..n_resources {
+            let capacity = tokens.next()?.parse::<isize>().ok()?;
+            let n_steps = tokens.next()?.parse::<usize>().ok()?;
+            let mut steps = VecDeque::with_capacity(n_steps);
+            for _ in 0..n_steps {
+                let mut next = || tokens.next()?.parse::<isize>().ok();
+                steps.push_back(ConsumptionStep { start: next()?, end: next()?, rem_capacity: next()? });
+            }
+            let p = ConsumptionProfile { steps, capacity };
+            p.check_invariants().ok()?;
+            profile.push(p);
+        }
+
+        Some(State { done, maybe_done, profile, earliest, depth, origin, dead_end })
+    }
+
     pub fn merge_consumption_profile(&mut self, profile: &Vec<ConsumptionProfile>) {
         for i in 0..self.profile.len() {
             self.profile[i].merge_consumption_profile(&profile[i]);
@@ -174,7 +408,9 @@ impl Display for State {
 
 #[derive(Debug, Clone, Hash, PartialEq, Eq)]
 pub struct ConsumptionProfile {
-    pub steps: VecDeque<ConsumptionStep>
+    pub steps: VecDeque<ConsumptionStep>,
+    /// Capacity of the resource
+    pub capacity: isize,
 }
 
 impl ConsumptionProfile {
@@ -231,10 +467,16 @@ impl ConsumptionProfile {
     }
 
     pub fn forward_by(&mut self, delta: isize) {
-        while !self.steps.is_empty() && self.steps[0].end <= delta {
+        while self.steps.len() > 1 && self.steps[0].end <= delta {
             self.steps.pop_front();
         }
 
+        // moving to the end of the profile, which happens when the jobs are all in sequence,
+        // leaves a step without capacity so that the profile is never empty
+        if self.steps[0].end <= delta {
+            self.steps[0] = ConsumptionStep { start: delta, end: delta + 1, rem_capacity: 0 };
+        }
+
         self.steps[0].start = delta;
 
         for s in self.steps.iter_mut() {
@@ -242,8 +484,7 @@ impl ConsumptionProfile {
             s.end -= delta;
         }
 
-        let last = self.steps.len() - 1;
-        self.steps[last].end = isize::MAX;
+        self.debug_check();
     }
 
     fn merge_consumption_profile(&mut self, other: &ConsumptionProfile) {
@@ -289,18 +530,97 @@ impl ConsumptionProfile {
             }
         }
 
-        // merge consecutive steps with same rem_capacity
-        i = 0;
-        while i+1 < result.len() {
-            if result[i].rem_capacity == result[i+1].rem_capacity {
-                result[i].end = result[i+1].end;
-                result.remove(i+1);
+        // the profiles may end at different times if they were moved forward by different amounts
+        let end = self.steps[self.steps.len() - 1].end.max(other.steps[other.steps.len() - 1].end);
+        if let Some(last) = result.back_mut() {
+            last.end = end;
+        }
+
+        self.steps = result;
+        self.compact();
+        self.debug_check();
+    }
+
+    /// Intervals where the remaining capacity of this profile differs from the one of the
+    /// other profile, as (start, end, capacity in this profile, capacity in the other),
+    /// restricted to the times covered by both profiles
+    pub fn diff(&self, other: &Self) -> Vec<(isize, isize, isize, isize)> {
+        let mut intervals: Vec<(isize, isize, isize, isize)> = vec![];
+        let mut i = 0;
+        let mut j = 0;
+        while i < self.steps.len() && j < other.steps.len() {
+            let a = &self.steps[i];
+            let b = &other.steps[j];
+            let start = a.start.max(b.start);
+            let end = a.end.min(b.end);
+            if end > start && a.rem_capacity != b.rem_capacity {
+                match intervals.last_mut() {
+                    Some(last) if last.1 == start && last.2 == a.rem_capacity && last.3 == b.rem_capacity => last.1 = end,
+                    _ => intervals.push((start, end, a.rem_capacity, b.rem_capacity)),
+                }
+            }
+
+            if a.end < b.end {
+                i += 1;
+            } else {
+                j += 1;
+            }
+        }
+        intervals
+    }
+
+    /// Rounds the remaining capacity of each step up to a multiple of the given granularity,
+    /// without exceeding the capacity, which reduces the number of distinct levels. Since the
+    /// merged profiles keep the largest remaining capacity, rounding up keeps them a relaxation.
+    pub fn round_up_capacity(&mut self, granularity: isize) {
+        if granularity <= 1 {
+            return;
+        }
+        let capacity = self.capacity;
+        self.steps.iter_mut().for_each(|s| {
+            let rounded = (s.rem_capacity + granularity - 1).div_euclid(granularity) * granularity;
+            s.rem_capacity = rounded.min(capacity).max(s.rem_capacity);
+        });
+        self.compact();
+    }
+
+    /// Merges the consecutive steps with the same remaining capacity
+    pub fn compact(&mut self) {
+        let mut i = 0;
+        while i+1 < self.steps.len() {
+            if self.steps[i].rem_capacity == self.steps[i+1].rem_capacity {
+                self.steps[i].end = self.steps[i+1].end;
+                self.steps.remove(i+1);
             } else {
                 i += 1;
             }
         }
+    }
 
-        self.steps = result;
+    /// Checks that the steps are non-empty, contiguous and do not exceed the capacity
+    pub fn check_invariants(&self) -> Result<(), String> {
+        if self.steps.is_empty() {
+            return Err("profile without steps".to_string());
+        }
+
+        for (i, step) in self.steps.iter().enumerate() {
+            if step.start >= step.end {
+                return Err(format!("step {} is empty ({} - {})", i, step.start, step.end));
+            }
+            if step.rem_capacity > self.capacity {
+                return Err(format!("step {} exceeds the capacity ({} > {})", i, step.rem_capacity, self.capacity));
+            }
+            if i + 1 < self.steps.len() && step.end != self.steps[i + 1].start {
+                return Err(format!("steps {} and {} are not contiguous ({} != {})", i, i + 1, step.end, self.steps[i + 1].start));
+            }
+        }
+
+        Ok(())
+    }
+
+    /// Checks the invariants of the profile in debug builds only
+    fn debug_check(&self) {
+        debug_assert_eq!(self.check_invariants(), Ok(()), "corrupted profile {}", self);
     }
 }
 
@@ -321,4 +641,158 @@ pub struct ConsumptionStep {
     pub start: isize,
     pub end: isize,
     pub rem_capacity: isize,
-}
\ No newline at end of file
+}
+
+#[cfg(test)]
+mod tests {
+    use std::{sync::Arc, collections::hash_map::DefaultHasher};
+
+    use ddo::{Fringe, NoDupFringe, MaxUB, SubProblem};
+
+    use super::*;
+    use crate::heuristics::RcpspRanking;
+
+    fn profile(steps: &[(isize, isize, isize)], capacity: isize) -> ConsumptionProfile {
+        let steps = steps.iter().map(|(start, end, rem_capacity)| ConsumptionStep { start: *start, end: *end, rem_capacity: *rem_capacity }).collect();
+        ConsumptionProfile { steps, capacity }
+    }
+
+    #[test]
+    fn forward_to_the_end_of_the_profile_keeps_a_step() {
+        let mut p = profile(&[(0, 3, 1), (3, 5, 2)], 2);
+        p.forward_by(5);
+        assert_eq!(p, profile(&[(0, 1, 0)], 2));
+    }
+
+    #[test]
+    fn forward_inside_the_profile_shifts_the_steps() {
+        let mut p = profile(&[(0, 3, 1), (3, 5, 2)], 2);
+        p.forward_by(4);
+        assert_eq!(p, profile(&[(0, 1, 2)], 2));
+    }
+
+    #[test]
+    fn corrupted_profiles_are_caught() {
+        assert_eq!(profile(&[(0, 3, 1), (3, 5, 2)], 2).check_invariants(), Ok(()));
+        assert!(profile(&[], 2).check_invariants().is_err());
+        assert!(profile(&[(0, 3, 1), (3, 3, 2), (3, 5, 2)], 2).check_invariants().is_err());
+        assert!(profile(&[(0, 3, 1), (4, 5, 2)], 2).check_invariants().is_err());
+        assert!(profile(&[(0, 3, 1), (2, 5, 2)], 2).check_invariants().is_err());
+        assert!(profile(&[(0, 3, 3), (3, 5, 2)], 2).check_invariants().is_err());
+    }
+
+    #[test]
+    #[should_panic(expected = "corrupted profile")]
+    #[cfg(debug_assertions)]
+    fn debug_builds_check_the_profile_after_each_update() {
+        let mut p = profile(&[(0, 3, 1), (3, 5, 4)], 2);
+        p.forward_by(1);
+    }
+
+    #[test]
+    fn fragmenting_insertions_keep_the_profile_compact() {
+        let mut state = State { done: FixedBitSet::with_capacity(0), maybe_done: None, profile: vec![profile(&[(0, 10, 3)], 3)], earliest: vec![], depth: 0, origin: 0, dead_end: false };
+        let expected = [
+            profile(&[(0, 2, 2), (2, 10, 3)], 3),
+            profile(&[(0, 4, 2), (4, 10, 3)], 3),
+            profile(&[(0, 1, 1), (1, 4, 2), (4, 10, 3)], 3),
+            profile(&[(0, 1, 1), (1, 7, 2), (7, 10, 3)], 3),
+            profile(&[(0, 7, 1), (7, 10, 3)], 3),
+        ];
+        for ((start, duration, c), expected) in [(0, 2, 1), (2, 2, 1), (0, 1, 1), (4, 3, 1), (1, 6, 1)].into_iter().zip(expected) {
+            state.add_consumption(start, duration, &vec![c]);
+            assert_eq!(state.profile[0], expected);
+        }
+    }
+
+    #[test]
+    fn stale_earliest_times_of_done_jobs_are_deduplicated() {
+        let hash = |state: &State| {
+            let mut hasher = DefaultHasher::new();
+            state.hash(&mut hasher);
+            hasher.finish()
+        };
+        let mut done = FixedBitSet::with_capacity(3);
+        done.insert(0);
+        let a = State { done, maybe_done: None, profile: vec![profile(&[(0, 10, 1)], 1)], earliest: vec![0, 2, 3], depth: 1, origin: 0, dead_end: false };
+        let mut b = a.clone();
+        b.earliest[0] = 5;
+        let mut c = a.clone();
+        c.earliest[1] = 1;
+        assert_eq!(a, b);
+        assert_eq!(hash(&a), hash(&b));
+        assert_ne!(a, c);
+
+        let ranking = RcpspRanking::default();
+        let mut fringe = NoDupFringe::new(MaxUB::new(&ranking));
+        for state in [a, b, c] {
+            fringe.push(SubProblem { state: Arc::new(state), value: 0, path: vec![], ub: 0, depth: 1 });
+        }
+        assert_eq!(fringe.len(), 2);
+    }
+
+    #[test]
+    fn job_that_does_not_fit_before_the_end_of_the_profiles_has_no_start() {
+        let state = State { done: FixedBitSet::with_capacity(0), maybe_done: None, profile: vec![profile(&[(0, 3, 1), (3, 6, 0), (6, 8, 1)], 1)], earliest: vec![], depth: 0, origin: 0, dead_end: false };
+        assert_eq!(state.get_earliest_start(1, 2, &vec![1]), Some(1));
+        assert_eq!(state.get_earliest_start(2, 2, &vec![1]), Some(6));
+        assert_eq!(state.get_earliest_start(0, 4, &vec![1]), None);
+    }
+
+    #[test]
+    fn rounded_capacities_stay_between_the_remaining_and_the_full_capacity() {
+        let original = profile(&[(0, 2, 0), (2, 4, 3), (4, 5, 5), (5, 7, 6), (7, 8, 7)], 7);
+        let mut rounded = original.clone();
+        rounded.round_up_capacity(3);
+        assert_eq!(rounded, profile(&[(0, 2, 0), (2, 4, 3), (4, 7, 6), (7, 8, 7)], 7));
+        for t in 0..8 {
+            let level = |p: &ConsumptionProfile| p.steps.iter().find(|s| s.start <= t && t < s.end).unwrap().rem_capacity;
+            assert!(level(&rounded) >= level(&original));
+            assert!(level(&rounded) <= rounded.capacity);
+        }
+
+        let mut unchanged = original.clone();
+        unchanged.round_up_capacity(1);
+        assert_eq!(unchanged, original);
+    }
+
+    #[test]
+    fn diff_gives_the_intervals_where_the_capacities_differ() {
+        let a = profile(&[(0, 2, 1), (2, 5, 2), (5, 8, 0)], 2);
+        let b = profile(&[(0, 3, 1), (3, 8, 2)], 2);
+        assert_eq!(a.diff(&b), vec![(2, 3, 2, 1), (5, 8, 0, 2)]);
+        assert_eq!(b.diff(&a), vec![(2, 3, 1, 2), (5, 8, 2, 0)]);
+        assert_eq!(a.diff(&a), vec![]);
+
+        // consecutive steps with the same capacities give one interval
+        let split = profile(&[(0, 2, 1), (2, 4, 1)], 2);
+        assert_eq!(profile(&[(0, 4, 0)], 2).diff(&split), vec![(0, 4, 0, 1)]);
+
+        // only the times covered by both profiles are compared
+        assert_eq!(profile(&[(0, 3, 1)], 2).diff(&profile(&[(0, 5, 0)], 2)), vec![(0, 3, 1, 0)]);
+    }
+    #[test]
+    fn serialized_states_are_read_back_identically() {
+        let mut done = FixedBitSet::with_capacity(3);
+        done.insert(0);
+        let mut maybe_done = done.clone();
+        maybe_done.insert(2);
+        let states = [
+            State { done: done.clone(), maybe_done: None, profile: vec![profile(&[(0, 2, 1), (2, 5, 2)], 2), profile(&[(0, 1, 0)], 0)], earliest: vec![0, 3, -1], depth: 1, origin: 4, dead_end: false },
+            State { done, maybe_done: Some(maybe_done), profile: vec![profile(&[(0, 3, 3)], 3)], earliest: vec![2, 2, 5], depth: 2, origin: 0, dead_end: true },
+        ];
+        for state in states {
+            let serialized = state.serialize();
+            let read = State::deserialize(&mut serialized.split_whitespace()).unwrap();
+            assert_eq!(read.done, state.done);
+            assert_eq!(read.maybe_done, state.maybe_done);
+            assert_eq!(read.profile, state.profile);
+            assert_eq!(read.earliest, state.earliest);
+            assert_eq!((read.depth, read.origin, read.dead_end), (state.depth, state.origin, state.dead_end));
+
+            // a truncated state is rejected
+            let tokens = serialized.split_whitespace().collect::<Vec<&str>>();
+            assert!(State::deserialize(&mut tokens[..tokens.len() - 1].iter().copied()).is_none());
+        }
+    }
+}
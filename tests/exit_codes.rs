@@ -0,0 +1,53 @@
+use std::{fs, process::{Command, Output}};
+
+fn run(args: &[&str]) -> Output {
+    Command::new(env!("CARGO_BIN_EXE_rcpsp")).args(args).output().unwrap()
+}
+
+fn instance(name: &str, content: &str) -> String {
+    let path = std::env::temp_dir().join(name);
+    fs::write(&path, content).unwrap();
+    path.to_str().unwrap().to_string()
+}
+
+#[test]
+fn proven_optimum_exits_with_0_and_quiet_prints_only_the_value() {
+    let output = run(&["--quiet", "resources/tests/serial.rcp"]);
+    assert_eq!(output.status.code(), Some(0));
+    assert_eq!(String::from_utf8(output.stdout).unwrap(), "4\n");
+    assert!(output.stderr.is_empty());
+}
+
+#[test]
+fn quiet_hides_the_parser_warnings() {
+    // 1.25 scaled by 2 is rounded to 3
+    let path = instance("rcpsp_rounded.rcp", "3 1\n1\n0 0 1 2\n1.25 1 1 3\n0 0 0\n");
+    let output = run(&["--scale", "2", &path]);
+    assert!(String::from_utf8(output.stderr).unwrap().contains("Warning   : 1.25 scaled by 2 is not integral, rounded to 3"));
+
+    let output = run(&["--quiet", "--scale", "2", &path]);
+    assert_eq!(output.status.code(), Some(0));
+    assert_eq!(String::from_utf8(output.stdout).unwrap(), "1.5\n");
+    assert!(output.stderr.is_empty());
+}
+
+#[test]
+fn timeout_with_a_feasible_solution_exits_with_2() {
+    let output = run(&["--quiet", "--duration", "0", "--priority-rule", "lpt", "resources/j120/j120_10_1.rcp"]);
+    assert_eq!(output.status.code(), Some(2));
+}
+
+#[test]
+fn job_exceeding_the_capacity_exits_with_3() {
+    // job 2 needs 2 units of a resource with a capacity of 1
+    let path = instance("rcpsp_infeasible.rcp", "3 1\n1\n0 0 1 2\n2 2 1 3\n0 0 0\n");
+    let output = run(&["--quiet", &path]);
+    assert_eq!(output.status.code(), Some(3));
+}
+
+#[test]
+fn invalid_instance_exits_with_4() {
+    let path = instance("rcpsp_invalid.rcp", "not an instance\n");
+    assert_eq!(run(&["--quiet", &path]).status.code(), Some(4));
+    assert_eq!(run(&["--quiet", "resources/tests/missing.rcp"]).status.code(), Some(4));
+}